@@ -0,0 +1,125 @@
+use digest_auth::AuthContext;
+use jsonrpc_client::{Response, SendRequest};
+use reqwest::{
+    header::{AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE},
+    StatusCode, Url,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+
+/// Credentials of an RPC server started with `--rpc-login`.
+#[derive(Clone, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("HTTP request failed")]
+    Http(#[from] reqwest::Error),
+    #[error("RPC server requires authentication but no credentials are configured")]
+    MissingCredentials,
+    #[error("RPC server rejected the request without an authentication challenge")]
+    MissingChallenge,
+    #[error("Failed to answer the digest authentication challenge")]
+    DigestAuth(#[from] digest_auth::Error),
+}
+
+/// HTTP client that answers the digest authentication challenges of RPC
+/// servers started with `--rpc-login`.
+#[derive(Debug, Clone)]
+pub struct Client {
+    inner: reqwest::Client,
+    credentials: Option<Credentials>,
+}
+
+impl Client {
+    /// Builds a client that trusts `root_certificate` (PEM encoded) in
+    /// addition to the system's root certificates. This allows connecting to
+    /// an RPC server that uses a self-signed certificate via `https`.
+    pub fn new(
+        credentials: Option<Credentials>,
+        root_certificate: Option<&[u8]>,
+    ) -> anyhow::Result<Self> {
+        let mut builder = reqwest::ClientBuilder::new().connection_verbose(true);
+
+        if let Some(pem) = root_certificate {
+            builder = builder.add_root_certificate(reqwest::Certificate::from_pem(pem)?);
+        }
+
+        Ok(Self {
+            inner: builder.build()?,
+            credentials,
+        })
+    }
+
+    async fn post(
+        &self,
+        endpoint: Url,
+        body: String,
+        authorization: Option<String>,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let mut request = self
+            .inner
+            .post(endpoint)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body);
+
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+
+        request.send().await
+    }
+}
+
+#[async_trait::async_trait]
+impl SendRequest for Client {
+    type Error = Error;
+
+    async fn send_request<P>(&self, endpoint: Url, body: String) -> Result<Response<P>, Error>
+    where
+        P: DeserializeOwned,
+    {
+        let response = self.post(endpoint.clone(), body.clone(), None).await?;
+
+        let response = if response.status() == StatusCode::UNAUTHORIZED {
+            let credentials = self.credentials.as_ref().ok_or(Error::MissingCredentials)?;
+
+            let authorization = {
+                let challenge = response
+                    .headers()
+                    .get(WWW_AUTHENTICATE)
+                    .and_then(|header| header.to_str().ok())
+                    .ok_or(Error::MissingChallenge)?;
+
+                let mut prompt = digest_auth::parse(challenge)?;
+                let context = AuthContext::new_post(
+                    credentials.username.as_str(),
+                    credentials.password.as_str(),
+                    endpoint.path(),
+                    Some(body.as_bytes()),
+                );
+
+                prompt.respond(&context)?.to_header_string()
+            };
+
+            self.post(endpoint, body, Some(authorization)).await?
+        } else {
+            response
+        };
+
+        Ok(response.error_for_status()?.json().await?)
+    }
+}
@@ -12,5 +12,8 @@
 )]
 #![forbid(unsafe_code)]
 
+mod http;
 pub mod monerod;
 pub mod wallet;
+
+pub use http::Credentials;
@@ -28,6 +28,16 @@ impl Client {
                 .context("url is well formed")?,
         })
     }
+
+    /// New monerod RPC client for the given `json_rpc` endpoint.
+    pub fn new(base_url: reqwest::Url) -> Result<Self> {
+        Ok(Self {
+            inner: reqwest::ClientBuilder::new()
+                .connection_verbose(true)
+                .build()?,
+            base_url,
+        })
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -1,3 +1,4 @@
+use crate::{http, http::Credentials};
 use anyhow::{Context, Result};
 use serde::{de::Error, Deserialize, Deserializer, Serialize};
 
@@ -6,6 +7,7 @@ pub trait MoneroWalletRpc {
     async fn get_address(&self, account_index: u32) -> GetAddress;
     async fn get_balance(&self, account_index: u32) -> GetBalance;
     async fn create_account(&self, label: String) -> CreateAccount;
+    async fn create_address(&self, account_index: u32, label: String) -> CreateAddress;
     async fn get_accounts(&self, tag: String) -> GetAccounts;
     async fn open_wallet(&self, filename: String) -> WalletOpened;
     async fn close_wallet(&self) -> WalletClosed;
@@ -37,7 +39,7 @@ pub trait MoneroWalletRpc {
 #[jsonrpc_client::implement(MoneroWalletRpc)]
 #[derive(Debug, Clone)]
 pub struct Client {
-    inner: reqwest::Client,
+    inner: http::Client,
     base_url: reqwest::Url,
 }
 
@@ -53,10 +55,19 @@ impl Client {
 
     /// Constructs a monero-wallet-rpc client with `url` endpoint.
     pub fn new(url: reqwest::Url) -> Result<Self> {
+        Client::with_options(url, None, None)
+    }
+
+    /// Constructs a monero-wallet-rpc client with `url` endpoint that
+    /// authenticates using `credentials` and, for `https` endpoints,
+    /// additionally trusts the PEM encoded `root_certificate`.
+    pub fn with_options(
+        url: reqwest::Url,
+        credentials: Option<Credentials>,
+        root_certificate: Option<&[u8]>,
+    ) -> Result<Self> {
         Ok(Self {
-            inner: reqwest::ClientBuilder::new()
-                .connection_verbose(true)
-                .build()?,
+            inner: http::Client::new(credentials, root_certificate)?,
             base_url: url,
         })
     }
@@ -80,6 +91,17 @@ impl Client {
 #[derive(Deserialize, Debug, Clone)]
 pub struct GetAddress {
     pub address: String,
+    #[serde(default)]
+    pub addresses: Vec<SubAddress>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SubAddress {
+    pub address: String,
+    pub address_index: u32,
+    #[serde(default)]
+    pub label: String,
+    pub used: bool,
 }
 
 #[derive(Deserialize, Debug, Clone, Copy)]
@@ -97,6 +119,12 @@ pub struct CreateAccount {
     pub address: String,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct CreateAddress {
+    pub address: String,
+    pub address_index: u32,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct GetAccounts {
     pub subaddress_accounts: Vec<SubAddressAccount>,
@@ -107,7 +135,7 @@ pub struct GetAccounts {
 #[derive(Deserialize, Debug, Clone)]
 pub struct SubAddressAccount {
     pub account_index: u32,
-    pub balance: u32,
+    pub balance: u64,
     pub base_address: String,
     pub label: String,
     pub tag: String,
@@ -246,6 +274,25 @@ mod tests {
         let _: Response<SweepAll> = serde_json::from_str(&response).unwrap();
     }
 
+    #[test]
+    fn can_deserialize_get_address() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+            "address": "55LTR8KniP4LQGJSPtbYDacR7dz8RBFnsfAKMaMuwUNYX6aQbBcovzDPyrQF9KXF9tVU6Xk3K8no1BywnJX6GvZX8yJsXvt",
+            "addresses": [{
+              "address": "55LTR8KniP4LQGJSPtbYDacR7dz8RBFnsfAKMaMuwUNYX6aQbBcovzDPyrQF9KXF9tVU6Xk3K8no1BywnJX6GvZX8yJsXvt",
+              "address_index": 0,
+              "label": "Primary account",
+              "used": true
+            }]
+          }
+        }"#;
+
+        let _: Response<GetAddress> = serde_json::from_str(&response).unwrap();
+    }
+
     #[test]
     fn can_deserialize_create_wallet() {
         let response = r#"{
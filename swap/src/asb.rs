@@ -1,6 +1,12 @@
+pub mod balance_monitor;
 pub mod command;
 pub mod config;
+pub mod pricing;
 mod rate;
+pub mod recorder;
+pub mod simulation;
+pub mod sweep;
 pub mod tracing;
 
+pub use pricing::Pricing;
 pub use rate::Rate;
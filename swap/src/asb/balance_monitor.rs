@@ -0,0 +1,93 @@
+//! Periodically checks the balances of the ASB and notifies the operator once
+//! a balance crosses its threshold, e.g. to refill the XMR or convert the
+//! bought BTC into XMR.
+
+use crate::asb::config::BalanceMonitoring;
+use crate::notification::{Notification, Notifier};
+use crate::{bitcoin, monero};
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Checks the balances every `config.interval_secs` until the ASB is shut
+/// down.
+pub async fn run(
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    monero_wallet: Arc<monero::Wallet>,
+    config: BalanceMonitoring,
+    notifier: Notifier,
+) {
+    let mut xmr_alert = Alert::default();
+    let mut btc_alert = Alert::default();
+
+    loop {
+        match monero_wallet.get_balance().await {
+            Ok(balance) => {
+                if xmr_alert.update(balance < config.xmr_threshold) {
+                    tracing::warn!(%balance, threshold = %config.xmr_threshold, "Monero balance is low");
+                    notifier
+                        .notify(Notification::LowXmrBalance {
+                            balance,
+                            threshold: config.xmr_threshold,
+                        })
+                        .await;
+                }
+            }
+            Err(e) => tracing::warn!("Failed to check Monero balance: {:#}", e),
+        }
+
+        if let Some(threshold) = config.btc_threshold {
+            match btc_balance(bitcoin_wallet.as_ref()).await {
+                Ok(balance) => {
+                    if btc_alert.update(balance > threshold) {
+                        tracing::warn!(%balance, %threshold, "Bitcoin balance is high");
+                        notifier
+                            .notify(Notification::HighBtcBalance { balance, threshold })
+                            .await;
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to check Bitcoin balance: {:#}", e),
+            }
+        }
+
+        tokio::time::sleep(Duration::from_secs(config.interval_secs)).await;
+    }
+}
+
+async fn btc_balance(bitcoin_wallet: &bitcoin::Wallet) -> Result<bitcoin::Amount> {
+    bitcoin_wallet.sync().await?;
+    bitcoin_wallet.balance().await
+}
+
+/// Fires once a threshold is crossed and not again until the balance has
+/// recovered, to not notify the operator on every check.
+#[derive(Debug, Default, Clone, Copy)]
+struct Alert {
+    active: bool,
+}
+
+impl Alert {
+    /// Returns whether to notify about the `exceeded` threshold.
+    fn update(&mut self, exceeded: bool) -> bool {
+        let fire = exceeded && !self.active;
+        self.active = exceeded;
+
+        fire
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alert_fires_once_per_crossing() {
+        let mut alert = Alert::default();
+
+        assert!(!alert.update(false));
+        assert!(alert.update(true));
+        assert!(!alert.update(true));
+        assert!(!alert.update(false));
+        assert!(alert.update(true));
+    }
+}
@@ -1,6 +1,7 @@
 use crate::bitcoin::Amount;
 use bitcoin::util::amount::ParseAmountError;
-use bitcoin::{Address, Denomination};
+use bitcoin::{Address, Denomination, OutPoint};
+use libp2p::PeerId;
 use rust_decimal::Decimal;
 use std::path::PathBuf;
 
@@ -26,16 +27,51 @@ pub struct Arguments {
 #[structopt(name = "xmr_btc-swap", about = "XMR BTC atomic swap")]
 pub enum Command {
     Start {
+        #[structopt(long = "min-buy-btc", help = "The minimum amount of BTC the ASB is willing to buy.", default_value="0.0001", parse(try_from_str = parse_btc))]
+        min_buy: Amount,
         #[structopt(long = "max-buy-btc", help = "The maximum amount of BTC the ASB is willing to buy.", default_value="0.005", parse(try_from_str = parse_btc))]
         max_buy: Amount,
+        #[structopt(
+            long = "max-concurrent-swaps-per-peer",
+            help = "Optionally limit the number of unfinished swaps a single peer may have with the ASB."
+        )]
+        max_concurrent_swaps_per_peer: Option<usize>,
+        #[structopt(long = "max-buy-btc-per-peer-24h", help = "Optionally limit the amount of BTC the ASB is willing to buy from a single peer within 24 hours.", parse(try_from_str = parse_btc))]
+        max_daily_buy_per_peer: Option<Amount>,
         #[structopt(
             long = "ask-spread",
             help = "The spread in percent that should be applied to the asking price.",
             default_value = "0.02"
         )]
         ask_spread: Decimal,
+        #[structopt(
+            long = "volatility-spread-factor",
+            help = "Widens the spread by this multiple of the relative range of the asking price within the last hour.",
+            default_value = "0.5"
+        )]
+        volatility_spread_factor: Decimal,
+        #[structopt(
+            long = "ask-spread-per-btc",
+            help = "Widens the spread by this much per BTC bought, making larger trades more expensive.",
+            default_value = "0"
+        )]
+        ask_spread_per_btc: Decimal,
+        #[structopt(
+            long = "max-rate-age-secs",
+            help = "Refuse to give quotes and spot prices if the asking price was not updated for this many seconds.",
+            default_value = "600"
+        )]
+        max_rate_age_secs: u64,
+        #[structopt(
+            long = "record-prices",
+            help = "Record every asking price received from Kraken and every quote and spot price given to a peer in the `recordings` directory of the data directory."
+        )]
+        record_prices: bool,
     },
     History,
+    /// Check the timelocks of all unfinished swaps, exits with an error if a
+    /// swap has to be resumed now
+    Check,
     WithdrawBtc {
         #[structopt(
             long = "amount",
@@ -44,8 +80,46 @@ pub enum Command {
         amount: Option<Amount>,
         #[structopt(long = "address", help = "The address to receive the Bitcoin.")]
         address: Address,
+        #[structopt(
+            long = "utxo",
+            help = "Only spend this output, given as <txid>:<vout>, can be given multiple times."
+        )]
+        utxos: Vec<OutPoint>,
     },
     Balance,
+    /// Show the unspent outputs of the Bitcoin wallet
+    ListUtxos,
+    /// Exclude an unspent output from being spent unless it is selected
+    /// explicitly
+    FreezeUtxo {
+        #[structopt(
+            long = "outpoint",
+            help = "The output to freeze, given as <txid>:<vout>."
+        )]
+        outpoint: OutPoint,
+    },
+    /// Allow a frozen unspent output to be spent again
+    UnfreezeUtxo {
+        #[structopt(
+            long = "outpoint",
+            help = "The output to unfreeze, given as <txid>:<vout>."
+        )]
+        outpoint: OutPoint,
+    },
+    /// Refuse connections from the given peer once the ASB is started again,
+    /// the ASB has to be stopped first
+    BanPeer {
+        #[structopt(long = "peer-id", help = "The peer-id of the peer to be banned.")]
+        peer_id: PeerId,
+    },
+    /// Allow connections from a previously banned peer again once the ASB is
+    /// started again, the ASB has to be stopped first
+    UnbanPeer {
+        #[structopt(long = "peer-id", help = "The peer-id of the peer to be unbanned.")]
+        peer_id: PeerId,
+    },
+    /// Show the list of banned peers
+    BannedPeers,
 }
 
 fn parse_btc(s: &str) -> Result<Amount, ParseAmountError> {
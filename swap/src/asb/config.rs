@@ -1,10 +1,14 @@
 use crate::fs::{ensure_directory_exists, system_config_dir, system_data_dir};
+use crate::notification::Hooks;
 use crate::tor::{DEFAULT_CONTROL_PORT, DEFAULT_SOCKS5_PORT};
+use crate::{bitcoin, monero};
 use anyhow::{Context, Result};
 use config::ConfigError;
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::Input;
 use libp2p::core::Multiaddr;
+use libp2p::PeerId;
+use monero_rpc::Credentials;
 use serde::{Deserialize, Serialize};
 use std::ffi::OsStr;
 use std::fs;
@@ -16,6 +20,10 @@ const DEFAULT_LISTEN_ADDRESS_TCP: &str = "/ip4/0.0.0.0/tcp/9939";
 const DEFAULT_LISTEN_ADDRESS_WS: &str = "/ip4/0.0.0.0/tcp/9940/ws";
 const DEFAULT_ELECTRUM_RPC_URL: &str = "ssl://electrum.blockstream.info:60002";
 const DEFAULT_MONERO_WALLET_RPC_TESTNET_URL: &str = "http://127.0.0.1:38083/json_rpc";
+const DEFAULT_SWEEP_INTERVAL_SECS: u64 = 60 * 60;
+const DEFAULT_BALANCE_CHECK_INTERVAL_SECS: u64 = 5 * 60;
+pub const DEFAULT_PER_PEER_REQUESTS_PER_MINUTE: usize = 10;
+pub const DEFAULT_GLOBAL_REQUESTS_PER_MINUTE: usize = 100;
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
 pub struct Config {
@@ -24,6 +32,13 @@ pub struct Config {
     pub bitcoin: Bitcoin,
     pub monero: Monero,
     pub tor: TorConf,
+    #[serde(default)]
+    pub rate_limit: RateLimit,
+    pub balance_monitoring: Option<BalanceMonitoring>,
+    /// Where to deliver notifications for the operator, e.g. about low
+    /// balances.
+    #[serde(default)]
+    pub notifications: Hooks,
 }
 
 impl Config {
@@ -49,18 +64,79 @@ pub struct Data {
 #[serde(deny_unknown_fields)]
 pub struct Network {
     pub listen: Vec<Multiaddr>,
+    /// Peers that are not allowed to connect, in addition to those banned
+    /// through the `ban-peer` command or automatically.
+    #[serde(default, with = "peer_ids")]
+    pub banned_peers: Vec<PeerId>,
+}
+
+mod peer_ids {
+    use libp2p::PeerId;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::str::FromStr;
+
+    pub fn serialize<S>(peer_ids: &[PeerId], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        peer_ids
+            .iter()
+            .map(PeerId::to_string)
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<PeerId>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|peer_id| PeerId::from_str(peer_id).map_err(D::Error::custom))
+            .collect()
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Bitcoin {
     pub electrum_rpc_url: Url,
+    /// Additional, independent Electrum servers that are queried to
+    /// cross-check the data returned by `electrum_rpc_url`.
+    #[serde(default)]
+    pub cross_check_electrum_rpc_urls: Vec<Url>,
+    pub sweep: Option<Sweep>,
+}
+
+/// Moves the BTC exceeding `threshold` from the hot wallet of the ASB to a
+/// cold storage wallet.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sweep {
+    /// Extended public key or output descriptor of the cold storage wallet.
+    pub destination: String,
+    /// Balance in satoshi the hot wallet may hold.
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub threshold: bitcoin::Amount,
+    /// Seconds between two checks of the balance.
+    #[serde(default = "default_sweep_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_sweep_interval_secs() -> u64 {
+    DEFAULT_SWEEP_INTERVAL_SECS
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Monero {
     pub wallet_rpc_url: Url,
+    /// Credentials of a wallet RPC started with `--rpc-login`.
+    pub wallet_rpc_login: Option<Credentials>,
+    /// PEM encoded certificate to trust when connecting to the wallet RPC via
+    /// `https`, e.g. the self-signed certificate of the wallet RPC.
+    pub wallet_rpc_tls_certificate: Option<PathBuf>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -79,6 +155,44 @@ impl Default for TorConf {
     }
 }
 
+/// Thresholds at which the operator is notified about the balances.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BalanceMonitoring {
+    /// Notify once the XMR balance, in piconero, falls below.
+    pub xmr_threshold: monero::Amount,
+    /// Notify once the BTC balance, in satoshi, exceeds.
+    #[serde(default, with = "::bitcoin::util::amount::serde::as_sat::opt")]
+    pub btc_threshold: Option<bitcoin::Amount>,
+    /// Seconds between two checks of the balances.
+    #[serde(default = "default_balance_check_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_balance_check_interval_secs() -> u64 {
+    DEFAULT_BALANCE_CHECK_INTERVAL_SECS
+}
+
+/// Budgets for the requests that are costly to serve, i.e. quotes, spot
+/// prices and execution setups. Each of these protocols has its own budget.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    /// Requests a single peer may make per minute.
+    pub per_peer_requests_per_minute: usize,
+    /// Requests all peers together may make per minute.
+    pub global_requests_per_minute: usize,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            per_peer_requests_per_minute: DEFAULT_PER_PEER_REQUESTS_PER_MINUTE,
+            global_requests_per_minute: DEFAULT_GLOBAL_REQUESTS_PER_MINUTE,
+        }
+    }
+}
+
 #[derive(thiserror::Error, Debug, Clone, Copy)]
 #[error("config not initialized")]
 pub struct ConfigNotInitialized {}
@@ -185,15 +299,25 @@ pub fn query_user_for_initial_testnet_config() -> Result<Config> {
         data: Data { dir: data_dir },
         network: Network {
             listen: listen_addresses,
+            banned_peers: vec![],
+        },
+        bitcoin: Bitcoin {
+            electrum_rpc_url,
+            cross_check_electrum_rpc_urls: vec![],
+            sweep: None,
         },
-        bitcoin: Bitcoin { electrum_rpc_url },
         monero: Monero {
             wallet_rpc_url: monero_wallet_rpc_url,
+            wallet_rpc_login: None,
+            wallet_rpc_tls_certificate: None,
         },
         tor: TorConf {
             control_port: tor_control_port,
             socks5_port: tor_socks5_port,
         },
+        rate_limit: Default::default(),
+        balance_monitoring: None,
+        notifications: Default::default(),
     })
 }
 
@@ -214,18 +338,40 @@ mod tests {
             },
             bitcoin: Bitcoin {
                 electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                cross_check_electrum_rpc_urls: vec![],
+                sweep: Some(Sweep {
+                    destination: "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8".to_string(),
+                    threshold: bitcoin::Amount::from_sat(10_000_000),
+                    interval_secs: DEFAULT_SWEEP_INTERVAL_SECS,
+                }),
             },
             network: Network {
                 listen: vec![
                     DEFAULT_LISTEN_ADDRESS_TCP.parse().unwrap(),
                     DEFAULT_LISTEN_ADDRESS_WS.parse().unwrap(),
                 ],
+                banned_peers: vec![PeerId::random()],
             },
 
             monero: Monero {
                 wallet_rpc_url: Url::from_str(DEFAULT_MONERO_WALLET_RPC_TESTNET_URL).unwrap(),
+                wallet_rpc_login: None,
+                wallet_rpc_tls_certificate: None,
             },
             tor: Default::default(),
+            rate_limit: RateLimit {
+                per_peer_requests_per_minute: 5,
+                global_requests_per_minute: 50,
+            },
+            balance_monitoring: Some(BalanceMonitoring {
+                xmr_threshold: monero::Amount::from_piconero(10_000_000_000_000),
+                btc_threshold: None,
+                interval_secs: DEFAULT_BALANCE_CHECK_INTERVAL_SECS,
+            }),
+            notifications: Hooks {
+                webhook_url: Some(Url::from_str("https://example.com/asb").unwrap()),
+                command: None,
+            },
         };
 
         initial_setup(config_path.clone(), || Ok(expected.clone())).unwrap();
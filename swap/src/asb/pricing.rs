@@ -0,0 +1,184 @@
+use crate::asb::Rate;
+use crate::bitcoin;
+use rust_decimal::Decimal;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// The period over which the volatility of the asking price is measured.
+pub const VOLATILITY_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+const ZERO: Decimal = Decimal::from_parts(0, 0, 0, false, 0);
+
+/// Determines the spread the ASB applies to the market asking price.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pricing {
+    /// The spread applied to every trade.
+    pub ask_spread: Decimal,
+    /// Widens the spread by this multiple of the recent volatility.
+    pub volatility_factor: Decimal,
+    /// Widens the spread by this much per BTC bought.
+    pub ask_spread_per_btc: Decimal,
+    /// Asking prices older than this are not used for quotes and spot
+    /// prices.
+    pub max_rate_age: Duration,
+}
+
+impl Pricing {
+    /// Computes the rate for the latest asking price in `history`.
+    ///
+    /// Fails if there was no update of the asking price within
+    /// `max_rate_age`.
+    pub fn rate(&self, history: &PriceHistory, now: Instant) -> Result<Rate, StaleRate> {
+        let (updated_at, ask) = match history.latest() {
+            Some(latest) if now.saturating_duration_since(latest.0) <= self.max_rate_age => latest,
+            _ => {
+                return Err(StaleRate {
+                    max_age: self.max_rate_age,
+                })
+            }
+        };
+
+        let volatility = history.volatility(updated_at);
+        let ask_spread = self.ask_spread + self.volatility_factor * volatility;
+
+        Ok(Rate::new(ask, ask_spread).with_ask_spread_per_btc(self.ask_spread_per_btc))
+    }
+}
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("No update of the asking price within the last {} seconds", max_age.as_secs())]
+pub struct StaleRate {
+    pub max_age: Duration,
+}
+
+/// The asking prices received within the volatility window.
+#[derive(Debug)]
+pub struct PriceHistory {
+    window: Duration,
+    updates: VecDeque<(Instant, bitcoin::Amount)>,
+}
+
+impl PriceHistory {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            updates: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, at: Instant, ask: bitcoin::Amount) {
+        self.updates.push_back((at, ask));
+
+        while let Some((oldest, _)) = self.updates.front() {
+            if at.saturating_duration_since(*oldest) <= self.window {
+                break;
+            }
+
+            self.updates.pop_front();
+        }
+    }
+
+    pub fn latest(&self) -> Option<(Instant, bitcoin::Amount)> {
+        self.updates.back().copied()
+    }
+
+    /// The range of the asking prices within the window before `now`,
+    /// relative to the lowest of them.
+    ///
+    /// E.g. if the asking price moved between 0.0100 and 0.0102 BTC, the
+    /// volatility is 0.02.
+    pub fn volatility(&self, now: Instant) -> Decimal {
+        let asks = self
+            .updates
+            .iter()
+            .filter(|(at, _)| now.saturating_duration_since(*at) <= self.window)
+            .map(|(_, ask)| ask.as_sat());
+
+        let (min, max) = asks.fold((u64::MAX, 0), |(min, max), ask| {
+            (min.min(ask), max.max(ask))
+        });
+
+        if min == 0 || min > max {
+            return ZERO;
+        }
+
+        Decimal::from(max - min) / Decimal::from(min)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE_PERCENT: Decimal = Decimal::from_parts(1, 0, 0, false, 2);
+    const TWO_PERCENT: Decimal = Decimal::from_parts(2, 0, 0, false, 2);
+    const ONE: Decimal = Decimal::from_parts(1, 0, 0, false, 0);
+
+    fn pricing(volatility_factor: Decimal) -> Pricing {
+        Pricing {
+            ask_spread: ONE_PERCENT,
+            volatility_factor,
+            ask_spread_per_btc: ZERO,
+            max_rate_age: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn volatility_is_relative_range_of_asking_prices() {
+        let now = Instant::now();
+        let mut history = PriceHistory::new(VOLATILITY_WINDOW);
+
+        history.record(now, bitcoin::Amount::from_sat(10_000));
+        history.record(now, bitcoin::Amount::from_sat(10_200));
+        history.record(now, bitcoin::Amount::from_sat(10_100));
+
+        assert_eq!(history.volatility(now), TWO_PERCENT);
+    }
+
+    #[test]
+    fn updates_outside_of_window_are_ignored() {
+        let now = Instant::now();
+        let mut history = PriceHistory::new(Duration::from_secs(60));
+
+        history.record(now, bitcoin::Amount::from_sat(5_000));
+        history.record(
+            now + Duration::from_secs(120),
+            bitcoin::Amount::from_sat(10_000),
+        );
+
+        assert_eq!(history.volatility(now + Duration::from_secs(120)), ZERO);
+    }
+
+    #[test]
+    fn volatility_widens_spread() {
+        let now = Instant::now();
+        let mut history = PriceHistory::new(VOLATILITY_WINDOW);
+        history.record(now, bitcoin::Amount::from_sat(10_000));
+        history.record(now, bitcoin::Amount::from_sat(10_200));
+
+        let rate = pricing(ONE).rate(&history, now).unwrap();
+
+        // 1% spread plus 2% volatility
+        assert_eq!(rate.ask().unwrap().as_sat(), 10_506);
+    }
+
+    #[test]
+    fn refuses_stale_rate() {
+        let now = Instant::now();
+        let mut history = PriceHistory::new(VOLATILITY_WINDOW);
+        history.record(now, bitcoin::Amount::from_sat(10_000));
+
+        let result = pricing(ONE).rate(&history, now + Duration::from_secs(61));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn refuses_rate_without_updates() {
+        let history = PriceHistory::new(VOLATILITY_WINDOW);
+
+        let result = pricing(ONE).rate(&history, Instant::now());
+
+        assert!(result.is_err());
+    }
+}
@@ -11,6 +11,8 @@ pub struct Rate {
     ask: bitcoin::Amount,
     /// The spread which should be applied to the market asking price.
     ask_spread: Decimal,
+    /// The spread added on top of `ask_spread` per BTC bought.
+    ask_spread_per_btc: Decimal,
 }
 
 const ZERO_SPREAD: Decimal = Decimal::from_parts(0, 0, 0, false, 0);
@@ -19,20 +21,69 @@ impl Rate {
     pub const ZERO: Rate = Rate {
         ask: bitcoin::Amount::ZERO,
         ask_spread: ZERO_SPREAD,
+        ask_spread_per_btc: ZERO_SPREAD,
     };
 
     pub fn new(ask: bitcoin::Amount, ask_spread: Decimal) -> Self {
-        Self { ask, ask_spread }
+        Self {
+            ask,
+            ask_spread,
+            ask_spread_per_btc: ZERO_SPREAD,
+        }
+    }
+
+    /// Widens the spread by `ask_spread_per_btc` per BTC bought, making large
+    /// trades more expensive.
+    pub fn with_ask_spread_per_btc(self, ask_spread_per_btc: Decimal) -> Self {
+        Self {
+            ask_spread_per_btc,
+            ..self
+        }
     }
 
     /// Computes the asking price at which we are willing to sell 1 XMR.
     ///
     /// This applies the spread to the market asking price.
     pub fn ask(&self) -> Result<bitcoin::Amount> {
+        self.ask_with_spread(self.ask_spread)
+    }
+
+    /// Calculate a sell quote for a given BTC amount.
+    ///
+    /// The spread depends on the amount, see
+    /// [`Rate::with_ask_spread_per_btc`].
+    pub fn sell_quote(&self, quote: bitcoin::Amount) -> Result<monero::Amount> {
+        let quote_in_btc = Decimal::from(quote.as_sat())
+            .checked_div(Decimal::from(bitcoin::Amount::ONE_BTC.as_sat()))
+            .context("Division overflow")?;
+        let spread = self.ask_spread + self.ask_spread_per_btc * quote_in_btc;
+
+        Self::quote(self.ask_with_spread(spread)?, quote)
+    }
+
+    /// The most BTC we accept in exchange for at most `xmr`.
+    ///
+    /// This is the inverse of [`Rate::sell_quote`] at the asking price, the
+    /// spread per BTC only lowers the XMR sold for the returned amount.
+    pub fn max_quote(&self, xmr: monero::Amount) -> Result<bitcoin::Amount> {
+        let ask_in_sats = Decimal::from(self.ask()?.as_sat());
+        let sats = xmr
+            .as_piconero_decimal()
+            .checked_mul(ask_in_sats)
+            .context("Multiplication overflow")?
+            .checked_div(Decimal::from(monero::Amount::ONE_XMR.as_piconero()))
+            .context("Division overflow")?
+            .to_u64()
+            .context("Failed to fit BTC amount into a u64")?;
+
+        Ok(bitcoin::Amount::from_sat(sats))
+    }
+
+    fn ask_with_spread(&self, spread: Decimal) -> Result<bitcoin::Amount> {
         let sats = self.ask.as_sat();
         let sats = Decimal::from(sats);
 
-        let additional_sats = sats * self.ask_spread;
+        let additional_sats = sats * spread;
         let additional_sats = bitcoin::Amount::from_sat(
             additional_sats
                 .to_u64()
@@ -42,11 +93,6 @@ impl Rate {
         Ok(self.ask + additional_sats)
     }
 
-    /// Calculate a sell quote for a given BTC amount.
-    pub fn sell_quote(&self, quote: bitcoin::Amount) -> Result<monero::Amount> {
-        Self::quote(self.ask()?, quote)
-    }
-
     fn quote(rate: bitcoin::Amount, quote: bitcoin::Amount) -> Result<monero::Amount> {
         // quote (btc) = rate * base (xmr)
         // base = quote / rate
@@ -108,6 +154,50 @@ mod tests {
         assert_eq!(amount.as_sat(), 102);
     }
 
+    #[test]
+    fn spread_per_btc_makes_larger_trades_more_expensive() {
+        let asking_price = bitcoin::Amount::from_btc(0.004).unwrap();
+        let rate = Rate::new(asking_price, TWO_PERCENT).with_ask_spread_per_btc(TWO_PERCENT);
+
+        let xmr_for_one_btc = rate.sell_quote(bitcoin::Amount::ONE_BTC).unwrap();
+        let xmr_for_two_btc = rate
+            .sell_quote(bitcoin::Amount::from_btc(2.0).unwrap())
+            .unwrap();
+
+        // 2 BTC are bought at a spread of 6% instead of 4%
+        assert_eq!(
+            xmr_for_one_btc,
+            Rate::new(asking_price, TWO_PERCENT + TWO_PERCENT)
+                .sell_quote(bitcoin::Amount::ONE_BTC)
+                .unwrap()
+        );
+        assert!(xmr_for_two_btc.as_piconero() < 2 * xmr_for_one_btc.as_piconero());
+    }
+
+    #[test]
+    fn max_quote_does_not_sell_more_xmr_than_given() {
+        let asking_price = bitcoin::Amount::from_btc(0.004).unwrap();
+        let xmr = monero::Amount::from_monero(10.0).unwrap();
+
+        let rate = Rate::new(asking_price, ZERO_SPREAD);
+        assert_eq!(
+            rate.max_quote(xmr).unwrap(),
+            bitcoin::Amount::from_btc(0.04).unwrap()
+        );
+
+        let rate = Rate::new(asking_price, TWO_PERCENT).with_ask_spread_per_btc(TWO_PERCENT);
+        let btc = rate.max_quote(xmr).unwrap();
+        assert!(rate.sell_quote(btc).unwrap() <= xmr);
+    }
+
+    #[test]
+    fn spread_per_btc_does_not_affect_asking_price() {
+        let asking_price = bitcoin::Amount::from_sat(100);
+        let rate = Rate::new(asking_price, TWO_PERCENT).with_ask_spread_per_btc(TWO_PERCENT);
+
+        assert_eq!(rate.ask().unwrap().as_sat(), 102);
+    }
+
     #[test]
     fn given_spread_of_two_percent_when_caluclating_sell_quote_factor_between_should_be_two_percent(
     ) {
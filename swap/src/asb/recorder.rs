@@ -0,0 +1,256 @@
+//! Recording of the asking prices received from Kraken and of the prices
+//! given to takers.
+//!
+//! The asking prices are written to `price_ticks.csv.<date>` in the format
+//! read by [`crate::asb::simulation::read_price_ticks`], hence they can be
+//! replayed with `asb_backtest`. The quotes and spot prices given to peers are
+//! written as JSON lines to `served_prices.jsonl.<date>`, see
+//! [`read_served_prices`]. A new file is started every day.
+//!
+//! The files are written on a dedicated thread so that recording never blocks
+//! the event loop.
+
+use crate::network::quote::BidQuote;
+use crate::network::spot_price;
+use crate::{bitcoin, kraken};
+use anyhow::{Context, Result};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tracing_appender::rolling::{self, RollingFileAppender};
+
+const PRICE_TICKS_FILE_NAME: &str = "price_ticks.csv";
+const SERVED_PRICES_FILE_NAME: &str = "served_prices.jsonl";
+/// The records that may queue up while the writer thread is busy, further
+/// records are dropped.
+const QUEUE_SIZE: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    records: mpsc::Sender<Record>,
+}
+
+#[derive(Debug)]
+enum Record {
+    PriceTick(String),
+    ServedPrice(ServedPrice),
+}
+
+/// A quote or spot price given to a peer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServedPrice {
+    /// Seconds since the unix epoch.
+    pub timestamp: u64,
+    pub peer: String,
+    pub price: Price,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Price {
+    Quote(BidQuote),
+    SpotPrice {
+        request: spot_price::Request,
+        response: spot_price::Response,
+    },
+}
+
+impl Recorder {
+    /// Records into files within `dir`, which is created if it does not
+    /// exist.
+    ///
+    /// The writer thread stops once the recorder and all its clones are
+    /// dropped.
+    pub fn new(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+
+        let price_ticks = rolling::daily(dir, PRICE_TICKS_FILE_NAME);
+        let served_prices = rolling::daily(dir, SERVED_PRICES_FILE_NAME);
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+
+        thread::Builder::new()
+            .name("recorder".to_owned())
+            .spawn(move || write_records(receiver, price_ticks, served_prices))
+            .context("Failed to spawn recorder thread")?;
+
+        Ok(Self { records: sender })
+    }
+
+    pub fn record_price_tick(&self, ask: bitcoin::Amount) {
+        let line = price_tick_line(unix_timestamp(), ask);
+
+        self.record(Record::PriceTick(line));
+    }
+
+    pub fn record_served_price(&self, peer: PeerId, price: Price) {
+        self.record(Record::ServedPrice(ServedPrice {
+            timestamp: unix_timestamp(),
+            peer: peer.to_string(),
+            price,
+        }));
+    }
+
+    fn record(&self, record: Record) {
+        match self.records.try_send(record) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                tracing::warn!("Dropping record because the recorder is falling behind")
+            }
+            Err(TrySendError::Closed(_)) => {
+                tracing::warn!("Dropping record because the recorder stopped")
+            }
+        }
+    }
+}
+
+fn write_records(
+    mut records: mpsc::Receiver<Record>,
+    mut price_ticks: RollingFileAppender,
+    mut served_prices: RollingFileAppender,
+) {
+    while let Some(record) = records.blocking_recv() {
+        match record {
+            Record::PriceTick(line) => {
+                if let Err(e) = write_line(&mut price_ticks, &line) {
+                    tracing::warn!("Failed to record price tick: {:#}", e);
+                }
+            }
+            Record::ServedPrice(served_price) => {
+                let result = serde_json::to_string(&served_price)
+                    .context("Failed to serialize served price")
+                    .and_then(|line| write_line(&mut served_prices, &line));
+
+                if let Err(e) = result {
+                    tracing::warn!(peer = %served_price.peer, "Failed to record served price: {:#}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Records every asking price received from Kraken until the connection to
+/// Kraken is closed for good.
+pub async fn record_price_ticks(mut price_updates: kraken::PriceUpdates, recorder: Recorder) {
+    while let Ok(update) = price_updates.wait_for_next_update().await {
+        if let Ok(update) = update {
+            recorder.record_price_tick(update.ask);
+        }
+    }
+}
+
+/// Reads the quotes and spot prices recorded by a [`Recorder`].
+pub fn read_served_prices(reader: impl BufRead) -> Result<Vec<ServedPrice>> {
+    let mut served_prices = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let served_price = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid served price in line {}", index + 1))?;
+        served_prices.push(served_price);
+    }
+
+    Ok(served_prices)
+}
+
+fn price_tick_line(timestamp: u64, ask: bitcoin::Amount) -> String {
+    format!(
+        "{},{}",
+        timestamp,
+        ask.to_string_in(::bitcoin::Denomination::Bitcoin)
+    )
+}
+
+fn write_line(file: &mut impl Write, line: &str) -> Result<()> {
+    writeln!(file, "{}", line)?;
+    file.flush()?;
+
+    Ok(())
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asb::simulation::{read_price_ticks, PriceTick};
+    use crate::monero;
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    #[test]
+    fn recorded_price_ticks_can_be_replayed() {
+        let lines = [
+            price_tick_line(1_600_000_000, bitcoin::Amount::from_sat(712_345)),
+            price_tick_line(1_600_000_060, bitcoin::Amount::from_sat(712_400)),
+        ]
+        .join("\n");
+
+        let ticks = read_price_ticks(lines.as_bytes()).unwrap();
+
+        assert_eq!(ticks, vec![
+            PriceTick {
+                timestamp: 1_600_000_000,
+                ask: bitcoin::Amount::from_sat(712_345)
+            },
+            PriceTick {
+                timestamp: 1_600_000_060,
+                ask: bitcoin::Amount::from_sat(712_400)
+            }
+        ]);
+    }
+
+    #[test]
+    fn served_prices_roundtrip() {
+        let served_prices = vec![
+            ServedPrice {
+                timestamp: 1_600_000_000,
+                peer: PeerId::random().to_string(),
+                price: Price::Quote(BidQuote {
+                    id: Some(Uuid::new_v4()),
+                    price: bitcoin::Amount::from_sat(712_345),
+                    min_quantity: bitcoin::Amount::from_sat(10_000),
+                    max_quantity: bitcoin::Amount::from_sat(500_000),
+                    valid_for: Some(Duration::from_secs(60)),
+                }),
+            },
+            ServedPrice {
+                timestamp: 1_600_000_010,
+                peer: PeerId::random().to_string(),
+                price: Price::SpotPrice {
+                    request: spot_price::Request {
+                        btc: bitcoin::Amount::from_sat(100_000),
+                        quote_id: None,
+                    },
+                    response: spot_price::Response::Xmr(monero::Amount::from_piconero(
+                        140_000_000_000,
+                    )),
+                },
+            },
+        ];
+
+        let lines = served_prices
+            .iter()
+            .map(|served_price| serde_json::to_string(served_price).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        assert_eq!(read_served_prices(lines.as_bytes()).unwrap(), served_prices);
+    }
+}
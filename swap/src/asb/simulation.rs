@@ -0,0 +1,502 @@
+//! Backtesting of pricing strategies against recorded asking prices.
+//!
+//! The asking prices are replayed from a CSV file and a synthetic flow of
+//! takers requests spot prices. Each trade is evaluated at the asking price
+//! at the time it settles, which results in the profit or loss of the
+//! strategy.
+
+use crate::asb::pricing::{PriceHistory, StaleRate, VOLATILITY_WINDOW};
+use crate::asb::{Pricing, Rate};
+use crate::network::spot_price;
+use crate::protocol::alice::event_loop::{
+    decide_spot_price, spot_price_error, LatestRate, PeerLimits, RateUnavailable, SpotPriceContext,
+};
+use crate::{bitcoin, monero};
+use ::bitcoin::util::amount::SignedAmount;
+use anyhow::{bail, Context, Result};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+use std::time::{Duration, Instant};
+
+/// An asking price at a point in time given in seconds since the unix
+/// epoch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceTick {
+    pub timestamp: u64,
+    pub ask: bitcoin::Amount,
+}
+
+/// Reads price ticks from CSV lines of the form `timestamp,ask` where
+/// `timestamp` is given in seconds since the unix epoch and `ask` is the
+/// price of 1 XMR in BTC.
+///
+/// A header line, empty lines and lines starting with `#` are skipped. The
+/// ticks are returned in chronological order.
+pub fn read_price_ticks(reader: impl BufRead) -> Result<Vec<PriceTick>> {
+    let mut ticks = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') || line.starts_with("timestamp") {
+            continue;
+        }
+
+        let tick = parse_price_tick(line)
+            .with_context(|| format!("Invalid price tick in line {}: {}", index + 1, line))?;
+        ticks.push(tick);
+    }
+
+    ticks.sort_by_key(|tick| tick.timestamp);
+
+    Ok(ticks)
+}
+
+fn parse_price_tick(line: &str) -> Result<PriceTick> {
+    let mut fields = line.split(',').map(str::trim);
+
+    let (timestamp, ask) = match (fields.next(), fields.next(), fields.next()) {
+        (Some(timestamp), Some(ask), None) => (timestamp, ask),
+        _ => bail!("Expected two fields"),
+    };
+
+    Ok(PriceTick {
+        timestamp: timestamp.parse().context("Invalid timestamp")?,
+        ask: bitcoin::Amount::from_str_in(ask, ::bitcoin::Denomination::Bitcoin)
+            .context("Invalid asking price")?,
+    })
+}
+
+/// Replays recorded price ticks as if they were received from Kraken.
+///
+/// The replay starts at the first tick, [`ReplayRate::advance_to`] moves the
+/// clock forward.
+#[derive(Debug)]
+pub struct ReplayRate {
+    pricing: Pricing,
+    ticks: Vec<PriceTick>,
+    history: PriceHistory,
+    /// The ticks before this index were replayed.
+    next_tick: usize,
+    now: u64,
+    /// The instant corresponding to the timestamp of the first tick.
+    start: Instant,
+}
+
+impl ReplayRate {
+    pub fn new(pricing: Pricing, ticks: Vec<PriceTick>) -> Self {
+        let now = ticks.first().map(|tick| tick.timestamp).unwrap_or_default();
+
+        let mut rate = Self {
+            pricing,
+            ticks,
+            history: PriceHistory::new(VOLATILITY_WINDOW),
+            next_tick: 0,
+            now,
+            start: Instant::now(),
+        };
+        rate.advance_to(now);
+
+        rate
+    }
+
+    /// Replays all ticks up to and including `timestamp`.
+    pub fn advance_to(&mut self, timestamp: u64) {
+        while let Some(tick) = self.ticks.get(self.next_tick).copied() {
+            if tick.timestamp > timestamp {
+                break;
+            }
+
+            let at = self.instant(tick.timestamp);
+            self.history.record(at, tick.ask);
+            self.next_tick += 1;
+        }
+
+        self.now = self.now.max(timestamp);
+    }
+
+    /// The market asking price at `timestamp`, without any spread.
+    pub fn market_ask(&self, timestamp: u64) -> Option<bitcoin::Amount> {
+        self.ticks
+            .iter()
+            .take_while(|tick| tick.timestamp <= timestamp)
+            .last()
+            .map(|tick| tick.ask)
+    }
+
+    fn instant(&self, timestamp: u64) -> Instant {
+        let first = self
+            .ticks
+            .first()
+            .map(|tick| tick.timestamp)
+            .unwrap_or_default();
+
+        self.start + Duration::from_secs(timestamp.saturating_sub(first))
+    }
+}
+
+impl LatestRate for ReplayRate {
+    type Error = StaleRate;
+
+    fn latest_rate(&mut self) -> Result<Rate, Self::Error> {
+        self.pricing.rate(&self.history, self.instant(self.now))
+    }
+}
+
+/// The taker with index `taker` requesting a spot price for `btc` at
+/// `timestamp`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TakerRequest {
+    pub timestamp: u64,
+    pub btc: bitcoin::Amount,
+    pub taker: usize,
+}
+
+/// Generates `count` requests of `takers` different takers spread uniformly
+/// between `start` and `end` for amounts uniformly distributed between `min`
+/// and `max`.
+///
+/// The same `seed` always results in the same requests.
+pub fn synthetic_taker_flow(
+    start: u64,
+    end: u64,
+    count: usize,
+    takers: usize,
+    min: bitcoin::Amount,
+    max: bitcoin::Amount,
+    seed: u64,
+) -> Vec<TakerRequest> {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let mut requests = (0..count)
+        .map(|_| TakerRequest {
+            timestamp: rng.gen_range(start, end.max(start) + 1),
+            taker: rng.gen_range(0, takers.max(1)),
+            btc: bitcoin::Amount::from_sat(
+                rng.gen_range(min.as_sat(), max.as_sat().max(min.as_sat()) + 1),
+            ),
+        })
+        .collect::<Vec<_>>();
+    requests.sort_by_key(|request| request.timestamp);
+
+    requests
+}
+
+/// The parameters of the ASB that are evaluated by the simulation.
+#[derive(Debug, Clone, Copy)]
+pub struct Simulation {
+    pub pricing: Pricing,
+    pub min_buy: bitcoin::Amount,
+    pub max_buy: bitcoin::Amount,
+    pub peer_limits: PeerLimits,
+    /// The XMR available for swaps at the start of the simulation.
+    pub xmr_balance: monero::Amount,
+    /// The fee reserved for each XMR lock transaction.
+    pub xmr_lock_fee: monero::Amount,
+    /// The time between giving the spot price and redeeming the BTC, during
+    /// which the ASB is exposed to price movements.
+    pub settlement_delay: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub accepted: usize,
+    /// The number of rejected requests per reason.
+    pub rejected: BTreeMap<&'static str, usize>,
+    pub btc_bought: bitcoin::Amount,
+    pub xmr_sold: monero::Amount,
+    /// The BTC bought minus the value of the XMR sold at the market asking
+    /// price at the time the trades settled.
+    pub profit_and_loss: SignedAmount,
+}
+
+impl Simulation {
+    /// Serves `requests` at the prices replayed from `ticks`.
+    ///
+    /// The XMR and the lock fee of a trade are taken from the balance as
+    /// soon as the spot price is given, like the ASB reserves them. A trade
+    /// counts as unfinished for the peer limits until it settles. Bitcoin
+    /// transaction fees are not taken into account.
+    pub fn run(&self, ticks: Vec<PriceTick>, requests: &[TakerRequest]) -> Result<Report> {
+        let mut replay = ReplayRate::new(self.pricing, ticks);
+        let mut xmr_balance = self.xmr_balance;
+        let mut trades_by_taker = HashMap::<usize, Vec<Trade>>::new();
+        let mut report = Report {
+            accepted: 0,
+            rejected: BTreeMap::new(),
+            btc_bought: bitcoin::Amount::ZERO,
+            xmr_sold: monero::Amount::ZERO,
+            profit_and_loss: SignedAmount::ZERO,
+        };
+        let mut profit_and_loss = Decimal::from(0);
+
+        for request in requests {
+            replay.advance_to(request.timestamp);
+
+            let trades = trades_by_taker.entry(request.taker).or_default();
+            let context = SpotPriceContext {
+                min_buy: self.min_buy,
+                max_buy: self.max_buy,
+                peer_limits: self.peer_limits,
+                unfinished_swaps_with_peer: trades
+                    .iter()
+                    .filter(|trade| trade.settled_at > request.timestamp)
+                    .count(),
+                peer_daily_volume: trades
+                    .iter()
+                    .filter(|trade| trade.timestamp + ONE_DAY_SECS > request.timestamp)
+                    .fold(bitcoin::Amount::ZERO, |volume, trade| volume + trade.btc),
+                xmr_available: xmr_balance,
+                xmr_lock_fee: self.xmr_lock_fee,
+            };
+
+            let xmr = match spot_price(&mut replay, request.btc, &context) {
+                Ok(xmr) => xmr,
+                Err(e) => {
+                    let reason = rejection_reason(&spot_price_error(&e, request.btc));
+                    *report.rejected.entry(reason).or_default() += 1;
+                    continue;
+                }
+            };
+
+            let settled_at = request.timestamp + self.settlement_delay.as_secs();
+            let market_ask = replay
+                .market_ask(settled_at)
+                .context("No asking price at settlement")?;
+
+            trades.push(Trade {
+                timestamp: request.timestamp,
+                settled_at,
+                btc: request.btc,
+            });
+            xmr_balance = xmr_balance - xmr - self.xmr_lock_fee;
+            report.accepted += 1;
+            report.btc_bought += request.btc;
+            report.xmr_sold = report.xmr_sold + xmr;
+            profit_and_loss +=
+                Decimal::from(request.btc.as_sat()) - xmr_value_in_sat(xmr, market_ask);
+        }
+
+        report.profit_and_loss = SignedAmount::from_sat(
+            profit_and_loss
+                .round()
+                .to_i64()
+                .context("Profit and loss does not fit into i64")?,
+        );
+
+        Ok(report)
+    }
+}
+
+const ONE_DAY_SECS: u64 = 24 * 60 * 60;
+
+/// A trade accepted from a taker.
+#[derive(Debug, Clone, Copy)]
+struct Trade {
+    timestamp: u64,
+    settled_at: u64,
+    btc: bitcoin::Amount,
+}
+
+/// Decides on a spot price at the replayed rate, the same way the ASB does.
+fn spot_price(
+    replay: &mut ReplayRate,
+    btc: bitcoin::Amount,
+    context: &SpotPriceContext,
+) -> Result<monero::Amount> {
+    let rate = replay.latest_rate().context(RateUnavailable)?;
+
+    decide_spot_price(rate, btc, context)
+}
+
+fn xmr_value_in_sat(xmr: monero::Amount, ask: bitcoin::Amount) -> Decimal {
+    xmr.as_piconero_decimal() * Decimal::from(ask.as_sat())
+        / Decimal::from(monero::Amount::ONE_XMR.as_piconero())
+}
+
+fn rejection_reason(error: &spot_price::Error) -> &'static str {
+    match error {
+        spot_price::Error::MaxBuyAmountExceeded { .. } => "amount above maximum",
+        spot_price::Error::MinBuyAmountNotReached { .. } => "amount below minimum",
+        spot_price::Error::BalanceTooLow { .. } => "insufficient XMR",
+        spot_price::Error::TooManyConcurrentSwaps { .. } => "too many unfinished swaps of taker",
+        spot_price::Error::DailyVolumeExceeded { .. } => "daily volume of taker exceeded",
+        spot_price::Error::RateUnavailable => "no rate",
+        spot_price::Error::RateStale => "stale rate",
+        _ => "other",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_PERCENT: Decimal = Decimal::from_parts(2, 0, 0, false, 2);
+    const ZERO: Decimal = Decimal::from_parts(0, 0, 0, false, 0);
+
+    fn pricing(ask_spread: Decimal) -> Pricing {
+        Pricing {
+            ask_spread,
+            volatility_factor: ZERO,
+            ask_spread_per_btc: ZERO,
+            max_rate_age: Duration::from_secs(600),
+        }
+    }
+
+    fn simulation(ask_spread: Decimal) -> Simulation {
+        Simulation {
+            pricing: pricing(ask_spread),
+            min_buy: bitcoin::Amount::from_btc(0.001).unwrap(),
+            max_buy: bitcoin::Amount::from_btc(0.1).unwrap(),
+            peer_limits: PeerLimits::default(),
+            xmr_balance: monero::Amount::from_monero(100.0).unwrap(),
+            xmr_lock_fee: monero::Amount::from_piconero(30_000_000),
+            settlement_delay: Duration::from_secs(60),
+        }
+    }
+
+    fn constant_ticks(ask: f64) -> Vec<PriceTick> {
+        (0..10)
+            .map(|i| PriceTick {
+                timestamp: 1_000 + i * 60,
+                ask: bitcoin::Amount::from_btc(ask).unwrap(),
+            })
+            .collect()
+    }
+
+    fn request(timestamp: u64, btc: f64) -> TakerRequest {
+        TakerRequest {
+            timestamp,
+            btc: bitcoin::Amount::from_btc(btc).unwrap(),
+            taker: 0,
+        }
+    }
+
+    #[test]
+    fn reads_price_ticks_from_csv() {
+        let csv = "timestamp,ask\n# comment\n1620000060,0.0052\n\n1620000000,0.0051\n";
+
+        let ticks = read_price_ticks(csv.as_bytes()).unwrap();
+
+        assert_eq!(ticks, vec![
+            PriceTick {
+                timestamp: 1_620_000_000,
+                ask: bitcoin::Amount::from_btc(0.0051).unwrap()
+            },
+            PriceTick {
+                timestamp: 1_620_000_060,
+                ask: bitcoin::Amount::from_btc(0.0052).unwrap()
+            }
+        ]);
+    }
+
+    #[test]
+    fn rejects_malformed_csv() {
+        let result = read_price_ticks("1620000000;0.0051\n".as_bytes());
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn replay_refuses_stale_rate() {
+        let mut replay = ReplayRate::new(pricing(ZERO), constant_ticks(0.005));
+
+        assert!(replay.latest_rate().is_ok());
+
+        replay.advance_to(1_000 + 9 * 60 + 601);
+        assert!(replay.latest_rate().is_err());
+    }
+
+    #[test]
+    fn spread_results_in_profit_at_constant_price() {
+        let report = simulation(TWO_PERCENT)
+            .run(constant_ticks(0.005), &[request(1_100, 0.01)])
+            .unwrap();
+
+        assert_eq!(report.accepted, 1);
+        assert!(report.profit_and_loss > SignedAmount::ZERO);
+    }
+
+    #[test]
+    fn counts_rejected_requests() {
+        let report = simulation(TWO_PERCENT)
+            .run(constant_ticks(0.005), &[
+                request(1_100, 0.0001),
+                request(1_100, 1.0),
+                request(1_100, 0.01),
+            ])
+            .unwrap();
+
+        assert_eq!(report.accepted, 1);
+        assert_eq!(report.rejected["amount below minimum"], 1);
+        assert_eq!(report.rejected["amount above maximum"], 1);
+    }
+
+    #[test]
+    fn applies_peer_limits_until_trades_settled() {
+        let simulation = Simulation {
+            peer_limits: PeerLimits {
+                max_concurrent_swaps: Some(1),
+                max_daily_volume: None,
+            },
+            ..simulation(TWO_PERCENT)
+        };
+
+        let report = simulation
+            .run(constant_ticks(0.005), &[
+                request(1_000, 0.01),
+                request(1_030, 0.01),
+                TakerRequest {
+                    taker: 1,
+                    ..request(1_030, 0.01)
+                },
+                request(1_100, 0.01),
+            ])
+            .unwrap();
+
+        assert_eq!(report.accepted, 3);
+        assert_eq!(report.rejected["too many unfinished swaps of taker"], 1);
+    }
+
+    #[test]
+    fn reserves_xmr_lock_fee() {
+        let xmr_lock_fee = monero::Amount::from_piconero(30_000_000);
+        let simulation = Simulation {
+            pricing: pricing(ZERO),
+            xmr_balance: monero::Amount::from_monero(2.0).unwrap() + xmr_lock_fee,
+            ..simulation(ZERO)
+        };
+
+        let report = simulation
+            .run(constant_ticks(0.005), &[
+                request(1_100, 0.005),
+                request(1_100, 0.005),
+                request(1_100, 0.005),
+            ])
+            .unwrap();
+
+        assert_eq!(report.accepted, 1);
+        assert_eq!(report.rejected["insufficient XMR"], 2);
+    }
+
+    #[test]
+    fn synthetic_taker_flow_is_deterministic() {
+        let min = bitcoin::Amount::from_btc(0.001).unwrap();
+        let max = bitcoin::Amount::from_btc(0.01).unwrap();
+
+        let flow = synthetic_taker_flow(1_000, 2_000, 10, 3, min, max, 42);
+
+        assert_eq!(
+            flow,
+            synthetic_taker_flow(1_000, 2_000, 10, 3, min, max, 42)
+        );
+        assert!(flow
+            .iter()
+            .all(|request| request.btc >= min && request.btc <= max && request.taker < 3));
+    }
+}
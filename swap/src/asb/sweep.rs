@@ -0,0 +1,275 @@
+//! Bounds the BTC held by the hot wallet of the ASB by periodically moving
+//! everything above a threshold to a cold storage wallet.
+//!
+//! The cold storage wallet is given as extended public key or output
+//! descriptor, hence the ASB never learns its private keys. A fresh address is
+//! derived for every sweep, the derivation index is persisted so addresses are
+//! not reused across restarts.
+
+use crate::bitcoin::{self, Address, Amount, Txid};
+use anyhow::{bail, Context, Result};
+use bdk::wallet::{AddressIndex, OfflineWallet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// Sweeping less than this is not worth the transaction fee.
+const MIN_SWEEP_AMOUNT_SAT: u64 = 50_000;
+
+/// The size of the largest locking script a cold storage address can have
+/// (P2WSH), used to estimate the fee before deriving the address.
+const MAX_LOCKING_SCRIPT_SIZE: usize = 34;
+
+#[allow(missing_debug_implementations)]
+pub struct ColdStorage {
+    wallet: Mutex<OfflineWallet<bdk::sled::Tree>>,
+}
+
+impl ColdStorage {
+    /// Watches the cold storage wallet given by `destination`, an extended
+    /// public key or an output descriptor.
+    ///
+    /// The derivation index is stored in `dir`. Fails if the extended keys
+    /// are not for `network`.
+    pub fn new(destination: &str, network: ::bitcoin::Network, dir: &Path) -> Result<Self> {
+        let descriptor = descriptor(destination);
+        check_network(&descriptor, network)?;
+
+        let sled = bdk::sled::open(dir)
+            .with_context(|| format!("Failed to open database at {}", dir.display()))?;
+        // One tree per descriptor so changing the destination starts over at index 0
+        let db = sled.open_tree(descriptor.as_bytes())?;
+
+        let wallet = bdk::Wallet::new_offline(descriptor.as_str(), None, network, db)
+            .with_context(|| format!("Invalid cold storage wallet {}", destination))?;
+
+        Ok(Self {
+            wallet: Mutex::new(wallet),
+        })
+    }
+
+    pub async fn next_address(&self) -> Result<Address> {
+        let address = self
+            .wallet
+            .lock()
+            .await
+            .get_address(AddressIndex::New)
+            .context("Failed to derive cold storage address")?;
+
+        Ok(address)
+    }
+}
+
+/// Sweeps the BTC above `threshold` every `interval` until the ASB is shut
+/// down.
+pub async fn run(
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    cold_storage: ColdStorage,
+    threshold: Amount,
+    interval: Duration,
+) {
+    loop {
+        if let Err(e) = sweep(bitcoin_wallet.as_ref(), &cold_storage, threshold).await {
+            tracing::warn!("Failed to sweep BTC to cold storage: {:#}", e);
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Sends the BTC above `threshold` to the next address of the cold storage
+/// wallet.
+///
+/// Frozen UTXOs are neither swept nor counted towards the balance. Returns the
+/// id of the sweep transaction, if there was anything to sweep.
+pub async fn sweep(
+    bitcoin_wallet: &bitcoin::Wallet,
+    cold_storage: &ColdStorage,
+    threshold: Amount,
+) -> Result<Option<Txid>> {
+    bitcoin_wallet.sync().await?;
+
+    let balance = bitcoin_wallet
+        .list_utxos()
+        .await?
+        .into_iter()
+        .filter(|utxo| !utxo.frozen)
+        .fold(Amount::ZERO, |balance, utxo| balance + utxo.amount);
+    let max_giveable = bitcoin_wallet
+        .max_giveable(MAX_LOCKING_SCRIPT_SIZE, &[])
+        .await?;
+
+    let amount = match amount_to_sweep(balance, max_giveable, threshold) {
+        Some(amount) => amount,
+        None => {
+            tracing::debug!(%balance, %threshold, "Nothing to sweep to cold storage");
+            return Ok(None);
+        }
+    };
+
+    let address = cold_storage.next_address().await?;
+    let psbt = bitcoin_wallet
+        .send_to_address(address.clone(), amount, &[])
+        .await?;
+    let signed_tx = bitcoin_wallet.sign_and_finalize(psbt).await?;
+    let (txid, _) = bitcoin_wallet.broadcast(signed_tx, "sweep").await?;
+
+    tracing::info!(%txid, %address, "Swept {} to cold storage", amount);
+
+    Ok(Some(txid))
+}
+
+/// The fee is paid from the BTC that remain in the hot wallet unless there
+/// is not enough left, then the swept amount is reduced accordingly.
+fn amount_to_sweep(balance: Amount, max_giveable: Amount, threshold: Amount) -> Option<Amount> {
+    let excess = balance.checked_sub(threshold)?;
+    let amount = excess.min(max_giveable);
+
+    if amount.as_sat() < MIN_SWEEP_AMOUNT_SAT {
+        return None;
+    }
+
+    Some(amount)
+}
+
+/// A bare extended public key is taken as BIP84 account key, i.e. the
+/// addresses are derived as `wpkh(<xpub>/0/*)`.
+fn descriptor(destination: &str) -> String {
+    let destination = destination.trim();
+
+    if destination.contains('(') {
+        return destination.to_string();
+    }
+
+    format!("wpkh({}/0/*)", destination)
+}
+
+/// The addresses are derived for the network of the ASB no matter what the
+/// extended keys are for, so a mainnet ASB with a testnet key would sweep to
+/// addresses nobody has the keys for.
+fn check_network(descriptor: &str, network: ::bitcoin::Network) -> Result<()> {
+    const MAINNET_PREFIXES: [&str; 6] = ["xpub", "ypub", "zpub", "xprv", "yprv", "zprv"];
+    const TESTNET_PREFIXES: [&str; 6] = ["tpub", "upub", "vpub", "tprv", "uprv", "vprv"];
+
+    let expected = match network {
+        ::bitcoin::Network::Bitcoin => &MAINNET_PREFIXES,
+        _ => &TESTNET_PREFIXES,
+    };
+
+    let extended_keys = descriptor
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| {
+            MAINNET_PREFIXES
+                .iter()
+                .chain(TESTNET_PREFIXES.iter())
+                .any(|prefix| word.starts_with(prefix))
+        });
+
+    for key in extended_keys {
+        if !expected.iter().any(|prefix| key.starts_with(prefix)) {
+            bail!(
+                "Cold storage wallet key {} is not for the {} network",
+                key,
+                network
+            )
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::bitcoin::secp256k1::Secp256k1;
+    use ::bitcoin::util::bip32::{ExtendedPrivKey, ExtendedPubKey};
+    use ::bitcoin::Network;
+    use tempfile::tempdir;
+
+    #[test]
+    fn sweeps_everything_above_threshold() {
+        let amount = amount_to_sweep(
+            Amount::from_sat(1_500_000),
+            Amount::from_sat(1_499_000),
+            Amount::from_sat(1_000_000),
+        );
+
+        assert_eq!(amount, Some(Amount::from_sat(500_000)));
+    }
+
+    #[test]
+    fn fee_is_taken_from_swept_amount_if_nothing_remains() {
+        let amount = amount_to_sweep(
+            Amount::from_sat(1_500_000),
+            Amount::from_sat(1_499_000),
+            Amount::ZERO,
+        );
+
+        assert_eq!(amount, Some(Amount::from_sat(1_499_000)));
+    }
+
+    #[test]
+    fn does_not_sweep_below_minimum() {
+        assert_eq!(
+            amount_to_sweep(
+                Amount::from_sat(900_000),
+                Amount::from_sat(899_000),
+                Amount::from_sat(1_000_000)
+            ),
+            None
+        );
+        assert_eq!(
+            amount_to_sweep(
+                Amount::from_sat(1_010_000),
+                Amount::from_sat(1_009_000),
+                Amount::from_sat(1_000_000)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn bare_xpub_is_taken_as_bip84_account() {
+        assert_eq!(descriptor("tpubABC"), "wpkh(tpubABC/0/*)");
+        assert_eq!(descriptor(" wpkh(tpubABC/1/*) "), "wpkh(tpubABC/1/*)");
+    }
+
+    #[test]
+    fn rejects_keys_of_other_network() {
+        let xprv = ExtendedPrivKey::new_master(Network::Testnet, &[7u8; 32]).unwrap();
+        let tpub = ExtendedPubKey::from_private(&Secp256k1::new(), &xprv).to_string();
+        let dir = tempdir().unwrap();
+
+        let result = ColdStorage::new(&tpub, Network::Bitcoin, dir.path());
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn network_of_keys_in_descriptor_is_checked() {
+        let xprv = ExtendedPrivKey::new_master(Network::Bitcoin, &[7u8; 32]).unwrap();
+        let xpub = ExtendedPubKey::from_private(&Secp256k1::new(), &xprv).to_string();
+        let descriptor = format!("wpkh([d34db33f/84'/0'/0']{}/0/*)", xpub);
+
+        assert!(check_network(&descriptor, Network::Bitcoin).is_ok());
+        assert!(check_network(&descriptor, Network::Testnet).is_err());
+    }
+
+    #[tokio::test]
+    async fn cold_storage_addresses_are_not_reused_across_restarts() {
+        let xprv = ExtendedPrivKey::new_master(Network::Testnet, &[7u8; 32]).unwrap();
+        let xpub = ExtendedPubKey::from_private(&Secp256k1::new(), &xprv).to_string();
+        let dir = tempdir().unwrap();
+
+        let first = {
+            let cold_storage = ColdStorage::new(&xpub, Network::Testnet, dir.path()).unwrap();
+            cold_storage.next_address().await.unwrap()
+        };
+        let cold_storage = ColdStorage::new(&xpub, Network::Testnet, dir.path()).unwrap();
+        let second = cold_storage.next_address().await.unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(second.network, Network::Testnet);
+    }
+}
@@ -12,25 +12,31 @@
 #![forbid(unsafe_code)]
 #![allow(non_snake_case)]
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use libp2p::core::multiaddr::Protocol;
 use libp2p::core::Multiaddr;
 use libp2p::Swarm;
 use prettytable::{row, Table};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 use structopt::StructOpt;
 use swap::asb::command::{Arguments, Command};
 use swap::asb::config::{
     default_config_path, initial_setup, query_user_for_initial_testnet_config, read_config, Config,
     ConfigNotInitialized,
 };
+use swap::asb::recorder::{record_price_ticks, Recorder};
+use swap::asb::sweep::ColdStorage;
+use swap::asb::Pricing;
 use swap::database::Database;
 use swap::env::GetConfig;
 use swap::monero::Amount;
 use swap::network::swarm;
+use swap::notification::{Notifier, Role, Urgency};
 use swap::protocol::alice::event_loop::KrakenRate;
-use swap::protocol::alice::{run, EventLoop};
+use swap::protocol::alice::{run, EventLoop, PeerLimits, RateLimiter};
+use swap::protocol::watchdog;
 use swap::seed::Seed;
 use swap::tor::AuthenticatedClient;
 use swap::{asb, bitcoin, env, kraken, monero, tor};
@@ -79,15 +85,62 @@ async fn main() -> Result<()> {
 
     match opt.cmd {
         Command::Start {
+            min_buy,
             max_buy,
+            max_concurrent_swaps_per_peer,
+            max_daily_buy_per_peer,
             ask_spread,
+            volatility_spread_factor,
+            ask_spread_per_btc,
+            max_rate_age_secs,
+            record_prices,
         } => {
+            if min_buy > max_buy {
+                bail!(
+                    "The minimum buy amount {} must not exceed the maximum buy amount {}",
+                    min_buy,
+                    max_buy
+                )
+            }
+
             let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
             let monero_wallet = init_monero_wallet(&config, env_config).await?;
 
             let bitcoin_balance = bitcoin_wallet.balance().await?;
             info!("Bitcoin balance: {}", bitcoin_balance);
 
+            let bitcoin_wallet = Arc::new(bitcoin_wallet);
+
+            if let Some(sweep) = config.bitcoin.sweep.clone() {
+                let cold_storage = ColdStorage::new(
+                    &sweep.destination,
+                    env_config.bitcoin_network,
+                    &config.data.dir.join("cold_storage"),
+                )?;
+                info!(
+                    "Sweeping BTC above {} to cold storage every {} seconds",
+                    sweep.threshold, sweep.interval_secs
+                );
+
+                tokio::spawn(asb::sweep::run(
+                    bitcoin_wallet.clone(),
+                    cold_storage,
+                    sweep.threshold,
+                    Duration::from_secs(sweep.interval_secs),
+                ));
+            }
+
+            let monero_wallet = Arc::new(monero_wallet);
+
+            if let Some(balance_monitoring) = config.balance_monitoring.clone() {
+                tokio::spawn(asb::balance_monitor::run(
+                    bitcoin_wallet.clone(),
+                    monero_wallet.clone(),
+                    balance_monitoring,
+                    Notifier::new(config.notifications.clone()),
+                ));
+            }
+
             let monero_balance = monero_wallet.get_balance().await?;
             if monero_balance == Amount::ZERO {
                 let deposit_address = monero_wallet.get_main_address();
@@ -101,6 +154,21 @@ async fn main() -> Result<()> {
 
             let kraken_price_updates = kraken::connect()?;
 
+            let recorder = if record_prices {
+                let recordings_dir = config.data.dir.join("recordings");
+                info!("Recording prices to {}", recordings_dir.display());
+
+                let recorder = Recorder::new(recordings_dir)?;
+                tokio::spawn(record_price_ticks(
+                    kraken_price_updates.clone(),
+                    recorder.clone(),
+                ));
+
+                Some(recorder)
+            } else {
+                None
+            };
+
             // setup Tor hidden services
             let tor_client =
                 tor::Client::new(config.tor.socks5_port).with_control_port(config.tor.control_port);
@@ -118,23 +186,59 @@ async fn main() -> Result<()> {
                 }
             };
 
-            let mut swarm = swarm::alice(&seed)?;
+            let rate_limiter = RateLimiter::new(
+                config.rate_limit.per_peer_requests_per_minute,
+                config.rate_limit.global_requests_per_minute,
+            );
+            let mut swarm = swarm::alice(&seed, rate_limiter)?;
+
+            for peer_id in config.network.banned_peers.iter() {
+                Swarm::ban_peer_id(&mut swarm, *peer_id);
+            }
 
             for listen in config.network.listen {
                 Swarm::listen_on(&mut swarm, listen.clone())
                     .with_context(|| format!("Failed to listen on network interface {}", listen))?;
             }
 
+            let db = Arc::new(db);
+
+            // Shared by the watchdog and the swaps so a deadline is only notified once
+            let notifier = Notifier::new(config.notifications.clone());
+
+            tokio::spawn(watchdog::run(
+                Role::Alice,
+                db.clone(),
+                bitcoin_wallet.clone(),
+                notifier.clone(),
+                watchdog::CHECK_INTERVAL,
+            ));
+
             let (event_loop, mut swap_receiver) = EventLoop::new(
                 swarm,
                 env_config,
-                Arc::new(bitcoin_wallet),
-                Arc::new(monero_wallet),
-                Arc::new(db),
-                KrakenRate::new(ask_spread, kraken_price_updates),
+                bitcoin_wallet,
+                monero_wallet,
+                db,
+                KrakenRate::new(
+                    Pricing {
+                        ask_spread,
+                        volatility_factor: volatility_spread_factor,
+                        ask_spread_per_btc,
+                        max_rate_age: Duration::from_secs(max_rate_age_secs),
+                    },
+                    kraken_price_updates,
+                ),
+                min_buy,
                 max_buy,
+                PeerLimits {
+                    max_concurrent_swaps: max_concurrent_swaps_per_peer,
+                    max_daily_volume: max_daily_buy_per_peer,
+                },
+                recorder,
             )
             .unwrap();
+            let event_loop = event_loop.with_notifier(notifier);
 
             tokio::spawn(async move {
                 while let Some(swap) = swap_receiver.recv().await {
@@ -168,23 +272,131 @@ async fn main() -> Result<()> {
             // Print the table to stdout
             table.printstd();
         }
-        Command::WithdrawBtc { amount, address } => {
+        Command::Check => {
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            let notifier = Notifier::new(config.notifications.clone());
+
+            let deadlines = watchdog::deadlines(Role::Alice, &db, &bitcoin_wallet).await?;
+
+            let mut table = Table::new();
+
+            table.add_row(row!["SWAP ID", "STATE", "BLOCKS LEFT", "URGENCY"]);
+
+            for deadline in deadlines.iter() {
+                let blocks_left = deadline
+                    .status
+                    .blocks_left
+                    .map(|blocks_left| blocks_left.to_string())
+                    .unwrap_or_default();
+                table.add_row(row![
+                    deadline.update.swap_id,
+                    deadline.update.state,
+                    blocks_left,
+                    deadline.urgency
+                ]);
+
+                if deadline.urgency > Urgency::None {
+                    notifier.notify(deadline.notification()).await;
+                }
+            }
+
+            // Print the table to stdout
+            table.printstd();
+
+            let urgent = deadlines
+                .iter()
+                .filter(|deadline| deadline.urgency >= Urgency::Critical)
+                .count();
+            if urgent > 0 {
+                bail!("{} swap(s) have to be resumed now", urgent)
+            }
+        }
+        Command::WithdrawBtc {
+            amount,
+            address,
+            utxos,
+        } => {
             let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
 
             let amount = match amount {
                 Some(amount) => amount,
                 None => {
                     bitcoin_wallet
-                        .max_giveable(address.script_pubkey().len())
+                        .max_giveable(address.script_pubkey().len(), &utxos)
                         .await?
                 }
             };
 
-            let psbt = bitcoin_wallet.send_to_address(address, amount).await?;
+            let psbt = bitcoin_wallet
+                .send_to_address(address, amount, &utxos)
+                .await?;
             let signed_tx = bitcoin_wallet.sign_and_finalize(psbt).await?;
 
             bitcoin_wallet.broadcast(signed_tx, "withdraw").await?;
         }
+        Command::ListUtxos => {
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+
+            let mut table = Table::new();
+
+            table.add_row(row!["OUTPOINT", "AMOUNT", "ADDRESS", "CHANGE", "FROZEN"]);
+
+            for utxo in bitcoin_wallet.list_utxos().await? {
+                let address = utxo
+                    .address
+                    .map(|address| address.to_string())
+                    .unwrap_or_default();
+                table.add_row(row![
+                    utxo.outpoint,
+                    utxo.amount,
+                    address,
+                    utxo.is_change,
+                    utxo.frozen
+                ]);
+            }
+
+            // Print the table to stdout
+            table.printstd();
+        }
+        Command::FreezeUtxo { outpoint } => {
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            bitcoin_wallet.freeze_utxo(outpoint).await?;
+
+            tracing::info!(%outpoint, "Froze UTXO");
+        }
+        Command::UnfreezeUtxo { outpoint } => {
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+            bitcoin_wallet.unfreeze_utxo(outpoint).await?;
+
+            tracing::info!(%outpoint, "Unfroze UTXO");
+        }
+        Command::BanPeer { peer_id } => {
+            db.ban_peer(peer_id, "banned by operator".to_owned())
+                .await?;
+
+            tracing::info!(%peer_id, "Banned peer");
+        }
+        Command::UnbanPeer { peer_id } => {
+            db.unban_peer(peer_id).await?;
+
+            tracing::info!(%peer_id, "Unbanned peer");
+        }
+        Command::BannedPeers => {
+            let mut table = Table::new();
+
+            table.add_row(row!["PEER ID", "REASON"]);
+
+            for (peer_id, reason) in db.banned_peers()? {
+                table.add_row(row![peer_id, reason]);
+            }
+
+            for peer_id in config.network.banned_peers {
+                table.add_row(row![peer_id, "banned in config"]);
+            }
+
+            // Print the table to stdout
+            table.printstd();
+        }
         Command::Balance => {
             let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
             let monero_wallet = init_monero_wallet(&config, env_config).await?;
@@ -193,6 +405,23 @@ async fn main() -> Result<()> {
             let monero_balance = monero_wallet.get_balance().await?;
 
             tracing::info!("Current balance: {}, {}", bitcoin_balance, monero_balance);
+
+            let reservations = db.xmr_reservations()?;
+            let reserved = reservations
+                .iter()
+                .fold(monero::Amount::ZERO, |total, (_, amount)| total + *amount);
+
+            for (swap_id, amount) in reservations {
+                tracing::info!("Reserved for swap {}: {}", swap_id, amount);
+            }
+
+            let free = if reserved > monero_balance {
+                monero::Amount::ZERO
+            } else {
+                monero_balance - reserved
+            };
+
+            tracing::info!("Free Monero balance: {}", free);
         }
     };
 
@@ -208,6 +437,7 @@ async fn init_bitcoin_wallet(
 
     let wallet = bitcoin::Wallet::new(
         config.bitcoin.electrum_rpc_url.clone(),
+        config.bitcoin.cross_check_electrum_rpc_urls.clone(),
         &wallet_dir,
         seed.derive_extended_private_key(env_config.bitcoin_network)?,
         env_config,
@@ -224,8 +454,24 @@ async fn init_monero_wallet(
     config: &Config,
     env_config: swap::env::Config,
 ) -> Result<monero::Wallet> {
-    let wallet = monero::Wallet::open_or_create(
+    let tls_certificate = match &config.monero.wallet_rpc_tls_certificate {
+        Some(path) => Some(std::fs::read(path).with_context(|| {
+            format!(
+                "Failed to read Monero wallet RPC TLS certificate {}",
+                path.display()
+            )
+        })?),
+        None => None,
+    };
+
+    let client = monero_rpc::wallet::Client::with_options(
         config.monero.wallet_rpc_url.clone(),
+        config.monero.wallet_rpc_login.clone(),
+        tls_certificate.as_deref(),
+    )?;
+
+    let wallet = monero::Wallet::open_or_create_with_client(
+        client,
         DEFAULT_WALLET_NAME.to_string(),
         env_config,
     )
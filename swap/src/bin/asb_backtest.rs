@@ -0,0 +1,171 @@
+#![warn(
+    unused_extern_crates,
+    missing_copy_implementations,
+    rust_2018_idioms,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::fallible_impl_from,
+    clippy::cast_precision_loss,
+    clippy::cast_possible_wrap,
+    clippy::dbg_macro
+)]
+#![forbid(unsafe_code)]
+#![allow(non_snake_case)]
+
+use anyhow::{bail, Context, Result};
+use bitcoin::util::amount::ParseAmountError;
+use bitcoin::{Amount, Denomination};
+use prettytable::{row, Table};
+use rust_decimal::Decimal;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::time::Duration;
+use structopt::StructOpt;
+use swap::asb::simulation::{read_price_ticks, synthetic_taker_flow, Simulation};
+use swap::asb::Pricing;
+use swap::monero;
+use swap::protocol::alice::PeerLimits;
+
+#[macro_use]
+extern crate prettytable;
+
+/// Evaluates the pricing of the ASB against recorded asking prices.
+#[derive(StructOpt, Debug)]
+#[structopt(name = "asb_backtest")]
+struct Arguments {
+    #[structopt(
+        long = "price-ticks",
+        help = "CSV file with lines of the form `timestamp,ask`, the timestamp in seconds since the unix epoch and the price of 1 XMR in BTC.",
+        parse(from_os_str)
+    )]
+    price_ticks: PathBuf,
+    #[structopt(long = "ask-spread", default_value = "0.02")]
+    ask_spread: Decimal,
+    #[structopt(long = "volatility-spread-factor", default_value = "0.5")]
+    volatility_spread_factor: Decimal,
+    #[structopt(long = "ask-spread-per-btc", default_value = "0")]
+    ask_spread_per_btc: Decimal,
+    #[structopt(long = "max-rate-age-secs", default_value = "600")]
+    max_rate_age_secs: u64,
+    #[structopt(long = "min-buy-btc", default_value = "0.0001", parse(try_from_str = parse_btc))]
+    min_buy: Amount,
+    #[structopt(long = "max-buy-btc", default_value = "0.005", parse(try_from_str = parse_btc))]
+    max_buy: Amount,
+    #[structopt(
+        long = "xmr-balance",
+        help = "The XMR available for swaps at the start.",
+        default_value = "10",
+        parse(try_from_str = parse_xmr)
+    )]
+    xmr_balance: monero::Amount,
+    #[structopt(
+        long = "xmr-lock-fee",
+        help = "The fee reserved for each XMR lock transaction, the ASB reserves 0.00003 XMR.",
+        default_value = "0.00003",
+        parse(try_from_str = parse_xmr)
+    )]
+    xmr_lock_fee: monero::Amount,
+    #[structopt(
+        long = "max-concurrent-swaps-per-peer",
+        help = "The maximum number of unfinished swaps per taker, like the ASB option of the same name."
+    )]
+    max_concurrent_swaps_per_peer: Option<usize>,
+    #[structopt(
+        long = "max-buy-btc-per-peer-24h",
+        help = "The maximum amount of BTC a taker may sell within 24 hours, like the ASB option of the same name.",
+        parse(try_from_str = parse_btc)
+    )]
+    max_daily_buy_per_peer: Option<Amount>,
+    #[structopt(
+        long = "settlement-delay-mins",
+        help = "The minutes between handing out a spot price and redeeming the BTC.",
+        default_value = "60"
+    )]
+    settlement_delay_mins: u64,
+    #[structopt(
+        long = "requests",
+        help = "The number of synthetic spot price requests.",
+        default_value = "1000"
+    )]
+    requests: usize,
+    #[structopt(
+        long = "takers",
+        help = "The number of different takers sending the synthetic requests.",
+        default_value = "100"
+    )]
+    takers: usize,
+    #[structopt(long = "min-request-btc", default_value = "0.00005", parse(try_from_str = parse_btc))]
+    min_request: Amount,
+    #[structopt(long = "max-request-btc", default_value = "0.01", parse(try_from_str = parse_btc))]
+    max_request: Amount,
+    #[structopt(
+        long = "seed",
+        help = "Seed for generating the requests, the same seed results in the same requests.",
+        default_value = "0"
+    )]
+    seed: u64,
+}
+
+fn main() -> Result<()> {
+    let args = Arguments::from_args();
+
+    let file = File::open(&args.price_ticks)
+        .with_context(|| format!("Failed to open {}", args.price_ticks.display()))?;
+    let ticks = read_price_ticks(BufReader::new(file))?;
+
+    let (start, end) = match (ticks.first(), ticks.last()) {
+        (Some(first), Some(last)) => (first.timestamp, last.timestamp),
+        _ => bail!("No price ticks in {}", args.price_ticks.display()),
+    };
+
+    let requests = synthetic_taker_flow(
+        start,
+        end,
+        args.requests,
+        args.takers,
+        args.min_request,
+        args.max_request,
+        args.seed,
+    );
+
+    let simulation = Simulation {
+        pricing: Pricing {
+            ask_spread: args.ask_spread,
+            volatility_factor: args.volatility_spread_factor,
+            ask_spread_per_btc: args.ask_spread_per_btc,
+            max_rate_age: Duration::from_secs(args.max_rate_age_secs),
+        },
+        min_buy: args.min_buy,
+        max_buy: args.max_buy,
+        peer_limits: PeerLimits {
+            max_concurrent_swaps: args.max_concurrent_swaps_per_peer,
+            max_daily_volume: args.max_daily_buy_per_peer,
+        },
+        xmr_balance: args.xmr_balance,
+        xmr_lock_fee: args.xmr_lock_fee,
+        settlement_delay: Duration::from_secs(args.settlement_delay_mins * 60),
+    };
+
+    let report = simulation.run(ticks, &requests)?;
+
+    let mut table = Table::new();
+    table.add_row(row!["Accepted requests", report.accepted]);
+    for (reason, count) in report.rejected.iter() {
+        table.add_row(row![format!("Rejected: {}", reason), count]);
+    }
+    table.add_row(row!["BTC bought", report.btc_bought]);
+    table.add_row(row!["XMR sold", report.xmr_sold]);
+    table.add_row(row!["Profit and loss", report.profit_and_loss]);
+    table.printstd();
+
+    Ok(())
+}
+
+fn parse_btc(s: &str) -> Result<Amount, ParseAmountError> {
+    Amount::from_str_in(s, Denomination::Bitcoin)
+}
+
+fn parse_xmr(s: &str) -> Result<monero::Amount> {
+    monero::Amount::parse_monero(s)
+}
@@ -13,6 +13,7 @@
 #![allow(non_snake_case)]
 
 use anyhow::{bail, Context, Result};
+use libp2p::{Multiaddr, PeerId, Swarm};
 use prettytable::{row, Table};
 use std::cmp::min;
 use std::future::Future;
@@ -20,16 +21,17 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 use structopt::StructOpt;
-use swap::bitcoin::{Amount, TxLock};
+use swap::bitcoin::{Amount, ExternalFunding, TxLock, TxLockFunding};
 use swap::cli::command::{Arguments, Command, MoneroParams};
 use swap::database::Database;
 use swap::env::{Config, GetConfig};
 use swap::network::quote::BidQuote;
-use swap::network::swarm;
-use swap::protocol::bob;
-use swap::protocol::bob::{EventLoop, Swap};
+use swap::network::{self, swarm};
+use swap::notification::{Notifier, Role, Urgency};
+use swap::protocol::bob::{BobState, EventLoop, Swap, TxLockSigner};
+use swap::protocol::{bob, watchdog};
 use swap::seed::Seed;
-use swap::{bitcoin, cli, env, monero};
+use swap::{bitcoin, cli, env, monero, watchtower};
 use tracing::{debug, error, info, warn};
 use url::Url;
 use uuid::Uuid;
@@ -48,10 +50,20 @@ async fn main() -> Result<()> {
             monero_params:
                 MoneroParams {
                     receive_monero_address,
-                    monero_daemon_host,
+                    monero_daemon_hosts,
+                    monero_wallet_rpc_path,
+                    monero_wallet_rpc_url,
                 },
             electrum_rpc_url,
+            cross_check_electrum_rpc_urls,
             tor_socks5_port,
+            psbt_dir,
+            fund_from,
+            change_address,
+            refund_address,
+            utxos,
+            notification_params,
+            watchtower_params,
         } => {
             let swap_id = Uuid::new_v4();
 
@@ -71,10 +83,31 @@ async fn main() -> Result<()> {
                 )
             }
 
-            let bitcoin_wallet =
-                init_bitcoin_wallet(electrum_rpc_url, &seed, data_dir.clone(), env_config).await?;
-            let (monero_wallet, _process) =
-                init_monero_wallet(data_dir, monero_daemon_host, env_config).await?;
+            let watchtower_peer = watchtower_params.watchtower()?;
+
+            if let Some(refund_address) = refund_address.as_ref() {
+                ensure_bitcoin_network(refund_address, env_config)?;
+            }
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                electrum_rpc_url,
+                cross_check_electrum_rpc_urls,
+                &seed,
+                data_dir.clone(),
+                env_config,
+            )
+            .await?;
+            let (monero_wallet, daemon_supervisor) = init_monero_wallet(
+                data_dir,
+                monero_daemon_hosts,
+                monero_wallet_rpc_path,
+                monero_wallet_rpc_url,
+                env_config,
+            )
+            .await?;
+            if let Some(daemon_supervisor) = daemon_supervisor {
+                tokio::spawn(daemon_supervisor.run());
+            }
             let bitcoin_wallet = Arc::new(bitcoin_wallet);
 
             let mut swarm = swarm::bob(&seed, alice_peer_id, tor_socks5_port).await?;
@@ -87,35 +120,90 @@ async fn main() -> Result<()> {
                 EventLoop::new(swap_id, swarm, alice_peer_id, bitcoin_wallet.clone())?;
             let event_loop = tokio::spawn(event_loop.run());
 
-            let send_bitcoin = determine_btc_to_swap(
-                event_loop_handle.request_quote(),
-                bitcoin_wallet.balance(),
-                bitcoin_wallet.new_address(),
-                async {
-                    while bitcoin_wallet.balance().await? == Amount::ZERO {
-                        bitcoin_wallet.sync().await?;
+            let funding = match (fund_from, change_address) {
+                (Some(outpoint), Some(change_address)) => {
+                    ensure_bitcoin_network(&change_address, env_config)?;
 
-                        tokio::time::sleep(Duration::from_secs(1)).await;
-                    }
+                    let previous_transaction = bitcoin_wallet
+                        .get_raw_transaction(outpoint.txid)
+                        .await
+                        .context("Failed to get the transaction of the output to fund from")?;
 
-                    bitcoin_wallet.balance().await
-                },
-                bitcoin_wallet.max_giveable(TxLock::script_size()),
-            )
-            .await?;
+                    TxLockFunding::External(ExternalFunding::new(
+                        outpoint,
+                        previous_transaction,
+                        change_address,
+                    )?)
+                }
+                _ => TxLockFunding::Wallet { utxos },
+            };
+
+            let send_bitcoin = match &funding {
+                TxLockFunding::External(funding) => {
+                    determine_btc_to_swap_from_output(
+                        event_loop_handle.request_quote(),
+                        funding.max_giveable(),
+                    )
+                    .await?
+                }
+                TxLockFunding::Wallet { utxos } => {
+                    determine_btc_to_swap(
+                        event_loop_handle.request_quote(),
+                        bitcoin_wallet.balance(),
+                        bitcoin_wallet.new_address(),
+                        async {
+                            while bitcoin_wallet.balance().await? == Amount::ZERO {
+                                bitcoin_wallet.sync().await?;
+
+                                tokio::time::sleep(Duration::from_secs(1)).await;
+                            }
+
+                            bitcoin_wallet.balance().await
+                        },
+                        bitcoin_wallet.max_giveable(TxLock::script_size(), utxos),
+                    )
+                    .await?
+                }
+            };
 
             db.insert_peer_id(swap_id, alice_peer_id).await?;
 
+            let db = Arc::new(db);
+            let notifier = Notifier::new(notification_params.into());
+            tokio::spawn(watchdog::run(
+                Role::Bob,
+                db.clone(),
+                bitcoin_wallet.clone(),
+                notifier.clone(),
+                watchdog::CHECK_INTERVAL,
+            ));
+
+            if let Some((peer_id, multiaddr)) = watchtower_peer {
+                let swarm = watchtower_client(&seed, tor_socks5_port, peer_id, multiaddr).await?;
+                tokio::spawn(hand_swap_to_watchtower(
+                    swap_id,
+                    db.clone(),
+                    bitcoin_wallet.clone(),
+                    env_config.bitcoin_finality_confirmations,
+                    swarm,
+                    peer_id,
+                ));
+            }
+
             let swap = Swap::new(
                 db,
                 swap_id,
                 bitcoin_wallet,
-                Arc::new(monero_wallet),
+                monero_wallet,
                 env_config,
                 event_loop_handle,
                 receive_monero_address,
                 send_bitcoin,
-            );
+                funding,
+                refund_address,
+            )
+            .with_tx_lock_signer(tx_lock_signer(psbt_dir))
+            .with_notifier(notifier);
 
             tokio::select! {
                 result = event_loop => {
@@ -150,10 +238,16 @@ async fn main() -> Result<()> {
             monero_params:
                 MoneroParams {
                     receive_monero_address,
-                    monero_daemon_host,
+                    monero_daemon_hosts,
+                    monero_wallet_rpc_path,
+                    monero_wallet_rpc_url,
                 },
             electrum_rpc_url,
+            cross_check_electrum_rpc_urls,
             tor_socks5_port,
+            psbt_dir,
+            notification_params,
+            watchtower_params,
         } => {
             let data_dir = data.0;
             cli::tracing::init(debug, data_dir.join("logs"), swap_id)?;
@@ -167,10 +261,27 @@ async fn main() -> Result<()> {
                 bail!("The given monero address is on network {:?}, expected address of network {:?}.", receive_monero_address.network, env_config.monero_network)
             }
 
-            let bitcoin_wallet =
-                init_bitcoin_wallet(electrum_rpc_url, &seed, data_dir.clone(), env_config).await?;
-            let (monero_wallet, _process) =
-                init_monero_wallet(data_dir, monero_daemon_host, env_config).await?;
+            let watchtower_peer = watchtower_params.watchtower()?;
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                electrum_rpc_url,
+                cross_check_electrum_rpc_urls,
+                &seed,
+                data_dir.clone(),
+                env_config,
+            )
+            .await?;
+            let (monero_wallet, daemon_supervisor) = init_monero_wallet(
+                data_dir,
+                monero_daemon_hosts,
+                monero_wallet_rpc_path,
+                monero_wallet_rpc_url,
+                env_config,
+            )
+            .await?;
+            if let Some(daemon_supervisor) = daemon_supervisor {
+                tokio::spawn(daemon_supervisor.run());
+            }
             let bitcoin_wallet = Arc::new(bitcoin_wallet);
 
             let alice_peer_id = db.get_peer_id(swap_id)?;
@@ -186,15 +297,42 @@ async fn main() -> Result<()> {
                 EventLoop::new(swap_id, swarm, alice_peer_id, bitcoin_wallet.clone())?;
             let handle = tokio::spawn(event_loop.run());
 
+            let db = Arc::new(db);
+            let notifier = Notifier::new(notification_params.into());
+            tokio::spawn(watchdog::run(
+                Role::Bob,
+                db.clone(),
+                bitcoin_wallet.clone(),
+                notifier.clone(),
+                watchdog::CHECK_INTERVAL,
+            ));
+
+            if let Some((peer_id, multiaddr)) = watchtower_peer {
+                let swarm = watchtower_client(&seed, tor_socks5_port, peer_id, multiaddr).await?;
+                tokio::spawn(hand_swap_to_watchtower(
+                    swap_id,
+                    db.clone(),
+                    bitcoin_wallet.clone(),
+                    env_config.bitcoin_finality_confirmations,
+                    swarm,
+                    peer_id,
+                ));
+            }
+
             let swap = Swap::from_db(
                 db,
                 swap_id,
                 bitcoin_wallet,
-                Arc::new(monero_wallet),
+                monero_wallet,
                 env_config,
                 event_loop_handle,
                 receive_monero_address,
             )?;
+            let tx_lock_signer =
+                resume_tx_lock_signer(&swap.state, psbt_dir, &swap.bitcoin_wallet).await?;
+            let swap = swap
+                .with_tx_lock_signer(tx_lock_signer)
+                .with_notifier(notifier);
 
             tokio::select! {
                 event_loop_result = handle => {
@@ -209,6 +347,7 @@ async fn main() -> Result<()> {
             swap_id,
             force,
             electrum_rpc_url,
+            cross_check_electrum_rpc_urls,
         } => {
             let data_dir = data.0;
             cli::tracing::init(debug, data_dir.join("logs"), swap_id)?;
@@ -218,12 +357,24 @@ async fn main() -> Result<()> {
                 .context("Failed to read in seed file")?;
             let env_config = env::Testnet::get_config();
 
-            let bitcoin_wallet =
-                init_bitcoin_wallet(electrum_rpc_url, &seed, data_dir, env_config).await?;
+            let bitcoin_wallet = init_bitcoin_wallet(
+                electrum_rpc_url,
+                cross_check_electrum_rpc_urls,
+                &seed,
+                data_dir,
+                env_config,
+            )
+            .await?;
 
             let resume_state = db.get_state(swap_id)?.try_into_bob()?.into();
-            let cancel =
-                bob::cancel(swap_id, resume_state, Arc::new(bitcoin_wallet), db, force).await?;
+            let cancel = bob::cancel(
+                swap_id,
+                resume_state,
+                Arc::new(bitcoin_wallet),
+                Arc::new(db),
+                force,
+            )
+            .await?;
 
             match cancel {
                 Ok((txid, _)) => {
@@ -239,6 +390,7 @@ async fn main() -> Result<()> {
             swap_id,
             force,
             electrum_rpc_url,
+            cross_check_electrum_rpc_urls,
         } => {
             let data_dir = data.0;
             cli::tracing::init(debug, data_dir.join("logs"), swap_id)?;
@@ -248,19 +400,349 @@ async fn main() -> Result<()> {
                 .context("Failed to read in seed file")?;
             let env_config = env::Testnet::get_config();
 
-            let bitcoin_wallet =
-                init_bitcoin_wallet(electrum_rpc_url, &seed, data_dir, env_config).await?;
+            let bitcoin_wallet = init_bitcoin_wallet(
+                electrum_rpc_url,
+                cross_check_electrum_rpc_urls,
+                &seed,
+                data_dir,
+                env_config,
+            )
+            .await?;
 
             let resume_state = db.get_state(swap_id)?.try_into_bob()?.into();
 
-            bob::refund(swap_id, resume_state, Arc::new(bitcoin_wallet), db, force).await??;
+            bob::refund(
+                swap_id,
+                resume_state,
+                Arc::new(bitcoin_wallet),
+                Arc::new(db),
+                force,
+            )
+            .await??;
+        }
+        Command::Check {
+            refund,
+            electrum_rpc_url,
+            cross_check_electrum_rpc_urls,
+            notification_params,
+        } => {
+            let data_dir = data.0;
+            let db = Arc::new(
+                Database::open(data_dir.join("database").as_path())
+                    .context("Failed to open database")?,
+            );
+            let seed = Seed::from_file_or_generate(data_dir.as_path())
+                .context("Failed to read in seed file")?;
+            let env_config = env::Testnet::get_config();
+            let notifier = Notifier::new(notification_params.into());
+
+            let bitcoin_wallet = Arc::new(
+                init_bitcoin_wallet(
+                    electrum_rpc_url,
+                    cross_check_electrum_rpc_urls,
+                    &seed,
+                    data_dir,
+                    env_config,
+                )
+                .await?,
+            );
+
+            let mut table = Table::new();
+            let mut urgent = 0;
+
+            table.add_row(row!["SWAP ID", "STATE", "BLOCKS LEFT", "URGENCY"]);
+
+            for deadline in watchdog::deadlines(Role::Bob, &db, &bitcoin_wallet).await? {
+                let swap_id = deadline.update.swap_id;
+                let blocks_left = deadline
+                    .status
+                    .blocks_left
+                    .map(|blocks_left| blocks_left.to_string())
+                    .unwrap_or_default();
+
+                if deadline.urgency > Urgency::None {
+                    notifier.notify(deadline.notification()).await;
+                }
+
+                if refund && deadline.urgency >= Urgency::Critical {
+                    let state = db.get_state(swap_id)?.try_into_bob()?.into();
+
+                    match watchdog::cancel_and_refund(
+                        swap_id,
+                        state,
+                        bitcoin_wallet.clone(),
+                        db.clone(),
+                    )
+                    .await
+                    {
+                        Ok(state @ BobState::BtcRedeemed(..)) => {
+                            urgent += 1;
+                            table.add_row(row![swap_id, state, blocks_left, "resume to redeem"]);
+                            continue;
+                        }
+                        Ok(state) => {
+                            table.add_row(row![swap_id, state, blocks_left, "refunded"]);
+                            continue;
+                        }
+                        Err(e) => error!(%swap_id, "Failed to refund swap: {:#}", e),
+                    }
+                }
+
+                if deadline.urgency >= Urgency::Critical {
+                    urgent += 1;
+                }
+
+                table.add_row(row![
+                    swap_id,
+                    deadline.update.state,
+                    blocks_left,
+                    deadline.urgency
+                ]);
+            }
+
+            // Print the table to stdout
+            table.printstd();
+
+            if urgent > 0 {
+                bail!("{} swap(s) have to be resumed or refunded now", urgent)
+            }
+        }
+        Command::Watch {
+            swap_id,
+            watchtower_peer_id,
+            watchtower_multiaddr,
+            tor_socks5_port,
+            electrum_rpc_url,
+            cross_check_electrum_rpc_urls,
+        } => {
+            let data_dir = data.0;
+            cli::tracing::init(debug, data_dir.join("logs"), swap_id)?;
+            let db = Database::open(data_dir.join("database").as_path())
+                .context("Failed to open database")?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path())
+                .context("Failed to read in seed file")?;
+            let env_config = env::Testnet::get_config();
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                electrum_rpc_url,
+                cross_check_electrum_rpc_urls,
+                &seed,
+                data_dir,
+                env_config,
+            )
+            .await?;
+
+            let state = BobState::from(db.get_state(swap_id)?.try_into_bob()?);
+            let tx_cancel = bitcoin_wallet
+                .least_confirmed_status_of_script(&state.tx_cancel(swap_id)?)
+                .await?;
+            let job = match state.watch_job_with_refund(
+                swap_id,
+                tx_cancel,
+                env_config.bitcoin_finality_confirmations,
+            ) {
+                Ok(job) => job,
+                Err(_) => state.watch_job(swap_id)?,
+            };
+            let with_refund = job.tx_refund.is_some();
+
+            let mut swarm = watchtower_client(
+                &seed,
+                tor_socks5_port,
+                watchtower_peer_id,
+                watchtower_multiaddr,
+            )
+            .await?;
+            watchtower::send_request(
+                &mut swarm,
+                watchtower_peer_id,
+                network::watchtower::Request::Submit(Box::new(job)),
+            )
+            .await?;
+
+            if with_refund {
+                println!("Swap {} is watched by {}", swap_id, watchtower_peer_id);
+            } else {
+                println!(
+                    "Swap {} is watched by {}, run `watch` again once the cancel transaction is final to hand over the refund transaction",
+                    swap_id, watchtower_peer_id
+                );
+            }
+        }
+        Command::Unwatch {
+            swap_id,
+            watchtower_peer_id,
+            watchtower_multiaddr,
+            tor_socks5_port,
+        } => {
+            let data_dir = data.0;
+            cli::tracing::init(debug, data_dir.join("logs"), swap_id)?;
+            let seed = Seed::from_file_or_generate(data_dir.as_path())
+                .context("Failed to read in seed file")?;
+
+            let mut swarm = watchtower_client(
+                &seed,
+                tor_socks5_port,
+                watchtower_peer_id,
+                watchtower_multiaddr,
+            )
+            .await?;
+            watchtower::send_request(
+                &mut swarm,
+                watchtower_peer_id,
+                network::watchtower::Request::Revoke { swap_id },
+            )
+            .await?;
+
+            println!(
+                "Swap {} is no longer watched by {}",
+                swap_id, watchtower_peer_id
+            );
+        }
+        Command::WithdrawBtc {
+            amount,
+            address,
+            utxos,
+            electrum_rpc_url,
+            cross_check_electrum_rpc_urls,
+        } => {
+            let data_dir = data.0;
+            let seed = Seed::from_file_or_generate(data_dir.as_path())
+                .context("Failed to read in seed file")?;
+            let env_config = env::Testnet::get_config();
+
+            ensure_bitcoin_network(&address, env_config)?;
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                electrum_rpc_url,
+                cross_check_electrum_rpc_urls,
+                &seed,
+                data_dir,
+                env_config,
+            )
+            .await?;
+
+            let amount = match amount {
+                Some(amount) => amount,
+                None => {
+                    bitcoin_wallet
+                        .max_giveable(address.script_pubkey().len(), &utxos)
+                        .await?
+                }
+            };
+
+            let psbt = bitcoin_wallet
+                .send_to_address(address, amount, &utxos)
+                .await?;
+            let signed_tx = bitcoin_wallet.sign_and_finalize(psbt).await?;
+
+            let (txid, _) = bitcoin_wallet.broadcast(signed_tx, "withdraw").await?;
+
+            println!("Withdrew {} in transaction {}", amount, txid);
+        }
+        Command::ListUtxos {
+            electrum_rpc_url,
+            cross_check_electrum_rpc_urls,
+        } => {
+            let data_dir = data.0;
+            let seed = Seed::from_file_or_generate(data_dir.as_path())
+                .context("Failed to read in seed file")?;
+            let env_config = env::Testnet::get_config();
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                electrum_rpc_url,
+                cross_check_electrum_rpc_urls,
+                &seed,
+                data_dir,
+                env_config,
+            )
+            .await?;
+
+            let mut table = Table::new();
+
+            table.add_row(row!["OUTPOINT", "AMOUNT", "ADDRESS", "CHANGE", "FROZEN"]);
+
+            for utxo in bitcoin_wallet.list_utxos().await? {
+                let address = utxo
+                    .address
+                    .map(|address| address.to_string())
+                    .unwrap_or_default();
+                table.add_row(row![
+                    utxo.outpoint,
+                    utxo.amount,
+                    address,
+                    utxo.is_change,
+                    utxo.frozen
+                ]);
+            }
+
+            // Print the table to stdout
+            table.printstd();
+        }
+        Command::FreezeUtxo {
+            outpoint,
+            electrum_rpc_url,
+            cross_check_electrum_rpc_urls,
+        } => {
+            let data_dir = data.0;
+            let seed = Seed::from_file_or_generate(data_dir.as_path())
+                .context("Failed to read in seed file")?;
+            let env_config = env::Testnet::get_config();
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                electrum_rpc_url,
+                cross_check_electrum_rpc_urls,
+                &seed,
+                data_dir,
+                env_config,
+            )
+            .await?;
+            bitcoin_wallet.freeze_utxo(outpoint).await?;
+
+            println!("Froze {}", outpoint);
+        }
+        Command::UnfreezeUtxo {
+            outpoint,
+            electrum_rpc_url,
+            cross_check_electrum_rpc_urls,
+        } => {
+            let data_dir = data.0;
+            let seed = Seed::from_file_or_generate(data_dir.as_path())
+                .context("Failed to read in seed file")?;
+            let env_config = env::Testnet::get_config();
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                electrum_rpc_url,
+                cross_check_electrum_rpc_urls,
+                &seed,
+                data_dir,
+                env_config,
+            )
+            .await?;
+            bitcoin_wallet.unfreeze_utxo(outpoint).await?;
+
+            println!("Unfroze {}", outpoint);
         }
     };
     Ok(())
 }
 
+fn ensure_bitcoin_network(address: &bitcoin::Address, env_config: Config) -> Result<()> {
+    if address.network != env_config.bitcoin_network {
+        bail!(
+            "Given bitcoin address {} is on network {:?}, expected address on network {:?}",
+            address,
+            address.network,
+            env_config.bitcoin_network
+        )
+    }
+
+    Ok(())
+}
+
 async fn init_bitcoin_wallet(
     electrum_rpc_url: Url,
+    cross_check_electrum_rpc_urls: Vec<Url>,
     seed: &Seed,
     data_dir: PathBuf,
     env_config: Config,
@@ -268,7 +750,8 @@ async fn init_bitcoin_wallet(
     let wallet_dir = data_dir.join("wallet");
 
     let wallet = bitcoin::Wallet::new(
-        electrum_rpc_url.clone(),
+        electrum_rpc_url,
+        cross_check_electrum_rpc_urls,
         &wallet_dir,
         seed.derive_extended_private_key(env_config.bitcoin_network)?,
         env_config,
@@ -283,27 +766,206 @@ async fn init_bitcoin_wallet(
 
 async fn init_monero_wallet(
     data_dir: PathBuf,
-    monero_daemon_host: String,
+    monero_daemon_hosts: Vec<String>,
+    monero_wallet_rpc_path: Option<PathBuf>,
+    monero_wallet_rpc_url: Option<Url>,
     env_config: Config,
-) -> Result<(monero::Wallet, monero::WalletRpcProcess)> {
+) -> Result<(Arc<monero::Wallet>, Option<monero::DaemonSupervisor>)> {
     let network = env_config.monero_network;
 
     const MONERO_BLOCKCHAIN_MONITORING_WALLET_NAME: &str = "swap-tool-blockchain-monitoring-wallet";
 
-    let monero_wallet_rpc = monero::WalletRpc::new(data_dir.join("monero")).await?;
+    if let Some(url) = monero_wallet_rpc_url {
+        info!("Using monero-wallet-rpc at {}", url);
+
+        let monero_wallet = monero::Wallet::open_or_create(
+            url,
+            MONERO_BLOCKCHAIN_MONITORING_WALLET_NAME.to_string(),
+            env_config,
+        )
+        .await?;
+
+        return Ok((Arc::new(monero_wallet), None));
+    }
+
+    let monero_wallet_rpc = match monero_wallet_rpc_path {
+        Some(path) => monero::WalletRpc::with_executable(data_dir.join("monero"), path).await?,
+        None => monero::WalletRpc::new(data_dir.join("monero")).await?,
+    };
+
+    let monero_daemon_host = monero::select_daemon(&monero_daemon_hosts, network).await?;
+    info!("Using Monero daemon {}", monero_daemon_host);
 
     let monero_wallet_rpc_process = monero_wallet_rpc
         .run(network, monero_daemon_host.as_str())
         .await?;
 
-    let monero_wallet = monero::Wallet::open_or_create(
-        monero_wallet_rpc_process.endpoint(),
-        MONERO_BLOCKCHAIN_MONITORING_WALLET_NAME.to_string(),
-        env_config,
-    )
-    .await?;
+    let monero_wallet = Arc::new(
+        monero::Wallet::open_or_create(
+            monero_wallet_rpc_process.endpoint(),
+            MONERO_BLOCKCHAIN_MONITORING_WALLET_NAME.to_string(),
+            env_config,
+        )
+        .await?,
+    );
+
+    let daemon_supervisor = monero::DaemonSupervisor::new(
+        monero_daemon_hosts,
+        monero_daemon_host,
+        network,
+        monero_wallet_rpc,
+        monero_wallet_rpc_process,
+        monero_wallet.clone(),
+    );
+
+    Ok((monero_wallet, Some(daemon_supervisor)))
+}
+
+async fn watchtower_client(
+    seed: &Seed,
+    tor_socks5_port: u16,
+    watchtower_peer_id: PeerId,
+    watchtower_multiaddr: Multiaddr,
+) -> Result<Swarm<network::watchtower::Behaviour>> {
+    let mut swarm = swarm::watchtower_client(seed, tor_socks5_port).await?;
+    swarm
+        .behaviour_mut()
+        .add_address(&watchtower_peer_id, watchtower_multiaddr);
+
+    Ok(swarm)
+}
+
+/// Hands the cancel transaction to the watchtower as soon as the Bitcoin are
+/// locked and the refund transaction once the cancel transaction is final, see
+/// [`BobState::watch_job_with_refund`]. Revokes the job if the swap finishes
+/// otherwise.
+async fn hand_swap_to_watchtower(
+    swap_id: Uuid,
+    db: Arc<Database>,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    finality_confirmations: u32,
+    mut swarm: Swarm<network::watchtower::Behaviour>,
+    watchtower_peer_id: PeerId,
+) {
+    let (state, job) = loop {
+        if let Some(state) = bob_state(&db, swap_id) {
+            if bob::swap::is_complete(&state) {
+                return;
+            }
+            if let Ok(job) = state.watch_job(swap_id) {
+                break (state, job);
+            }
+        }
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    };
+
+    let request = network::watchtower::Request::Submit(Box::new(job));
+    match watchtower::send_request(&mut swarm, watchtower_peer_id, request).await {
+        Ok(()) => info!(%swap_id, %watchtower_peer_id, "Handed cancel transaction to watchtower"),
+        Err(e) => warn!(%swap_id, "Failed to hand swap to watchtower: {:#}", e),
+    }
+
+    let result: Result<Option<network::watchtower::WatchJob>> = async {
+        let tx_cancel = state.tx_cancel(swap_id)?;
+        let subscription = bitcoin_wallet.subscribe_to(state.tx_cancel(swap_id)?).await;
+        let swap_complete = async {
+            loop {
+                if let Some(state) = bob_state(&db, swap_id) {
+                    if bob::swap::is_complete(&state) {
+                        return;
+                    }
+                }
+
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        };
+
+        tokio::select! {
+            result = subscription.wait_until_final() => result?,
+            _ = swap_complete => return Ok(None),
+        }
+
+        let status = bitcoin_wallet
+            .least_confirmed_status_of_script(&tx_cancel)
+            .await?;
+
+        state
+            .watch_job_with_refund(swap_id, status, finality_confirmations)
+            .map(Some)
+    }
+    .await;
+
+    let request = match result {
+        Ok(Some(job)) => network::watchtower::Request::Submit(Box::new(job)),
+        Ok(None) => network::watchtower::Request::Revoke { swap_id },
+        Err(e) => {
+            warn!(%swap_id, "Not handing the refund transaction to the watchtower: {:#}", e);
+            return;
+        }
+    };
+
+    match watchtower::send_request(&mut swarm, watchtower_peer_id, request).await {
+        Ok(()) => info!(%swap_id, %watchtower_peer_id, "Updated watch job"),
+        Err(e) => warn!(%swap_id, "Failed to update watch job: {:#}", e),
+    }
+}
 
-    Ok((monero_wallet, monero_wallet_rpc_process))
+fn bob_state(db: &Database, swap_id: Uuid) -> Option<BobState> {
+    db.get_state(swap_id)
+        .and_then(|state| state.try_into_bob())
+        .map(BobState::from)
+        .ok()
+}
+
+fn tx_lock_signer(psbt_dir: Option<PathBuf>) -> TxLockSigner {
+    match psbt_dir {
+        Some(dir) => TxLockSigner::External { dir },
+        None => TxLockSigner::Wallet,
+    }
+}
+
+/// The signer of the lock transaction of a resumed swap.
+///
+/// An externally funded lock transaction can only be signed by the external
+/// wallet, hence resuming such a swap requires `--psbt-dir`.
+async fn resume_tx_lock_signer(
+    state: &BobState,
+    psbt_dir: Option<PathBuf>,
+    bitcoin_wallet: &bitcoin::Wallet,
+) -> Result<TxLockSigner> {
+    let externally_funded = match state {
+        BobState::Started { funding, .. } => matches!(funding, TxLockFunding::External(_)),
+        BobState::ExecutionSetupDone(state2) => {
+            let utxos = bitcoin_wallet.list_utxos().await?;
+
+            state2
+                .tx_lock_psbt()
+                .global
+                .unsigned_tx
+                .input
+                .iter()
+                .any(|input| {
+                    !utxos
+                        .iter()
+                        .any(|utxo| utxo.outpoint == input.previous_output)
+                })
+        }
+        // The lock transaction was already signed
+        _ => false,
+    };
+
+    match (externally_funded, psbt_dir) {
+        (true, Some(dir)) => Ok(TxLockSigner::External { dir }),
+        (true, None) => bail!(
+            "The Bitcoin lock transaction of this swap is funded by an external wallet, pass --psbt-dir to sign it"
+        ),
+        (false, Some(_)) => {
+            warn!("Ignoring --psbt-dir, the Bitcoin lock transaction of this swap is signed by the internal wallet or was already signed");
+            Ok(TxLockSigner::Wallet)
+        }
+        (false, None) => Ok(TxLockSigner::Wallet),
+    }
 }
 
 async fn determine_btc_to_swap(
@@ -319,13 +981,18 @@ async fn determine_btc_to_swap(
 
     info!("Received quote: 1 XMR ~ {}", bid_quote.price);
 
+    if bid_quote.max_quantity == bitcoin::Amount::ZERO {
+        bail!("The seller has no Monero to sell at the moment, please try again later")
+    }
+
     // TODO: Also wait for more funds if balance < dust
     let initial_balance = initial_balance.await?;
 
     let balance = if initial_balance == Amount::ZERO {
         info!(
-            "Please deposit the BTC you want to swap to {} (max {})",
+            "Please deposit the BTC you want to swap to {} (min {}, max {})",
             get_new_address.await?,
+            bid_quote.min_quantity,
             bid_quote.max_quantity
         );
 
@@ -348,11 +1015,54 @@ async fn determine_btc_to_swap(
     let max_accepted = bid_quote.max_quantity;
 
     let btc_swap_amount = min(max_giveable, max_accepted);
+
+    if btc_swap_amount < bid_quote.min_quantity {
+        bail!(
+            "The seller only buys {} or more but only {} can be swapped, please deposit more BTC",
+            bid_quote.min_quantity,
+            btc_swap_amount
+        )
+    }
+
     info!("Swapping {} with {} fees", btc_swap_amount, fees);
 
     Ok(btc_swap_amount)
 }
 
+/// Determines the BTC to swap when funding the lock transaction directly from
+/// an output of an external wallet, of which `max_giveable` can be locked.
+async fn determine_btc_to_swap_from_output(
+    request_quote: impl Future<Output = Result<BidQuote>>,
+    max_giveable: bitcoin::Amount,
+) -> Result<bitcoin::Amount> {
+    debug!("Requesting quote");
+
+    let bid_quote = request_quote.await?;
+
+    info!("Received quote: 1 XMR ~ {}", bid_quote.price);
+
+    if bid_quote.max_quantity == bitcoin::Amount::ZERO {
+        bail!("The seller has no Monero to sell at the moment, please try again later")
+    }
+
+    let btc_swap_amount = min(max_giveable, bid_quote.max_quantity);
+
+    if btc_swap_amount < bid_quote.min_quantity {
+        bail!(
+            "The seller only buys {} or more but the given output only covers {}",
+            bid_quote.min_quantity,
+            btc_swap_amount
+        )
+    }
+
+    info!(
+        "Swapping {}, the rest of the output minus fees is paid to the change address",
+        btc_swap_amount
+    );
+
+    Ok(btc_swap_amount)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -428,10 +1138,76 @@ mod tests {
         assert_eq!(amount, Amount::from_btc(0.01).unwrap())
     }
 
+    #[tokio::test]
+    async fn given_balance_below_min_quantity_from_quote_fails() {
+        let _guard = subscriber::set_default(tracing_subscriber::fmt().with_test_writer().finish());
+
+        let result = determine_btc_to_swap(
+            async { Ok(quote_with_min_max(0.001, 0.01)) },
+            async { Ok(Amount::from_btc(0.0005)?) },
+            async { panic!("should not request new address when initial balance is > 0") },
+            async { panic!("should not wait for deposit when initial balance > 0") },
+            async { Ok(Amount::from_btc(0.00049)?) },
+        )
+        .await;
+
+        assert!(result.is_err())
+    }
+
+    #[tokio::test]
+    async fn given_output_above_max_quantity_swaps_max_quantity() {
+        let _guard = subscriber::set_default(tracing_subscriber::fmt().with_test_writer().finish());
+
+        let amount = determine_btc_to_swap_from_output(
+            async { Ok(quote_with_max(0.01)) },
+            Amount::from_btc(0.1).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(amount, Amount::from_btc(0.01).unwrap())
+    }
+
+    #[tokio::test]
+    async fn given_output_below_min_quantity_fails() {
+        let _guard = subscriber::set_default(tracing_subscriber::fmt().with_test_writer().finish());
+
+        let result = determine_btc_to_swap_from_output(
+            async { Ok(quote_with_min_max(0.001, 0.01)) },
+            Amount::from_btc(0.0005).unwrap(),
+        )
+        .await;
+
+        assert!(result.is_err())
+    }
+
+    #[tokio::test]
+    async fn given_quote_without_liquidity_fails_before_deposit() {
+        let _guard = subscriber::set_default(tracing_subscriber::fmt().with_test_writer().finish());
+
+        let result = determine_btc_to_swap(
+            async { Ok(quote_with_min_max(0.001, 0.0)) },
+            async { Ok(Amount::ZERO) },
+            async { panic!("should not request new address when the seller has no liquidity") },
+            async { panic!("should not wait for deposit when the seller has no liquidity") },
+            async { panic!("should not compute max giveable when the seller has no liquidity") },
+        )
+        .await;
+
+        assert!(result.is_err())
+    }
+
     fn quote_with_max(btc: f64) -> BidQuote {
+        quote_with_min_max(0.0, btc)
+    }
+
+    fn quote_with_min_max(min: f64, max: f64) -> BidQuote {
         BidQuote {
             price: Amount::from_btc(0.001).unwrap(),
-            max_quantity: Amount::from_btc(btc).unwrap(),
+            min_quantity: Amount::from_btc(min).unwrap(),
+            max_quantity: Amount::from_btc(max).unwrap(),
+            id: Some(Uuid::new_v4()),
+            valid_for: Some(Duration::from_secs(60)),
         }
     }
 
@@ -0,0 +1,121 @@
+#![warn(
+    unused_extern_crates,
+    missing_copy_implementations,
+    rust_2018_idioms,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::fallible_impl_from,
+    clippy::cast_precision_loss,
+    clippy::cast_possible_wrap,
+    clippy::dbg_macro
+)]
+#![forbid(unsafe_code)]
+#![allow(non_snake_case)]
+
+use anyhow::{Context, Result};
+use libp2p::Swarm;
+use prettytable::{row, Table};
+use std::sync::Arc;
+use structopt::StructOpt;
+use swap::env::GetConfig;
+use swap::network::swarm;
+use swap::seed::Seed;
+use swap::watchtower::command::{Arguments, Command};
+use swap::watchtower::{self, Watchtower};
+use swap::{bitcoin, env};
+use tracing::info;
+use tracing_subscriber::filter::LevelFilter;
+
+#[macro_use]
+extern crate prettytable;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let Arguments { data, debug, cmd } = Arguments::from_args();
+
+    let level = if debug {
+        LevelFilter::DEBUG
+    } else {
+        LevelFilter::INFO
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(format!("watchtower={},swap={}", level, level))
+        .with_writer(std::io::stderr)
+        .with_target(false)
+        .init();
+
+    let data_dir = data.0;
+    let watchtower =
+        Arc::new(Watchtower::open(&data_dir.join("database")).context("Failed to open database")?);
+
+    match cmd {
+        Command::Start {
+            listen,
+            electrum_rpc_url,
+            cross_check_electrum_rpc_urls,
+        } => {
+            let seed = Seed::from_file_or_generate(data_dir.as_path())
+                .context("Failed to read in seed file")?;
+            let env_config = env::Testnet::get_config();
+
+            // The wallet only broadcasts the transactions of the jobs and
+            // watches their status, it never holds any funds
+            let bitcoin_wallet = bitcoin::Wallet::new(
+                electrum_rpc_url,
+                cross_check_electrum_rpc_urls,
+                &data_dir.join("wallet"),
+                seed.derive_extended_private_key(env_config.bitcoin_network)?,
+                env_config,
+            )
+            .await
+            .context("Failed to initialize Bitcoin wallet")?;
+
+            let mut swarm = swarm::watchtower(&seed)?;
+
+            for listen in listen {
+                Swarm::listen_on(&mut swarm, listen.clone())
+                    .with_context(|| format!("Failed to listen on network interface {}", listen))?;
+            }
+
+            info!("Our peer id is {}", swarm.local_peer_id());
+
+            tokio::spawn(watchtower::watch(
+                watchtower.clone(),
+                Arc::new(bitcoin_wallet),
+                env_config.bitcoin_finality_confirmations,
+                watchtower::CHECK_INTERVAL,
+            ));
+
+            watchtower::serve(swarm, watchtower).await;
+        }
+        Command::Jobs => {
+            let mut table = Table::new();
+
+            table.add_row(row![
+                "SWAP ID",
+                "LOCK TXID",
+                "CANCEL TIMELOCK",
+                "PUNISH TIMELOCK",
+                "REFUND"
+            ]);
+
+            for job in watchtower.jobs()? {
+                table.add_row(row![
+                    job.swap_id,
+                    job.tx_lock.txid,
+                    u32::from(job.cancel_timelock),
+                    u32::from(job.punish_timelock),
+                    match job.tx_refund {
+                        Some(_) => "handed over",
+                        None => "pending",
+                    }
+                ]);
+            }
+
+            // Print the table to stdout
+            table.printstd();
+        }
+    }
+
+    Ok(())
+}
@@ -8,11 +8,11 @@ mod refund;
 mod timelocks;
 
 pub use crate::bitcoin::cancel::{CancelTimelock, PunishTimelock, TxCancel};
-pub use crate::bitcoin::lock::TxLock;
+pub use crate::bitcoin::lock::{ExternalFunding, TxLock, TxLockFunding};
 pub use crate::bitcoin::punish::TxPunish;
 pub use crate::bitcoin::redeem::TxRedeem;
 pub use crate::bitcoin::refund::TxRefund;
-pub use crate::bitcoin::timelocks::{BlockHeight, ExpiredTimelocks};
+pub use crate::bitcoin::timelocks::{BlockHeight, ExpiredTimelocks, TimelockStatus};
 pub use ::bitcoin::util::amount::Amount;
 pub use ::bitcoin::util::psbt::PartiallySignedTransaction;
 pub use ::bitcoin::{Address, Network, Transaction, Txid};
@@ -244,6 +244,39 @@ pub fn current_epoch(
     ExpiredTimelocks::None
 }
 
+pub fn timelock_status(
+    cancel_timelock: CancelTimelock,
+    punish_timelock: PunishTimelock,
+    tx_lock_status: ScriptStatus,
+    tx_cancel_status: ScriptStatus,
+) -> TimelockStatus {
+    let epoch = current_epoch(
+        cancel_timelock,
+        punish_timelock,
+        tx_lock_status,
+        tx_cancel_status,
+    );
+
+    let blocks_left = match epoch {
+        ExpiredTimelocks::None => {
+            Some(u32::from(cancel_timelock).saturating_sub(confirmations(tx_lock_status)))
+        }
+        ExpiredTimelocks::Cancel => {
+            Some(u32::from(punish_timelock).saturating_sub(confirmations(tx_cancel_status)))
+        }
+        ExpiredTimelocks::Punish => None,
+    };
+
+    TimelockStatus { epoch, blocks_left }
+}
+
+fn confirmations(status: ScriptStatus) -> u32 {
+    match status {
+        ScriptStatus::Confirmed(inner) => inner.confirmations(),
+        ScriptStatus::Unseen | ScriptStatus::InMempool => 0,
+    }
+}
+
 #[derive(Clone, Copy, thiserror::Error, Debug)]
 #[error("transaction does not spend anything")]
 pub struct NoInputs;
@@ -308,4 +341,58 @@ mod tests {
 
         assert_eq!(expired_timelock, ExpiredTimelocks::Punish)
     }
+
+    #[test]
+    fn blocks_left_until_cancel_timelock_expires() {
+        let status = timelock_status(
+            CancelTimelock::new(5),
+            PunishTimelock::new(5),
+            ScriptStatus::from_confirmations(3),
+            ScriptStatus::Unseen,
+        );
+
+        assert_eq!(status, TimelockStatus {
+            epoch: ExpiredTimelocks::None,
+            blocks_left: Some(2)
+        })
+    }
+
+    #[test]
+    fn full_cancel_timelock_left_if_lock_is_unconfirmed() {
+        let status = timelock_status(
+            CancelTimelock::new(5),
+            PunishTimelock::new(5),
+            ScriptStatus::InMempool,
+            ScriptStatus::Unseen,
+        );
+
+        assert_eq!(status.blocks_left, Some(5))
+    }
+
+    #[test]
+    fn blocks_left_until_punish_timelock_expires() {
+        let status = timelock_status(
+            CancelTimelock::new(5),
+            PunishTimelock::new(5),
+            ScriptStatus::from_confirmations(10),
+            ScriptStatus::from_confirmations(1),
+        );
+
+        assert_eq!(status, TimelockStatus {
+            epoch: ExpiredTimelocks::Cancel,
+            blocks_left: Some(4)
+        })
+    }
+
+    #[test]
+    fn no_blocks_left_once_punish_timelock_expired() {
+        let status = timelock_status(
+            CancelTimelock::new(5),
+            PunishTimelock::new(5),
+            ScriptStatus::from_confirmations(10),
+            ScriptStatus::from_confirmations(5),
+        );
+
+        assert_eq!(status.blocks_left, None)
+    }
 }
@@ -42,6 +42,12 @@ impl PartialOrd<CancelTimelock> for u32 {
     }
 }
 
+impl From<CancelTimelock> for u32 {
+    fn from(timelock: CancelTimelock) -> Self {
+        timelock.0
+    }
+}
+
 impl PartialEq<CancelTimelock> for u32 {
     fn eq(&self, other: &CancelTimelock) -> bool {
         self.eq(&other.0)
@@ -76,6 +82,12 @@ impl PartialOrd<PunishTimelock> for u32 {
     }
 }
 
+impl From<PunishTimelock> for u32 {
+    fn from(timelock: PunishTimelock) -> Self {
+        timelock.0
+    }
+}
+
 impl PartialEq<PunishTimelock> for u32 {
     fn eq(&self, other: &PunishTimelock) -> bool {
         self.eq(&other.0)
@@ -1,10 +1,10 @@
-use crate::bitcoin::wallet::Watchable;
+use crate::bitcoin::wallet::{Watchable, SATS_PER_VBYTE};
 use crate::bitcoin::{
     build_shared_output_descriptor, Address, Amount, PublicKey, Transaction, Wallet, TX_FEE,
 };
 use ::bitcoin::util::psbt::PartiallySignedTransaction;
 use ::bitcoin::{OutPoint, TxIn, TxOut, Txid};
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use bdk::database::BatchDatabase;
 use bitcoin::Script;
 use ecdsa_fun::fun::Point;
@@ -12,6 +12,20 @@ use miniscript::{Descriptor, DescriptorTrait};
 use rand::thread_rng;
 use serde::{Deserialize, Serialize};
 
+/// The weight of a lock transaction without inputs and outputs: version, lock
+/// time and the input and output counts.
+const BASE_WEIGHT: u64 = 4 * (4 + 4 + 1 + 1);
+/// The weight of the non-witness part of a segwit input: the outpoint, the
+/// empty script sig and the sequence.
+const SEGWIT_INPUT_WEIGHT: u64 = 4 * (36 + 1 + 4);
+/// The weight of the witness of a P2WPKH input including the segwit marker
+/// and flag: the item count, a DER signature with sighash flag and a
+/// compressed public key.
+const P2WPKH_WITNESS_WEIGHT: u64 = 2 + 1 + (1 + 72) + (1 + 33);
+
+/// Change below this amount is left to the fees.
+const DUST_AMOUNT: u64 = 546;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TxLock {
     inner: PartiallySignedTransaction,
@@ -19,11 +33,14 @@ pub struct TxLock {
 }
 
 impl TxLock {
+    /// Builds a lock transaction funded by `wallet`, spending only the given
+    /// `utxos` if there are any.
     pub async fn new<B, D, C>(
         wallet: &Wallet<B, D, C>,
         amount: Amount,
         A: PublicKey,
         B: PublicKey,
+        utxos: &[OutPoint],
     ) -> Result<Self>
     where
         D: BatchDatabase,
@@ -33,7 +50,65 @@ impl TxLock {
             .address(wallet.get_network())
             .expect("can derive address from descriptor");
 
-        let psbt = wallet.send_to_address(address, amount).await?;
+        let psbt = wallet.send_to_address(address, amount, utxos).await?;
+
+        Ok(Self {
+            inner: psbt,
+            output_descriptor: lock_output_descriptor,
+        })
+    }
+
+    /// Builds a lock transaction that spends the UTXO of an external wallet
+    /// and pays the change back to that wallet.
+    ///
+    /// The resulting PSBT has to be signed by the external wallet.
+    pub fn from_external_funding(
+        funding: &ExternalFunding,
+        amount: Amount,
+        A: PublicKey,
+        B: PublicKey,
+    ) -> Result<Self> {
+        let lock_output_descriptor = build_shared_output_descriptor(A.0, B.0);
+        let utxo = funding.utxo();
+
+        let change = utxo
+            .value
+            .checked_sub(amount.as_sat() + funding.fee().as_sat())
+            .with_context(|| {
+                format!(
+                    "UTXO {} of {} does not cover {} and the fee",
+                    funding.outpoint,
+                    funding.value(),
+                    amount
+                )
+            })?;
+
+        let mut output = vec![TxOut {
+            value: amount.as_sat(),
+            script_pubkey: lock_output_descriptor.script_pubkey(),
+        }];
+        if change >= DUST_AMOUNT {
+            output.push(TxOut {
+                value: change,
+                script_pubkey: funding.change_address.script_pubkey(),
+            });
+        }
+
+        let transaction = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: funding.outpoint,
+                script_sig: Default::default(),
+                sequence: 0xFFFF_FFFF,
+                witness: Vec::new(),
+            }],
+            output,
+        };
+
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(transaction)?;
+        psbt.inputs[0].witness_utxo = Some(utxo.clone());
+        psbt.inputs[0].non_witness_utxo = Some(funding.previous_transaction.clone());
 
         Ok(Self {
             inner: psbt,
@@ -99,6 +174,29 @@ impl TxLock {
         self.inner.clone().extract_tx().txid()
     }
 
+    /// Extracts the signed transaction, failing if any of the inputs was not
+    /// finalized.
+    pub fn finalized_tx(self) -> Result<Transaction> {
+        let unfinalized_inputs = self
+            .inner
+            .inputs
+            .iter()
+            .filter(|input| {
+                input.final_script_sig.is_none() && input.final_script_witness.is_none()
+            })
+            .count();
+
+        if unfinalized_inputs > 0 {
+            bail!(
+                "PSBT is not finalized, {} of {} inputs are missing their final script",
+                unfinalized_inputs,
+                self.inner.inputs.len()
+            )
+        }
+
+        Ok(self.inner.extract_tx())
+    }
+
     pub fn as_outpoint(&self) -> OutPoint {
         // This is fine because a transaction that has that many outputs is not
         // realistic
@@ -160,6 +258,145 @@ impl TxLock {
     }
 }
 
+/// The coins that fund the lock transaction.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum TxLockFunding {
+    /// UTXOs of the internal wallet, any that are not frozen if none are
+    /// given.
+    Wallet { utxos: Vec<OutPoint> },
+    /// A UTXO of an external wallet.
+    External(ExternalFunding),
+}
+
+impl Default for TxLockFunding {
+    fn default() -> Self {
+        TxLockFunding::Wallet { utxos: Vec::new() }
+    }
+}
+
+/// A UTXO of an external wallet that funds the lock transaction.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExternalFunding {
+    outpoint: OutPoint,
+    previous_transaction: Transaction,
+    change_address: Address,
+}
+
+impl ExternalFunding {
+    /// Fails if `previous_transaction` does not have the output referenced by
+    /// `outpoint` or if the output is not a P2WPKH output.
+    pub fn new(
+        outpoint: OutPoint,
+        previous_transaction: Transaction,
+        change_address: Address,
+    ) -> Result<Self> {
+        if previous_transaction.txid() != outpoint.txid {
+            bail!(
+                "Transaction {} is not the transaction of {}",
+                previous_transaction.txid(),
+                outpoint
+            )
+        }
+
+        let utxo = previous_transaction
+            .output
+            .get(outpoint.vout as usize)
+            .with_context(|| {
+                format!(
+                    "Transaction {} has no output {}",
+                    outpoint.txid, outpoint.vout
+                )
+            })?;
+
+        // The txid of a transaction spending a non-segwit output changes when
+        // it is signed, which invalidates the transactions spending TxLock
+        if !utxo.script_pubkey.is_witness_program() {
+            bail!("{} is not a segwit output", outpoint)
+        }
+
+        // The fee depends on the size of the witness, which is only known
+        // upfront for P2WPKH
+        if !utxo.script_pubkey.is_v0_p2wpkh() {
+            bail!(
+                "{} is not a P2WPKH output, other segwit outputs are not supported",
+                outpoint
+            )
+        }
+
+        Ok(Self {
+            outpoint,
+            previous_transaction,
+            change_address,
+        })
+    }
+
+    /// A P2WPKH output worth `value` sats that returns the change to a random
+    /// P2WPKH address.
+    #[cfg(test)]
+    pub fn p2wpkh(value: u64) -> Self {
+        use ::bitcoin::hashes::Hash;
+
+        let previous_transaction = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![TxOut {
+                value,
+                script_pubkey: Script::new_v0_wpkh(&::bitcoin::WPubkeyHash::hash(&[])),
+            }],
+        };
+        let change_address =
+            Address::p2wpkh(&PublicKey::random().into(), ::bitcoin::Network::Testnet)
+                .expect("compressed public key");
+
+        Self::new(
+            OutPoint::new(previous_transaction.txid(), 0),
+            previous_transaction,
+            change_address,
+        )
+        .expect("P2WPKH output to be accepted")
+    }
+
+    pub fn outpoint(&self) -> OutPoint {
+        self.outpoint
+    }
+
+    pub fn value(&self) -> Amount {
+        Amount::from_sat(self.utxo().value)
+    }
+
+    /// The fee of the lock transaction.
+    pub fn fee(&self) -> Amount {
+        Amount::from_sat(self.vsize() * u64::from(SATS_PER_VBYTE))
+    }
+
+    /// The virtual size of the lock transaction spending this UTXO, including
+    /// a change output.
+    fn vsize(&self) -> u64 {
+        let lock_output = output_weight(TxLock::script_size());
+        let change_output = output_weight(self.change_address.script_pubkey().len());
+        let weight =
+            BASE_WEIGHT + SEGWIT_INPUT_WEIGHT + P2WPKH_WITNESS_WEIGHT + lock_output + change_output;
+
+        (weight + 3) / 4
+    }
+
+    /// The maximum amount that can be locked with this UTXO.
+    pub fn max_giveable(&self) -> Amount {
+        Amount::from_sat(self.utxo().value.saturating_sub(self.fee().as_sat()))
+    }
+
+    fn utxo(&self) -> &TxOut {
+        &self.previous_transaction.output[self.outpoint.vout as usize]
+    }
+}
+
+/// The weight of an output with a script of `script_len` bytes: the value, the
+/// script length and the script.
+fn output_weight(script_len: usize) -> u64 {
+    4 * (8 + 1 + script_len as u64)
+}
+
 impl From<TxLock> for PartiallySignedTransaction {
     fn from(from: TxLock) -> Self {
         from.inner
@@ -179,6 +416,8 @@ impl Watchable for TxLock {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ::bitcoin::hashes::Hash;
+    use ::bitcoin::{PubkeyHash, WScriptHash};
 
     #[tokio::test]
     async fn given_bob_sends_good_psbt_when_reconstructing_then_succeeeds() {
@@ -236,6 +475,135 @@ mod tests {
         result.expect_err("PSBT to be invalid");
     }
 
+    #[tokio::test]
+    async fn unsigned_psbt_is_not_finalized() {
+        let (A, B) = alice_and_bob();
+        let wallet = Wallet::new_funded(50000);
+
+        let tx_lock = TxLock::new(&wallet, Amount::from_sat(10000), A, B, &[])
+            .await
+            .unwrap();
+
+        tx_lock
+            .finalized_tx()
+            .expect_err("PSBT not to be finalized");
+    }
+
+    #[test]
+    fn alice_accepts_externally_funded_tx_lock() {
+        let (A, B) = alice_and_bob();
+        let agreed_amount = Amount::from_sat(100_000);
+        let funding = ExternalFunding::p2wpkh(200_000);
+
+        let tx_lock = TxLock::from_external_funding(&funding, agreed_amount, A, B).unwrap();
+        let psbt: PartiallySignedTransaction = tx_lock.clone().into();
+        assert_eq!(
+            psbt.global.unsigned_tx.output.len(),
+            2,
+            "with change output"
+        );
+        assert_eq!(
+            psbt.global.unsigned_tx.input[0].previous_output,
+            funding.outpoint()
+        );
+
+        let result = TxLock::from_psbt(psbt, A, B, agreed_amount);
+
+        assert_eq!(result.unwrap(), tx_lock);
+    }
+
+    #[test]
+    fn dust_change_is_left_to_the_fees() {
+        let (A, B) = alice_and_bob();
+        let funding = ExternalFunding::p2wpkh(100_000);
+
+        let tx_lock = TxLock::from_external_funding(
+            &funding,
+            funding.max_giveable() - Amount::from_sat(100),
+            A,
+            B,
+        )
+        .unwrap();
+        let psbt: PartiallySignedTransaction = tx_lock.into();
+
+        assert_eq!(psbt.global.unsigned_tx.output.len(), 1);
+    }
+
+    #[test]
+    fn external_funding_must_cover_amount_and_fee() {
+        let (A, B) = alice_and_bob();
+        let funding = ExternalFunding::p2wpkh(100_000);
+
+        let result = TxLock::from_external_funding(&funding, Amount::from_sat(100_000), A, B);
+
+        result.expect_err("UTXO not to cover the fee");
+    }
+
+    #[test]
+    fn external_funding_must_be_segwit() {
+        let previous_transaction = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![TxOut {
+                value: 100_000,
+                script_pubkey: Script::new_p2pkh(&PubkeyHash::hash(&[])),
+            }],
+        };
+
+        let result = ExternalFunding::new(
+            OutPoint::new(previous_transaction.txid(), 0),
+            previous_transaction,
+            change_address(),
+        );
+
+        result.expect_err("P2PKH output to be rejected");
+    }
+
+    #[test]
+    fn external_funding_must_be_p2wpkh() {
+        let previous_transaction = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![TxOut {
+                value: 100_000,
+                script_pubkey: Script::new_v0_wsh(&WScriptHash::hash(&[])),
+            }],
+        };
+
+        let result = ExternalFunding::new(
+            OutPoint::new(previous_transaction.txid(), 0),
+            previous_transaction,
+            change_address(),
+        );
+
+        result.expect_err("P2WSH output to be rejected");
+    }
+
+    #[test]
+    fn vsize_of_externally_funded_tx_lock_matches_p2wpkh_spend() {
+        let funding = ExternalFunding::p2wpkh(100_000);
+
+        // One P2WPKH input, the P2WSH lock output and a P2WPKH change output
+        assert_eq!(funding.vsize(), 153);
+    }
+
+    #[test]
+    fn vsize_depends_on_change_address() {
+        let funding = ExternalFunding::p2wpkh(100_000);
+        let p2wsh_change = ExternalFunding {
+            change_address: Address::p2wsh(&Script::new(), ::bitcoin::Network::Testnet),
+            ..funding.clone()
+        };
+
+        assert_eq!(p2wsh_change.vsize(), funding.vsize() + 12);
+    }
+
+    fn change_address() -> Address {
+        Address::p2wpkh(&PublicKey::random().into(), ::bitcoin::Network::Testnet).unwrap()
+    }
+
     /// Helper function that represents Bob's action of constructing the PSBT.
     ///
     /// Extracting this allows us to keep the tests concise.
@@ -245,7 +613,10 @@ mod tests {
         wallet: &Wallet<(), bdk::database::MemoryDatabase, ()>,
         amount: Amount,
     ) -> PartiallySignedTransaction {
-        TxLock::new(&wallet, amount, A, B).await.unwrap().into()
+        TxLock::new(&wallet, amount, A, B, &[])
+            .await
+            .unwrap()
+            .into()
     }
 
     fn alice_and_bob() -> (PublicKey, PublicKey) {
@@ -55,3 +55,15 @@ pub enum ExpiredTimelocks {
     Cancel,
     Punish,
 }
+
+/// The current epoch of a swap and how far away the next one is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimelockStatus {
+    pub epoch: ExpiredTimelocks,
+    /// Blocks left until the next timelock expires, `None` once the punish
+    /// timelock expired.
+    ///
+    /// As long as the transaction the timelock is relative to is not
+    /// confirmed, this is the full timelock.
+    pub blocks_left: Option<u32>,
+}
@@ -11,7 +11,7 @@ use bdk::electrum_client::{ElectrumApi, GetHistoryRes};
 use bdk::keys::DerivableKey;
 use bdk::wallet::AddressIndex;
 use bdk::{FeeRate, KeychainKind};
-use bitcoin::{Network, Script};
+use bitcoin::{Network, OutPoint, Script};
 use reqwest::Url;
 use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
@@ -22,17 +22,35 @@ use std::time::{Duration, Instant};
 use tokio::sync::{watch, Mutex};
 
 const SLED_TREE_NAME: &str = "default_tree";
+const FROZEN_UTXOS_TREE_NAME: &str = "frozen_utxos";
+
+/// The fee rate of our transactions.
+// TODO: This should obviously not be a const :)
+pub(crate) const SATS_PER_VBYTE: u16 = 5;
 
 pub struct Wallet<B = ElectrumBlockchain, D = bdk::sled::Tree, C = Client> {
     client: Arc<Mutex<C>>,
     wallet: Arc<Mutex<bdk::Wallet<B, D>>>,
+    /// UTXOs that are only spent if selected explicitly.
+    frozen_utxos: bdk::sled::Tree,
     finality_confirmations: u32,
     network: Network,
 }
 
+/// An unspent output of the wallet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Utxo {
+    pub outpoint: OutPoint,
+    pub amount: Amount,
+    pub address: Option<Address>,
+    pub is_change: bool,
+    pub frozen: bool,
+}
+
 impl Wallet {
     pub async fn new(
         electrum_rpc_url: Url,
+        cross_check_electrum_rpc_urls: Vec<Url>,
         wallet_dir: &Path,
         key: impl DerivableKey<Segwitv0> + Clone,
         env_config: env::Config,
@@ -40,7 +58,9 @@ impl Wallet {
         let client = bdk::electrum_client::Client::new(electrum_rpc_url.as_str())
             .context("Failed to initialize Electrum RPC client")?;
 
-        let db = bdk::sled::open(wallet_dir)?.open_tree(SLED_TREE_NAME)?;
+        let sled = bdk::sled::open(wallet_dir)?;
+        let db = sled.open_tree(SLED_TREE_NAME)?;
+        let frozen_utxos = sled.open_tree(FROZEN_UTXOS_TREE_NAME)?;
 
         let wallet = bdk::Wallet::new(
             bdk::template::Bip84(key.clone(), KeychainKind::External),
@@ -53,14 +73,21 @@ impl Wallet {
         let electrum = bdk::electrum_client::Client::new(electrum_rpc_url.as_str())
             .context("Failed to initialize Electrum RPC client")?;
 
+        let cross_check_servers = cross_check_electrum_rpc_urls
+            .into_iter()
+            .map(CrossCheck::new)
+            .collect();
+
         let network = wallet.network();
 
         Ok(Self {
             client: Arc::new(Mutex::new(Client::new(
                 electrum,
+                cross_check_servers,
                 env_config.bitcoin_sync_interval(),
             )?)),
             wallet: Arc::new(Mutex::new(wallet)),
+            frozen_utxos,
             finality_confirmations: env_config.bitcoin_finality_confirmations,
             network,
         })
@@ -114,6 +141,8 @@ impl Wallet {
             .with_context(|| format!("Could not get raw tx with id: {}", txid))
     }
 
+    /// The status of the script under which timelocks expire earliest across
+    /// the primary and all cross-check servers.
     pub async fn status_of_script<T>(&self, tx: &T) -> Result<ScriptStatus>
     where
         T: Watchable,
@@ -121,6 +150,18 @@ impl Wallet {
         self.client.lock().await.status_of_script(tx)
     }
 
+    /// The status of the script with the fewest confirmations across the
+    /// primary and all cross-check servers that know the transaction.
+    pub async fn least_confirmed_status_of_script<T>(&self, tx: &T) -> Result<ScriptStatus>
+    where
+        T: Watchable,
+    {
+        self.client
+            .lock()
+            .await
+            .least_confirmed_status_of_script(tx)
+    }
+
     pub async fn subscribe_to(&self, tx: impl Watchable + Send + 'static) -> Subscription {
         let txid = tx.id();
         let script = tx.script();
@@ -141,7 +182,11 @@ impl Wallet {
                     loop {
                         tokio::time::sleep(Duration::from_secs(5)).await;
 
-                        let new_status = match client.lock().await.status_of_script(&tx) {
+                        let new_status = match client
+                            .lock()
+                            .await
+                            .least_confirmed_status_of_script(&tx)
+                        {
                             Ok(new_status) => new_status,
                             Err(e) => {
                                 tracing::warn!(%txid, "Failed to get status of script: {:#}", e);
@@ -276,41 +321,147 @@ where
         Ok(Amount::from_sat(fees))
     }
 
+    /// Builds a transaction paying `amount` to `address`.
+    ///
+    /// Only the given `utxos` are spent, if there are any. Otherwise the coins
+    /// are selected from all UTXOs that are not frozen.
     pub async fn send_to_address(
         &self,
         address: Address,
         amount: Amount,
+        utxos: &[OutPoint],
     ) -> Result<PartiallySignedTransaction> {
+        let frozen_utxos = self.frozen_utxos()?;
         let wallet = self.wallet.lock().await;
 
         let mut tx_builder = wallet.build_tx();
         tx_builder.add_recipient(address.script_pubkey(), amount.as_sat());
         tx_builder.fee_rate(self.select_feerate());
+        if utxos.is_empty() {
+            tx_builder.unspendable(frozen_utxos);
+        } else {
+            ensure_not_frozen(utxos, &frozen_utxos)?;
+            for utxo in utxos {
+                tx_builder.add_utxo(*utxo)?;
+            }
+            tx_builder.manually_selected_only();
+        }
         let (psbt, _details) = tx_builder.finish()?;
 
         Ok(psbt)
     }
 
-    /// Calculates the maximum "giveable" amount of this wallet.
+    /// Calculates the maximum "giveable" amount of this wallet, or of the
+    /// given `utxos` if there are any.
     ///
     /// We define this as the maximum amount we can pay to a single output,
     /// already accounting for the fees we need to spend to get the
-    /// transaction confirmed.
-    pub async fn max_giveable(&self, locking_script_size: usize) -> Result<Amount> {
+    /// transaction confirmed. Frozen UTXOs are only accounted for if they
+    /// are given explicitly.
+    pub async fn max_giveable(
+        &self,
+        locking_script_size: usize,
+        utxos: &[OutPoint],
+    ) -> Result<Amount> {
+        let frozen_utxos = self.frozen_utxos()?;
         let wallet = self.wallet.lock().await;
 
         let mut tx_builder = wallet.build_tx();
 
         let dummy_script = Script::from(vec![0u8; locking_script_size]);
         tx_builder.set_single_recipient(dummy_script);
-        tx_builder.drain_wallet();
         tx_builder.fee_rate(self.select_feerate());
+        if utxos.is_empty() {
+            tx_builder.unspendable(frozen_utxos);
+            tx_builder.drain_wallet();
+        } else {
+            ensure_not_frozen(utxos, &frozen_utxos)?;
+            for utxo in utxos {
+                tx_builder.add_utxo(*utxo)?;
+            }
+            tx_builder.manually_selected_only();
+        }
         let (_, details) = tx_builder.finish().context("Failed to build transaction")?;
 
         let max_giveable = details.sent - details.fees;
 
         Ok(Amount::from_sat(max_giveable))
     }
+
+    pub async fn list_utxos(&self) -> Result<Vec<Utxo>> {
+        let frozen_utxos = self.frozen_utxos()?;
+        let utxos = self
+            .wallet
+            .lock()
+            .await
+            .list_unspent()
+            .context("Failed to list unspent outputs")?
+            .into_iter()
+            .map(|utxo| Utxo {
+                outpoint: utxo.outpoint,
+                amount: Amount::from_sat(utxo.txout.value),
+                address: Address::from_script(&utxo.txout.script_pubkey, self.network),
+                is_change: utxo.keychain == KeychainKind::Internal,
+                frozen: frozen_utxos.contains(&utxo.outpoint),
+            })
+            .collect();
+
+        Ok(utxos)
+    }
+
+    /// Excludes `outpoint` from coin selection until it is unfrozen.
+    pub async fn freeze_utxo(&self, outpoint: OutPoint) -> Result<()> {
+        let is_ours = self
+            .list_utxos()
+            .await?
+            .iter()
+            .any(|utxo| utxo.outpoint == outpoint);
+        if !is_ours {
+            bail!("{} is not an unspent output of the wallet", outpoint)
+        }
+
+        self.frozen_utxos
+            .insert(outpoint.to_string().as_bytes(), vec![])?;
+
+        self.frozen_utxos
+            .flush_async()
+            .await
+            .map(|_| ())
+            .context("Could not flush db")
+    }
+
+    pub async fn unfreeze_utxo(&self, outpoint: OutPoint) -> Result<()> {
+        self.frozen_utxos
+            .remove(outpoint.to_string().as_bytes())?
+            .with_context(|| format!("{} is not frozen", outpoint))?;
+
+        self.frozen_utxos
+            .flush_async()
+            .await
+            .map(|_| ())
+            .context("Could not flush db")
+    }
+
+    fn frozen_utxos(&self) -> Result<Vec<OutPoint>> {
+        self.frozen_utxos
+            .iter()
+            .keys()
+            .map(|key| {
+                let key = key.context("Failed to retrieve frozen UTXO from DB")?;
+                let outpoint = std::str::from_utf8(&key)?.parse()?;
+
+                Ok(outpoint)
+            })
+            .collect()
+    }
+}
+
+fn ensure_not_frozen(utxos: &[OutPoint], frozen_utxos: &[OutPoint]) -> Result<()> {
+    if let Some(frozen) = utxos.iter().find(|utxo| frozen_utxos.contains(utxo)) {
+        bail!("{} is frozen, unfreeze it to spend it", frozen)
+    }
+
+    Ok(())
 }
 
 impl<B, D, C> Wallet<B, D, C>
@@ -344,8 +495,7 @@ impl<B, D, C> Wallet<B, D, C> {
     /// Selects an appropriate [`FeeRate`] to be used for getting transactions
     /// confirmed within a reasonable amount of time.
     fn select_feerate(&self) -> FeeRate {
-        // TODO: This should obviously not be a const :)
-        FeeRate::from_sat_per_vb(5.0)
+        FeeRate::from_sat_per_vb(f32::from(SATS_PER_VBYTE))
     }
 }
 
@@ -376,6 +526,12 @@ impl Wallet<(), bdk::database::MemoryDatabase, ()> {
         Self {
             client: Arc::new(Mutex::new(())),
             wallet: Arc::new(Mutex::new(wallet)),
+            frozen_utxos: bdk::sled::Config::new()
+                .temporary(true)
+                .open()
+                .unwrap()
+                .open_tree(FROZEN_UTXOS_TREE_NAME)
+                .unwrap(),
             finality_confirmations: 1,
             network: Network::Regtest,
         }
@@ -405,23 +561,31 @@ impl Watchable for (Txid, Script) {
 
 pub struct Client {
     electrum: bdk::electrum_client::Client,
+    cross_check_servers: Vec<CrossCheck>,
     latest_block: BlockHeight,
     last_ping: Instant,
+    last_cross_check: Option<Instant>,
     interval: Duration,
     script_history: BTreeMap<Script, Vec<GetHistoryRes>>,
     subscriptions: HashMap<(Txid, Script), Subscription>,
 }
 
 impl Client {
-    fn new(electrum: bdk::electrum_client::Client, interval: Duration) -> Result<Self> {
+    fn new(
+        electrum: bdk::electrum_client::Client,
+        cross_check_servers: Vec<CrossCheck>,
+        interval: Duration,
+    ) -> Result<Self> {
         let latest_block = electrum
             .block_headers_subscribe()
             .context("Failed to subscribe to header notifications")?;
 
         Ok(Self {
             electrum,
+            cross_check_servers,
             latest_block: BlockHeight::try_from(latest_block)?,
             last_ping: Instant::now(),
+            last_cross_check: None,
             interval,
             script_history: Default::default(),
             subscriptions: Default::default(),
@@ -460,10 +624,51 @@ impl Client {
         self.drain_blockheight_notifications()?;
         self.update_script_histories()?;
 
+        let cross_check_due = self
+            .last_cross_check
+            .map_or(true, |last| last.elapsed() >= CROSS_CHECK_INTERVAL);
+
+        if cross_check_due && !self.cross_check_servers.is_empty() {
+            self.cross_check();
+            self.last_cross_check = Some(Instant::now());
+        }
+
         Ok(())
     }
 
+    /// The status of the script under which timelocks expire earliest.
+    ///
+    /// Takes the highest block height and the earliest inclusion height
+    /// reported by the primary server or any reachable cross-check server, so
+    /// the state machine acts on a cancel or punish timelock as soon as any
+    /// server considers it expired.
     fn status_of_script<T>(&mut self, tx: &T) -> Result<ScriptStatus>
+    where
+        T: Watchable,
+    {
+        let views = self.views_of_script(tx)?;
+
+        most_confirmed(&views)
+    }
+
+    /// The status of the script with the fewest confirmations reported by
+    /// any server that knows the transaction.
+    ///
+    /// Used to wait for our own transactions, which must not be considered
+    /// final before all servers agree.
+    fn least_confirmed_status_of_script<T>(&mut self, tx: &T) -> Result<ScriptStatus>
+    where
+        T: Watchable,
+    {
+        let views = self.views_of_script(tx)?;
+
+        least_confirmed(&views)
+    }
+
+    /// The latest block and the inclusion height of the transaction, if
+    /// known, as reported by the primary server and every cross-check server
+    /// that was reachable during the last cross-check.
+    fn views_of_script<T>(&mut self, tx: &T) -> Result<Vec<(BlockHeight, Option<i32>)>>
     where
         T: Watchable,
     {
@@ -476,32 +681,41 @@ impl Client {
 
         self.drain_notifications()?;
 
-        let history = self.script_history.entry(script).or_default();
+        let history = self.script_history.entry(script.clone()).or_default();
 
         let history_of_tx = history
             .iter()
             .filter(|entry| entry.tx_hash == txid)
             .collect::<Vec<_>>();
 
-        match history_of_tx.as_slice() {
-            [] => Ok(ScriptStatus::Unseen),
+        let inclusion_height = match history_of_tx.as_slice() {
+            [] => None,
             [remaining @ .., last] => {
                 if !remaining.is_empty() {
                     tracing::warn!("Found more than a single history entry for script. This is highly unexpected and those history entries will be ignored.")
                 }
 
-                if last.height <= 0 {
-                    Ok(ScriptStatus::InMempool)
-                } else {
-                    Ok(ScriptStatus::Confirmed(
-                        Confirmed::from_inclusion_and_latest_block(
-                            u32::try_from(last.height)?,
-                            u32::from(self.latest_block),
-                        ),
-                    ))
-                }
+                Some(last.height)
             }
-        }
+        };
+
+        let cross_check_views = self
+            .cross_check_servers
+            .iter()
+            .filter_map(|server| server.view.as_ref())
+            .map(|view| {
+                let inclusion_height = view
+                    .script_history
+                    .get(&script)
+                    .and_then(|history| history.iter().rev().find(|entry| entry.tx_hash == txid))
+                    .map(|entry| entry.height);
+
+                (view.latest_block, inclusion_height)
+            });
+
+        Ok(std::iter::once((self.latest_block, inclusion_height))
+            .chain(cross_check_views)
+            .collect())
     }
 
     fn drain_blockheight_notifications(&mut self) -> Result<()> {
@@ -542,9 +756,251 @@ impl Client {
 
         Ok(())
     }
+
+    /// Fetches the view of the blockchain of all configured cross-check
+    /// servers and compares it with ours.
+    ///
+    /// Disagreements are logged, the views are taken into account by
+    /// [`Client::status_of_script`] and
+    /// [`Client::least_confirmed_status_of_script`]. A single honest server is
+    /// therefore enough to notice a transaction that is hidden from us.
+    ///
+    /// Failures to reach a cross-check server are logged but do not fail the
+    /// update, the server is ignored until it can be reached again.
+    fn cross_check(&mut self) {
+        let scripts = self.script_history.keys().cloned().collect::<Vec<_>>();
+
+        for server in self.cross_check_servers.iter_mut() {
+            let url = server.url.clone();
+
+            let (latest_block, histories) = match server.fetch(&scripts) {
+                Ok(view) => (view.latest_block, view.script_history.clone()),
+                Err(e) => {
+                    tracing::warn!(%url, "Failed to cross-check Electrum data: {:#}", e);
+                    continue;
+                }
+            };
+
+            if is_significant_height_disagreement(self.latest_block, latest_block) {
+                tracing::warn!(
+                    %url,
+                    "Cross-check server reports block height {} but our Electrum server reports {}",
+                    u32::from(latest_block),
+                    u32::from(self.latest_block)
+                );
+            }
+
+            for (script, history) in histories {
+                let ours = self.script_history.entry(script).or_default();
+
+                for theirs in history {
+                    match ours.iter().find(|entry| entry.tx_hash == theirs.tx_hash) {
+                        Some(entry) if entry.height != theirs.height => {
+                            tracing::warn!(
+                                %url,
+                                txid = %theirs.tx_hash,
+                                "Cross-check server reports transaction at height {} but our Electrum server reports {}",
+                                theirs.height,
+                                entry.height
+                            );
+                        }
+                        Some(_) => {}
+                        None => {
+                            tracing::warn!(
+                                %url,
+                                txid = %theirs.tx_hash,
+                                "Cross-check server knows a transaction our Electrum server did not report"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Cross-checking every sync would query all servers while the client is
+/// locked, once a minute is enough to notice a hidden transaction well before
+/// any timelock expires.
+const CROSS_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// An additional Electrum server that is only used to cross-check the data
+/// returned by our primary server.
+///
+/// The server is connected to lazily, and again after every failure, so an
+/// unreachable server never prevents the wallet from starting.
+struct CrossCheck {
+    url: Url,
+    connection: Option<CrossCheckConnection>,
+    /// The view of the last successful cross-check, `None` while the server
+    /// cannot be reached.
+    view: Option<CrossCheckView>,
+}
+
+struct CrossCheckConnection {
+    electrum: bdk::electrum_client::Client,
+    latest_block: BlockHeight,
+}
+
+struct CrossCheckView {
+    latest_block: BlockHeight,
+    script_history: BTreeMap<Script, Vec<GetHistoryRes>>,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+impl CrossCheck {
+    fn new(url: Url) -> Self {
+        Self {
+            url,
+            connection: None,
+            view: None,
+        }
+    }
+
+    fn fetch(&mut self, scripts: &[Script]) -> Result<&CrossCheckView> {
+        match self.try_fetch(scripts) {
+            Ok(view) => self.view = Some(view),
+            Err(e) => {
+                self.connection = None;
+                self.view = None;
+
+                return Err(e);
+            }
+        }
+
+        Ok(self.view.as_ref().expect("view was just set"))
+    }
+
+    fn try_fetch(&mut self, scripts: &[Script]) -> Result<CrossCheckView> {
+        if self.connection.is_none() {
+            self.connection = Some(CrossCheckConnection::new(&self.url)?);
+        }
+        let connection = self.connection.as_mut().expect("connected above");
+
+        let histories = connection
+            .electrum
+            .batch_script_get_history(scripts)
+            .context("Failed to get script histories")?;
+
+        if histories.len() != scripts.len() {
+            bail!(
+                "Expected {} history entries, received {}",
+                scripts.len(),
+                histories.len()
+            );
+        }
+
+        let latest_block =
+            std::iter::from_fn(|| connection.electrum.block_headers_pop().transpose())
+                .last()
+                .transpose()
+                .context("Failed to pop header notification")?;
+
+        if let Some(new_block) = latest_block {
+            connection.latest_block = BlockHeight::try_from(new_block)?;
+        }
+
+        Ok(CrossCheckView {
+            latest_block: connection.latest_block,
+            script_history: scripts.iter().cloned().zip(histories).collect(),
+        })
+    }
+}
+
+impl CrossCheckConnection {
+    fn new(url: &Url) -> Result<Self> {
+        let electrum = bdk::electrum_client::Client::new(url.as_str())
+            .context("Failed to initialize Electrum RPC client")?;
+        let latest_block = electrum
+            .block_headers_subscribe()
+            .context("Failed to subscribe to header notifications")?;
+
+        Ok(Self {
+            electrum,
+            latest_block: BlockHeight::try_from(latest_block)?,
+        })
+    }
+}
+
+/// The status of a transaction under which timelocks expire earliest: the
+/// highest block height and the earliest inclusion height any server reports.
+///
+/// `views` holds the latest block of each server and the inclusion height of
+/// the transaction, if the server knows it.
+fn most_confirmed(views: &[(BlockHeight, Option<i32>)]) -> Result<ScriptStatus> {
+    let latest_block = views.iter().map(|(latest_block, _)| *latest_block).max();
+    let inclusion_height = views
+        .iter()
+        .filter_map(|(_, inclusion_height)| *inclusion_height)
+        .filter(|inclusion_height| *inclusion_height > 0)
+        .min();
+
+    match (latest_block, inclusion_height) {
+        (Some(latest_block), Some(inclusion_height)) => Ok(ScriptStatus::Confirmed(
+            Confirmed::from_inclusion_and_latest_block(
+                u32::try_from(inclusion_height)?,
+                u32::from(latest_block),
+            ),
+        )),
+        _ if views
+            .iter()
+            .any(|(_, inclusion_height)| inclusion_height.is_some()) =>
+        {
+            Ok(ScriptStatus::InMempool)
+        }
+        _ => Ok(ScriptStatus::Unseen),
+    }
+}
+
+/// The status of a transaction with the fewest confirmations reported by any
+/// server.
+///
+/// Once any server knows the transaction, a server that does not report it is
+/// taken as seeing it in the mempool: the transaction exists but is not
+/// confirmed from the point of view of that server.
+fn least_confirmed(views: &[(BlockHeight, Option<i32>)]) -> Result<ScriptStatus> {
+    if views
+        .iter()
+        .all(|(_, inclusion_height)| inclusion_height.is_none())
+    {
+        return Ok(ScriptStatus::Unseen);
+    }
+
+    let mut least_confirmed = None;
+
+    for (latest_block, inclusion_height) in views {
+        let status = match inclusion_height {
+            None => ScriptStatus::InMempool,
+            Some(height) if *height <= 0 => ScriptStatus::InMempool,
+            Some(height) => ScriptStatus::Confirmed(Confirmed::from_inclusion_and_latest_block(
+                u32::try_from(*height)?,
+                u32::from(*latest_block),
+            )),
+        };
+
+        least_confirmed = Some(match least_confirmed {
+            Some(least_confirmed) => std::cmp::min(least_confirmed, status),
+            None => status,
+        });
+    }
+
+    Ok(least_confirmed.unwrap_or(ScriptStatus::Unseen))
+}
+
+/// Servers may legitimately disagree by a single block for a short amount of
+/// time while a new block propagates.
+const TOLERATED_BLOCK_HEIGHT_DISAGREEMENT: u32 = 1;
+
+fn is_significant_height_disagreement(ours: BlockHeight, theirs: BlockHeight) -> bool {
+    let difference = std::cmp::max(ours, theirs)
+        .checked_sub(std::cmp::min(ours, theirs))
+        .expect("max is always greater or equal than min");
+
+    u32::from(difference) > TOLERATED_BLOCK_HEIGHT_DISAGREEMENT
+}
+
+/// Ordered by the number of confirmations, an unseen transaction has the
+/// fewest.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ScriptStatus {
     Unseen,
     InMempool,
@@ -560,7 +1016,7 @@ impl ScriptStatus {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Confirmed {
     /// The depth of this transaction within the blockchain.
     ///
@@ -635,6 +1091,31 @@ impl fmt::Display for ScriptStatus {
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn frozen_utxos_are_not_spent() {
+        let wallet = Wallet::new_funded(50_000);
+        let utxo = wallet.list_utxos().await.unwrap()[0].outpoint;
+
+        wallet.freeze_utxo(utxo).await.unwrap();
+
+        assert!(wallet.list_utxos().await.unwrap()[0].frozen);
+        assert!(wallet.max_giveable(22, &[]).await.is_err());
+        assert!(wallet.max_giveable(22, &[utxo]).await.is_err());
+
+        wallet.unfreeze_utxo(utxo).await.unwrap();
+
+        assert!(wallet.max_giveable(22, &[utxo]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn only_unspent_outputs_of_the_wallet_can_be_frozen() {
+        let wallet = Wallet::new_funded(50_000);
+
+        let result = wallet.freeze_utxo(OutPoint::default()).await;
+
+        assert!(result.is_err());
+    }
+
     #[test]
     fn given_depth_0_should_meet_confirmation_target_one() {
         let script = ScriptStatus::Confirmed(Confirmed { depth: 0 });
@@ -653,6 +1134,79 @@ mod tests {
         assert!(confirmed)
     }
 
+    #[test]
+    fn timelocks_expire_under_highest_block_and_earliest_inclusion() {
+        let views = [
+            (BlockHeight::new(110), Some(100)),
+            (BlockHeight::new(112), Some(101)),
+            (BlockHeight::new(111), None),
+        ];
+
+        let status = most_confirmed(&views).unwrap();
+
+        assert_eq!(status, ScriptStatus::Confirmed(Confirmed::new(12)));
+    }
+
+    #[test]
+    fn transaction_hidden_by_primary_server_counts_towards_timelocks() {
+        let views = [
+            (BlockHeight::new(110), None),
+            (BlockHeight::new(110), Some(100)),
+        ];
+
+        let status = most_confirmed(&views).unwrap();
+
+        assert_eq!(status, ScriptStatus::Confirmed(Confirmed::new(10)));
+    }
+
+    #[test]
+    fn own_transaction_is_only_as_confirmed_as_least_confirmed_view() {
+        let views = [
+            (BlockHeight::new(110), Some(100)),
+            (BlockHeight::new(112), Some(105)),
+        ];
+
+        let status = least_confirmed(&views).unwrap();
+
+        assert_eq!(status, ScriptStatus::Confirmed(Confirmed::new(7)));
+    }
+
+    #[test]
+    fn transaction_unknown_to_one_server_is_not_confirmed() {
+        let views = [
+            (BlockHeight::new(110), Some(100)),
+            (BlockHeight::new(111), None),
+        ];
+
+        let status = least_confirmed(&views).unwrap();
+
+        assert_eq!(status, ScriptStatus::InMempool);
+    }
+
+    #[test]
+    fn given_no_server_knows_transaction_unseen() {
+        let views = [(BlockHeight::new(110), None)];
+
+        assert_eq!(most_confirmed(&views).unwrap(), ScriptStatus::Unseen);
+        assert_eq!(least_confirmed(&views).unwrap(), ScriptStatus::Unseen);
+    }
+
+    #[test]
+    fn given_block_heights_one_apart_no_significant_disagreement() {
+        let disagreement =
+            is_significant_height_disagreement(BlockHeight::new(100), BlockHeight::new(101));
+
+        assert!(!disagreement)
+    }
+
+    #[test]
+    fn given_block_heights_more_than_one_apart_significant_disagreement() {
+        let disagreement =
+            is_significant_height_disagreement(BlockHeight::new(103), BlockHeight::new(100));
+
+        assert!(disagreement)
+    }
+
     #[test]
     fn given_inclusion_after_lastest_known_block_at_least_depth_0() {
         let included_in = 10;
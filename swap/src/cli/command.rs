@@ -1,5 +1,9 @@
+use crate::bitcoin;
 use crate::fs::system_data_dir;
-use anyhow::{Context, Result};
+use crate::notification::Hooks;
+use ::bitcoin::util::amount::ParseAmountError;
+use ::bitcoin::OutPoint;
+use anyhow::{bail, Context, Result};
 use libp2p::core::Multiaddr;
 use libp2p::PeerId;
 use std::path::{Path, PathBuf};
@@ -48,11 +52,58 @@ pub enum Command {
         )]
         electrum_rpc_url: Url,
 
+        #[structopt(
+            long = "electrum-cross-check-rpc",
+            help = "Provide an additional Electrum RPC URL to cross-check the data of the primary Electrum server against, can be given multiple times"
+        )]
+        cross_check_electrum_rpc_urls: Vec<Url>,
+
         #[structopt(flatten)]
         monero_params: MoneroParams,
 
         #[structopt(long = "tor-socks5-port", help = "Your local Tor socks5 proxy port", default_value = DEFAULT_TOR_SOCKS5_PORT)]
         tor_socks5_port: u16,
+
+        #[structopt(
+            long = "psbt-dir",
+            help = "Sign the Bitcoin lock transaction funded via --fund-from with an external wallet: the unsigned PSBT is written to this directory and the swap waits for the finalized PSBT to be saved next to it",
+            parse(from_os_str),
+            requires = "fund-from"
+        )]
+        psbt_dir: Option<PathBuf>,
+
+        #[structopt(
+            long = "fund-from",
+            help = "Fund the Bitcoin lock transaction directly from this segwit output of an external wallet, given as <txid>:<vout>, instead of depositing to the internal wallet first. Requires --psbt-dir and --change-address",
+            requires_all = &["psbt-dir", "change-address"]
+        )]
+        fund_from: Option<OutPoint>,
+
+        #[structopt(
+            long = "change-address",
+            help = "The address that receives the change of the output given with --fund-from",
+            requires = "fund-from"
+        )]
+        change_address: Option<bitcoin::Address>,
+
+        #[structopt(
+            long = "refund-address",
+            help = "The address that receives the BTC if the swap is refunded, defaults to an address of the internal wallet"
+        )]
+        refund_address: Option<bitcoin::Address>,
+
+        #[structopt(
+            long = "utxo",
+            help = "Only fund the Bitcoin lock transaction from this output of the internal wallet, given as <txid>:<vout>, can be given multiple times",
+            conflicts_with = "fund-from"
+        )]
+        utxos: Vec<OutPoint>,
+
+        #[structopt(flatten)]
+        notification_params: NotificationParams,
+
+        #[structopt(flatten)]
+        watchtower_params: WatchtowerParams,
     },
     /// Show a list of past ongoing and completed swaps
     History,
@@ -73,11 +124,30 @@ pub enum Command {
         )]
         electrum_rpc_url: Url,
 
+        #[structopt(
+            long = "electrum-cross-check-rpc",
+            help = "Provide an additional Electrum RPC URL to cross-check the data of the primary Electrum server against, can be given multiple times"
+        )]
+        cross_check_electrum_rpc_urls: Vec<Url>,
+
         #[structopt(flatten)]
         monero_params: MoneroParams,
 
         #[structopt(long = "tor-socks5-port", help = "Your local Tor socks5 proxy port", default_value = DEFAULT_TOR_SOCKS5_PORT)]
         tor_socks5_port: u16,
+
+        #[structopt(
+            long = "psbt-dir",
+            help = "Required to resume a swap funded via --fund-from whose Bitcoin lock transaction is not signed yet: the unsigned PSBT is written to this directory and the swap waits for the finalized PSBT to be saved next to it",
+            parse(from_os_str)
+        )]
+        psbt_dir: Option<PathBuf>,
+
+        #[structopt(flatten)]
+        notification_params: NotificationParams,
+
+        #[structopt(flatten)]
+        watchtower_params: WatchtowerParams,
     },
     /// Try to cancel an ongoing swap (expert users only)
     Cancel {
@@ -95,6 +165,12 @@ pub enum Command {
         default_value = DEFAULT_ELECTRUM_RPC_URL
         )]
         electrum_rpc_url: Url,
+
+        #[structopt(
+            long = "electrum-cross-check-rpc",
+            help = "Provide an additional Electrum RPC URL to cross-check the data of the primary Electrum server against, can be given multiple times"
+        )]
+        cross_check_electrum_rpc_urls: Vec<Url>,
     },
     /// Try to cancel a swap and refund my BTC (expert users only)
     Refund {
@@ -112,6 +188,169 @@ pub enum Command {
         default_value = DEFAULT_ELECTRUM_RPC_URL
         )]
         electrum_rpc_url: Url,
+
+        #[structopt(
+            long = "electrum-cross-check-rpc",
+            help = "Provide an additional Electrum RPC URL to cross-check the data of the primary Electrum server against, can be given multiple times"
+        )]
+        cross_check_electrum_rpc_urls: Vec<Url>,
+    },
+    /// Check the timelocks of all unfinished swaps, exits with an error if a
+    /// swap has to be resumed or refunded now
+    Check {
+        #[structopt(
+            long = "refund",
+            help = "Cancel and refund every swap whose cancel timelock expired"
+        )]
+        refund: bool,
+
+        #[structopt(long = "electrum-rpc",
+        help = "Provide the Bitcoin Electrum RPC URL",
+        default_value = DEFAULT_ELECTRUM_RPC_URL
+        )]
+        electrum_rpc_url: Url,
+
+        #[structopt(
+            long = "electrum-cross-check-rpc",
+            help = "Provide an additional Electrum RPC URL to cross-check the data of the primary Electrum server against, can be given multiple times"
+        )]
+        cross_check_electrum_rpc_urls: Vec<Url>,
+
+        #[structopt(flatten)]
+        notification_params: NotificationParams,
+    },
+    /// Hand the signed cancel transaction of a swap whose BTC are locked to a
+    /// watchtower, which cancels the swap if the CLI is offline, together with
+    /// the signed refund transaction once the cancel transaction is final
+    Watch {
+        #[structopt(
+            long = "swap-id",
+            help = "The swap id can be retrieved using the history subcommand"
+        )]
+        swap_id: Uuid,
+
+        #[structopt(long = "watchtower-peer-id", help = "The watchtower's peer id")]
+        watchtower_peer_id: PeerId,
+
+        #[structopt(long = "watchtower-addr", help = "The watchtower's multiaddress")]
+        watchtower_multiaddr: Multiaddr,
+
+        #[structopt(long = "tor-socks5-port", help = "Your local Tor socks5 proxy port", default_value = DEFAULT_TOR_SOCKS5_PORT)]
+        tor_socks5_port: u16,
+
+        #[structopt(long = "electrum-rpc",
+        help = "Provide the Bitcoin Electrum RPC URL",
+        default_value = DEFAULT_ELECTRUM_RPC_URL
+        )]
+        electrum_rpc_url: Url,
+
+        #[structopt(
+            long = "electrum-cross-check-rpc",
+            help = "Provide an additional Electrum RPC URL to cross-check the data of the primary Electrum server against, can be given multiple times"
+        )]
+        cross_check_electrum_rpc_urls: Vec<Url>,
+    },
+    /// Tell the watchtower to stop watching a swap
+    Unwatch {
+        #[structopt(
+            long = "swap-id",
+            help = "The swap id can be retrieved using the history subcommand"
+        )]
+        swap_id: Uuid,
+
+        #[structopt(long = "watchtower-peer-id", help = "The watchtower's peer id")]
+        watchtower_peer_id: PeerId,
+
+        #[structopt(long = "watchtower-addr", help = "The watchtower's multiaddress")]
+        watchtower_multiaddr: Multiaddr,
+
+        #[structopt(long = "tor-socks5-port", help = "Your local Tor socks5 proxy port", default_value = DEFAULT_TOR_SOCKS5_PORT)]
+        tor_socks5_port: u16,
+    },
+    /// Withdraw BTC from the internal wallet, e.g. left over deposits
+    WithdrawBtc {
+        #[structopt(
+            long = "amount",
+            help = "Optionally specify the amount of Bitcoin to be withdrawn. If not specified the wallet will be drained.",
+            parse(try_from_str = parse_btc)
+        )]
+        amount: Option<bitcoin::Amount>,
+
+        #[structopt(long = "address", help = "The address to receive the Bitcoin.")]
+        address: bitcoin::Address,
+
+        #[structopt(
+            long = "utxo",
+            help = "Only spend this output, given as <txid>:<vout>, can be given multiple times"
+        )]
+        utxos: Vec<OutPoint>,
+
+        #[structopt(long = "electrum-rpc",
+        help = "Provide the Bitcoin Electrum RPC URL",
+        default_value = DEFAULT_ELECTRUM_RPC_URL
+        )]
+        electrum_rpc_url: Url,
+
+        #[structopt(
+            long = "electrum-cross-check-rpc",
+            help = "Provide an additional Electrum RPC URL to cross-check the data of the primary Electrum server against, can be given multiple times"
+        )]
+        cross_check_electrum_rpc_urls: Vec<Url>,
+    },
+    /// Show the unspent outputs of the Bitcoin wallet
+    ListUtxos {
+        #[structopt(long = "electrum-rpc",
+        help = "Provide the Bitcoin Electrum RPC URL",
+        default_value = DEFAULT_ELECTRUM_RPC_URL
+        )]
+        electrum_rpc_url: Url,
+
+        #[structopt(
+            long = "electrum-cross-check-rpc",
+            help = "Provide an additional Electrum RPC URL to cross-check the data of the primary Electrum server against, can be given multiple times"
+        )]
+        cross_check_electrum_rpc_urls: Vec<Url>,
+    },
+    /// Exclude an unspent output from being spent unless it is selected
+    /// explicitly
+    FreezeUtxo {
+        #[structopt(
+            long = "outpoint",
+            help = "The output to freeze, given as <txid>:<vout>"
+        )]
+        outpoint: OutPoint,
+
+        #[structopt(long = "electrum-rpc",
+        help = "Provide the Bitcoin Electrum RPC URL",
+        default_value = DEFAULT_ELECTRUM_RPC_URL
+        )]
+        electrum_rpc_url: Url,
+
+        #[structopt(
+            long = "electrum-cross-check-rpc",
+            help = "Provide an additional Electrum RPC URL to cross-check the data of the primary Electrum server against, can be given multiple times"
+        )]
+        cross_check_electrum_rpc_urls: Vec<Url>,
+    },
+    /// Allow a frozen unspent output to be spent again
+    UnfreezeUtxo {
+        #[structopt(
+            long = "outpoint",
+            help = "The output to unfreeze, given as <txid>:<vout>"
+        )]
+        outpoint: OutPoint,
+
+        #[structopt(long = "electrum-rpc",
+        help = "Provide the Bitcoin Electrum RPC URL",
+        default_value = DEFAULT_ELECTRUM_RPC_URL
+        )]
+        electrum_rpc_url: Url,
+
+        #[structopt(
+            long = "electrum-cross-check-rpc",
+            help = "Provide an additional Electrum RPC URL to cross-check the data of the primary Electrum server against, can be given multiple times"
+        )]
+        cross_check_electrum_rpc_urls: Vec<Url>,
     },
 }
 
@@ -125,10 +364,69 @@ pub struct MoneroParams {
 
     #[structopt(
         long = "monero-daemon-host",
-        help = "Specify to connect to a monero daemon of your choice",
+        help = "Specify to connect to a monero daemon of your choice, can be given multiple times in order of preference to fall back to other daemons if one is down or lagging behind",
         default_value = DEFAULT_STAGENET_MONERO_DAEMON_HOST
     )]
-    pub monero_daemon_host: String,
+    pub monero_daemon_hosts: Vec<String>,
+
+    #[structopt(
+        long = "monero-wallet-rpc-path",
+        help = "Use a monero-wallet-rpc executable installed on this system instead of downloading one"
+    )]
+    pub monero_wallet_rpc_path: Option<PathBuf>,
+
+    #[structopt(
+        long = "monero-wallet-rpc-url",
+        help = "Connect to an already running monero-wallet-rpc instead of starting one, the daemon it connects to is managed by the wallet RPC",
+        conflicts_with = "monero-wallet-rpc-path"
+    )]
+    pub monero_wallet_rpc_url: Option<Url>,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+pub struct NotificationParams {
+    #[structopt(
+        long = "notify-webhook",
+        help = "POST a JSON notification to this URL whenever the swap progresses or fails"
+    )]
+    pub webhook_url: Option<Url>,
+
+    #[structopt(
+        long = "notify-command",
+        help = "Run this command via `sh -c` whenever the swap progresses or fails, the JSON notification is passed in the SWAP_NOTIFICATION environment variable"
+    )]
+    pub command: Option<String>,
+}
+
+impl From<NotificationParams> for Hooks {
+    fn from(params: NotificationParams) -> Self {
+        Hooks {
+            webhook_url: params.webhook_url,
+            command: params.command,
+        }
+    }
+}
+
+#[derive(structopt::StructOpt, Debug)]
+pub struct WatchtowerParams {
+    #[structopt(
+        long = "watchtower-peer-id",
+        help = "Hand the cancel transaction to this watchtower as soon as the BTC are locked and the refund transaction once the cancel transaction is final, requires --watchtower-addr"
+    )]
+    pub peer_id: Option<PeerId>,
+
+    #[structopt(long = "watchtower-addr", help = "The watchtower's multiaddress")]
+    pub multiaddr: Option<Multiaddr>,
+}
+
+impl WatchtowerParams {
+    pub fn watchtower(self) -> Result<Option<(PeerId, Multiaddr)>> {
+        match (self.peer_id, self.multiaddr) {
+            (Some(peer_id), Some(multiaddr)) => Ok(Some((peer_id, multiaddr))),
+            (None, None) => Ok(None),
+            _ => bail!("--watchtower-peer-id and --watchtower-addr have to be given together"),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -164,6 +462,10 @@ impl ToString for Data {
     }
 }
 
+fn parse_btc(s: &str) -> Result<bitcoin::Amount, ParseAmountError> {
+    bitcoin::Amount::from_str_in(s, ::bitcoin::Denomination::Bitcoin)
+}
+
 fn parse_monero_address(s: &str) -> Result<monero::Address> {
     monero::Address::from_str(s).with_context(|| {
         format!(
@@ -1,6 +1,7 @@
 pub use alice::Alice;
 pub use bob::Bob;
 
+use crate::{bitcoin, monero};
 use anyhow::{anyhow, bail, Context, Result};
 use itertools::Itertools;
 use libp2p::PeerId;
@@ -9,6 +10,7 @@ use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 use std::path::Path;
 use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
 mod alice;
@@ -68,6 +70,19 @@ impl Swap {
 pub struct Database {
     swaps: sled::Tree,
     peers: sled::Tree,
+    peer_swaps: sled::Tree,
+    banned_peers: sled::Tree,
+    xmr_reservations: sled::Tree,
+}
+
+/// What we know about a swap with a peer to enforce per-peer limits.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+struct PeerSwap {
+    peer_id: String,
+    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    btc: bitcoin::Amount,
+    /// Seconds since the unix epoch.
+    started_at: u64,
 }
 
 impl Database {
@@ -79,8 +94,17 @@ impl Database {
 
         let swaps = db.open_tree("swaps")?;
         let peers = db.open_tree("peers")?;
-
-        Ok(Database { swaps, peers })
+        let peer_swaps = db.open_tree("peer_swaps")?;
+        let banned_peers = db.open_tree("banned_peers")?;
+        let xmr_reservations = db.open_tree("xmr_reservations")?;
+
+        Ok(Database {
+            swaps,
+            peers,
+            peer_swaps,
+            banned_peers,
+            xmr_reservations,
+        })
     }
 
     pub async fn insert_peer_id(&self, swap_id: Uuid, peer_id: PeerId) -> Result<()> {
@@ -110,6 +134,190 @@ impl Database {
         Ok(PeerId::from_str(peer_id.as_str())?)
     }
 
+    /// Record that we started a swap over `btc` with `peer_id` at
+    /// `started_at`.
+    pub async fn insert_peer_swap(
+        &self,
+        swap_id: Uuid,
+        peer_id: PeerId,
+        btc: bitcoin::Amount,
+        started_at: SystemTime,
+    ) -> Result<()> {
+        let key = peer_swap_key(peer_id, swap_id);
+        let value = serialize(&PeerSwap {
+            peer_id: peer_id.to_string(),
+            btc,
+            started_at: started_at.duration_since(UNIX_EPOCH)?.as_secs(),
+        })
+        .context("Could not serialize peer swap")?;
+
+        self.peer_swaps.insert(key, value)?;
+
+        self.peer_swaps
+            .flush_async()
+            .await
+            .map(|_| ())
+            .context("Could not flush db")
+    }
+
+    /// The BTC volume of all swaps started with `peer_id` since `since` that
+    /// locked their BTC.
+    ///
+    /// Swaps that are still waiting for the BTC or were aborted before they
+    /// were locked did not trade anything and do not count.
+    pub fn peer_volume_since(&self, peer_id: PeerId, since: SystemTime) -> Result<bitcoin::Amount> {
+        let since = since.duration_since(UNIX_EPOCH)?.as_secs();
+
+        Ok(self
+            .swaps_with_peer(peer_id)?
+            .into_iter()
+            .filter(|(_, peer_swap)| peer_swap.started_at >= since)
+            .filter(|(swap_id, _)| {
+                // A swap that has no state yet has only just been set up
+                matches!(
+                    self.get_state(*swap_id),
+                    Ok(Swap::Alice(state)) if !matches!(
+                        state,
+                        Alice::Started { .. } | Alice::Done(AliceEndState::SafelyAborted)
+                    )
+                )
+            })
+            .fold(bitcoin::Amount::ZERO, |total, (_, peer_swap)| {
+                total + peer_swap.btc
+            }))
+    }
+
+    /// The number of swaps with `peer_id` that have not finished yet.
+    pub fn unfinished_swaps_with_peer(&self, peer_id: PeerId) -> Result<usize> {
+        let unfinished = self
+            .swaps_with_peer(peer_id)?
+            .into_iter()
+            .filter(|(swap_id, _)| {
+                // A swap that has no state yet has only just been set up
+                !matches!(
+                    self.get_state(*swap_id),
+                    Ok(Swap::Alice(Alice::Done(_))) | Ok(Swap::Bob(Bob::Done(_)))
+                )
+            })
+            .count();
+
+        Ok(unfinished)
+    }
+
+    fn swaps_with_peer(&self, peer_id: PeerId) -> Result<Vec<(Uuid, PeerSwap)>> {
+        let prefix = peer_id.to_bytes();
+
+        self.peer_swaps
+            .scan_prefix(&prefix)
+            .map(|item| {
+                let (key, value) = item.context("Failed to retrieve peer swap from DB")?;
+                let swap_id = Uuid::from_slice(&key[prefix.len()..])?;
+                let peer_swap =
+                    deserialize::<PeerSwap>(&value).context("Failed to deserialize peer swap")?;
+
+                Ok((swap_id, peer_swap))
+            })
+            .collect()
+    }
+
+    pub async fn ban_peer(&self, peer_id: PeerId, reason: String) -> Result<()> {
+        let key = serialize(&peer_id.to_string())?;
+        let value = serialize(&reason)?;
+
+        self.banned_peers.insert(key, value)?;
+
+        self.banned_peers
+            .flush_async()
+            .await
+            .map(|_| ())
+            .context("Could not flush db")
+    }
+
+    pub async fn unban_peer(&self, peer_id: PeerId) -> Result<()> {
+        let key = serialize(&peer_id.to_string())?;
+
+        self.banned_peers
+            .remove(key)?
+            .ok_or_else(|| anyhow!("Peer {} is not banned", peer_id))?;
+
+        self.banned_peers
+            .flush_async()
+            .await
+            .map(|_| ())
+            .context("Could not flush db")
+    }
+
+    /// All banned peers together with the reason they were banned for.
+    pub fn banned_peers(&self) -> Result<Vec<(PeerId, String)>> {
+        self.banned_peers
+            .iter()
+            .map(|item| {
+                let (key, value) = item.context("Failed to retrieve banned peer from DB")?;
+
+                let peer_id: String = deserialize(&key).context("Could not deserialize peer-id")?;
+                let reason: String = deserialize(&value).context("Could not deserialize reason")?;
+
+                Ok((PeerId::from_str(peer_id.as_str())?, reason))
+            })
+            .collect()
+    }
+
+    /// Reserve `amount` of our XMR for the swap with `swap_id` until the XMR
+    /// are locked or the swap is aborted.
+    pub async fn insert_xmr_reservation(
+        &self,
+        swap_id: Uuid,
+        amount: monero::Amount,
+    ) -> Result<()> {
+        let key = serialize(&swap_id)?;
+        let value = serialize(&amount).context("Could not serialize XMR reservation")?;
+
+        self.xmr_reservations.insert(key, value)?;
+
+        self.xmr_reservations
+            .flush_async()
+            .await
+            .map(|_| ())
+            .context("Could not flush db")
+    }
+
+    pub async fn remove_xmr_reservation(&self, swap_id: Uuid) -> Result<()> {
+        let key = serialize(&swap_id)?;
+
+        if self.xmr_reservations.remove(key)?.is_none() {
+            return Ok(());
+        }
+
+        self.xmr_reservations
+            .flush_async()
+            .await
+            .map(|_| ())
+            .context("Could not flush db")
+    }
+
+    /// The XMR that are currently reserved, per swap.
+    pub fn xmr_reservations(&self) -> Result<Vec<(Uuid, monero::Amount)>> {
+        self.xmr_reservations
+            .iter()
+            .map(|item| {
+                let (key, value) = item.context("Failed to retrieve XMR reservation from DB")?;
+                let swap_id = deserialize::<Uuid>(&key)?;
+                let amount = deserialize::<monero::Amount>(&value)
+                    .context("Failed to deserialize XMR reservation")?;
+
+                Ok((swap_id, amount))
+            })
+            .collect()
+    }
+
+    /// The sum of all XMR that are currently reserved for swaps.
+    pub fn total_xmr_reserved(&self) -> Result<monero::Amount> {
+        Ok(self
+            .xmr_reservations()?
+            .into_iter()
+            .fold(monero::Amount::ZERO, |total, (_, amount)| total + amount))
+    }
+
     pub async fn insert_latest_state(&self, swap_id: Uuid, state: Swap) -> Result<()> {
         let key = serialize(&swap_id)?;
         let new_value = serialize(&state).context("Could not serialize new state value")?;
@@ -178,6 +386,21 @@ impl Database {
             .filter_ok(|(_swap_id, alice)| !matches!(alice, Alice::Done(_)))
             .collect()
     }
+
+    pub fn unfinished_bob(&self) -> Result<Vec<(Uuid, Bob)>> {
+        self.all_bob_iter()
+            .filter_ok(|(_swap_id, bob)| !matches!(bob, Bob::Done(_)))
+            .collect()
+    }
+}
+
+/// Keys the swaps with a peer by the peer id first, so they can be looked up
+/// by prefix. Peer ids are multihashes, hence no peer id is a prefix of
+/// another.
+fn peer_swap_key(peer_id: PeerId, swap_id: Uuid) -> Vec<u8> {
+    let mut key = peer_id.to_bytes();
+    key.extend_from_slice(swap_id.as_bytes());
+    key
 }
 
 pub fn serialize<T>(t: &T) -> Result<Vec<u8>>
@@ -199,6 +422,7 @@ mod tests {
     use super::*;
     use crate::database::alice::{Alice, AliceEndState};
     use crate::database::bob::{Bob, BobEndState};
+    use std::time::Duration;
 
     #[tokio::test]
     async fn can_write_and_read_to_multiple_keys() {
@@ -334,6 +558,181 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn xmr_reservations_are_summed_up_until_removed() -> Result<()> {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(db_dir.path()).unwrap();
+
+        let swap_id_1 = Uuid::new_v4();
+        let swap_id_2 = Uuid::new_v4();
+
+        db.insert_xmr_reservation(swap_id_1, monero::Amount::from_piconero(1_000))
+            .await?;
+        db.insert_xmr_reservation(swap_id_2, monero::Amount::from_piconero(2_000))
+            .await?;
+        assert_eq!(
+            db.total_xmr_reserved()?,
+            monero::Amount::from_piconero(3_000)
+        );
+
+        db.remove_xmr_reservation(swap_id_1).await?;
+        db.remove_xmr_reservation(swap_id_1).await?;
+        assert_eq!(
+            db.total_xmr_reserved()?,
+            monero::Amount::from_piconero(2_000)
+        );
+        assert_eq!(db.xmr_reservations()?, vec![(
+            swap_id_2,
+            monero::Amount::from_piconero(2_000)
+        )]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn peer_volume_only_counts_swaps_of_peer_in_time_window() -> Result<()> {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(db_dir.path()).unwrap();
+
+        let peer_id = PeerId::random();
+        let other_peer_id = PeerId::random();
+        let now = SystemTime::now();
+        let two_days_ago = now - Duration::from_secs(2 * 24 * 60 * 60);
+        let one_day_ago = now - Duration::from_secs(24 * 60 * 60);
+        let redeemed = Swap::Alice(Alice::Done(AliceEndState::BtcRedeemed));
+
+        let swap_id = Uuid::new_v4();
+        db.insert_peer_swap(swap_id, peer_id, bitcoin::Amount::from_sat(1_000), now)
+            .await?;
+        db.insert_latest_state(swap_id, redeemed.clone()).await?;
+
+        let swap_id = Uuid::new_v4();
+        db.insert_peer_swap(
+            swap_id,
+            peer_id,
+            bitcoin::Amount::from_sat(2_000),
+            two_days_ago,
+        )
+        .await?;
+        db.insert_latest_state(swap_id, redeemed.clone()).await?;
+
+        let swap_id = Uuid::new_v4();
+        db.insert_peer_swap(
+            swap_id,
+            other_peer_id,
+            bitcoin::Amount::from_sat(4_000),
+            now,
+        )
+        .await?;
+        db.insert_latest_state(swap_id, redeemed).await?;
+
+        let volume = db.peer_volume_since(peer_id, one_day_ago)?;
+
+        assert_eq!(volume, bitcoin::Amount::from_sat(1_000));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn peer_volume_only_counts_swaps_that_locked_btc() -> Result<()> {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(db_dir.path()).unwrap();
+
+        let peer_id = PeerId::random();
+        let now = SystemTime::now();
+        let one_day_ago = now - Duration::from_secs(24 * 60 * 60);
+
+        let refunded_swap_id = Uuid::new_v4();
+        db.insert_peer_swap(
+            refunded_swap_id,
+            peer_id,
+            bitcoin::Amount::from_sat(1_000),
+            now,
+        )
+        .await?;
+        db.insert_latest_state(
+            refunded_swap_id,
+            Swap::Alice(Alice::Done(AliceEndState::XmrRefunded)),
+        )
+        .await?;
+
+        let aborted_swap_id = Uuid::new_v4();
+        db.insert_peer_swap(
+            aborted_swap_id,
+            peer_id,
+            bitcoin::Amount::from_sat(2_000),
+            now,
+        )
+        .await?;
+        db.insert_latest_state(
+            aborted_swap_id,
+            Swap::Alice(Alice::Done(AliceEndState::SafelyAborted)),
+        )
+        .await?;
+
+        db.insert_peer_swap(
+            Uuid::new_v4(),
+            peer_id,
+            bitcoin::Amount::from_sat(4_000),
+            now,
+        )
+        .await?;
+
+        let volume = db.peer_volume_since(peer_id, one_day_ago)?;
+
+        assert_eq!(volume, bitcoin::Amount::from_sat(1_000));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn only_unfinished_swaps_with_peer_are_counted() -> Result<()> {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(db_dir.path()).unwrap();
+
+        let peer_id = PeerId::random();
+
+        let btc = bitcoin::Amount::from_sat(1_000);
+        let now = SystemTime::now();
+
+        let done_swap_id = Uuid::new_v4();
+        db.insert_peer_swap(done_swap_id, peer_id, btc, now).await?;
+        db.insert_latest_state(
+            done_swap_id,
+            Swap::Alice(Alice::Done(AliceEndState::BtcRedeemed)),
+        )
+        .await?;
+
+        let just_set_up_swap_id = Uuid::new_v4();
+        db.insert_peer_swap(just_set_up_swap_id, peer_id, btc, now)
+            .await?;
+
+        db.insert_peer_swap(Uuid::new_v4(), PeerId::random(), btc, now)
+            .await?;
+
+        assert_eq!(db.unfinished_swaps_with_peer(peer_id)?, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn banned_peers_can_be_unbanned() -> Result<()> {
+        let db_dir = tempfile::tempdir().unwrap();
+        let db = Database::open(db_dir.path()).unwrap();
+
+        let peer_id = PeerId::random();
+
+        db.ban_peer(peer_id, "protocol violation".to_owned())
+            .await?;
+        assert_eq!(db.banned_peers()?, vec![(
+            peer_id,
+            "protocol violation".to_owned()
+        )]);
+
+        db.unban_peer(peer_id).await?;
+        assert!(db.banned_peers()?.is_empty());
+        assert!(db.unban_peer(peer_id).await.is_err());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_reopen_db() -> Result<()> {
         let db_dir = tempfile::tempdir().unwrap();
@@ -10,6 +10,14 @@ pub enum Bob {
     Started {
         #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
         btc_amount: bitcoin::Amount,
+        #[serde(default)]
+        funding: crate::bitcoin::TxLockFunding,
+        /// Only set in states persisted before the funding of TxLock could
+        /// be chosen.
+        #[serde(default, skip_serializing)]
+        external_funding: Option<crate::bitcoin::ExternalFunding>,
+        #[serde(default)]
+        refund_address: Option<bitcoin::Address>,
     },
     ExecutionSetupDone {
         state2: bob::State2,
@@ -45,7 +53,16 @@ pub enum BobEndState {
 impl From<BobState> for Bob {
     fn from(bob_state: BobState) -> Self {
         match bob_state {
-            BobState::Started { btc_amount } => Bob::Started { btc_amount },
+            BobState::Started {
+                btc_amount,
+                funding,
+                refund_address,
+            } => Bob::Started {
+                btc_amount,
+                funding,
+                external_funding: None,
+                refund_address,
+            },
             BobState::ExecutionSetupDone(state2) => Bob::ExecutionSetupDone { state2 },
             BobState::BtcLocked(state3) => Bob::BtcLocked { state3 },
             BobState::XmrLockProofReceived {
@@ -77,7 +94,18 @@ impl From<BobState> for Bob {
 impl From<Bob> for BobState {
     fn from(db_state: Bob) -> Self {
         match db_state {
-            Bob::Started { btc_amount } => BobState::Started { btc_amount },
+            Bob::Started {
+                btc_amount,
+                funding,
+                external_funding,
+                refund_address,
+            } => BobState::Started {
+                btc_amount,
+                funding: external_funding
+                    .map(crate::bitcoin::TxLockFunding::External)
+                    .unwrap_or(funding),
+                refund_address,
+            },
             Bob::ExecutionSetupDone { state2 } => BobState::ExecutionSetupDone(state2),
             Bob::BtcLocked { state3 } => BobState::BtcLocked(state3),
             Bob::XmrLockProofReceived {
@@ -122,3 +150,67 @@ impl Display for Bob {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::{ExternalFunding, TxLockFunding};
+    use crate::database::{deserialize, serialize};
+
+    #[derive(Serialize)]
+    enum LegacyBob {
+        Started {
+            #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+            btc_amount: bitcoin::Amount,
+            external_funding: Option<ExternalFunding>,
+            refund_address: Option<bitcoin::Address>,
+        },
+    }
+
+    #[test]
+    fn started_state_with_internal_funding_from_before_funding_choice_can_be_read() {
+        let legacy = LegacyBob::Started {
+            btc_amount: bitcoin::Amount::from_sat(100_000),
+            external_funding: None,
+            refund_address: None,
+        };
+
+        let bob: Bob = deserialize(&serialize(&legacy).unwrap()).unwrap();
+
+        assert!(matches!(
+            BobState::from(bob),
+            BobState::Started { funding, .. } if funding == TxLockFunding::default()
+        ));
+    }
+
+    #[test]
+    fn started_state_with_external_funding_from_before_funding_choice_can_be_read() {
+        let external = ExternalFunding::p2wpkh(100_000);
+        let legacy = LegacyBob::Started {
+            btc_amount: bitcoin::Amount::from_sat(100_000),
+            external_funding: Some(external.clone()),
+            refund_address: None,
+        };
+
+        let bob: Bob = deserialize(&serialize(&legacy).unwrap()).unwrap();
+
+        assert!(matches!(
+            BobState::from(bob),
+            BobState::Started { funding, .. } if funding == TxLockFunding::External(external)
+        ));
+    }
+
+    #[test]
+    fn started_state_roundtrips() {
+        let started = Bob::Started {
+            btc_amount: bitcoin::Amount::from_sat(100_000),
+            funding: TxLockFunding::External(ExternalFunding::p2wpkh(100_000)),
+            external_funding: None,
+            refund_address: None,
+        };
+
+        let bob: Bob = deserialize(&serialize(&started).unwrap()).unwrap();
+
+        assert_eq!(bob, started);
+    }
+}
@@ -25,8 +25,10 @@ pub mod fs;
 pub mod kraken;
 pub mod monero;
 pub mod network;
+pub mod notification;
 pub mod protocol;
 pub mod seed;
 pub mod tor;
+pub mod watchtower;
 
 mod monero_ext;
@@ -1,8 +1,10 @@
+mod daemon;
 pub mod wallet;
 mod wallet_rpc;
 
 pub use ::monero::{Address, Network, PrivateKey, PublicKey};
 pub use curve25519_dalek::scalar::Scalar;
+pub use daemon::{select_daemon, DaemonSupervisor};
 pub use wallet::Wallet;
 pub use wallet_rpc::{WalletRpc, WalletRpcProcess};
 
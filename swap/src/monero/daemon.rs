@@ -0,0 +1,212 @@
+use crate::monero::{Wallet, WalletRpc, WalletRpcProcess};
+use ::monero::Network;
+use anyhow::{bail, Context, Result};
+use monero_rpc::monerod;
+use monero_rpc::monerod::MonerodRpc as _;
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+/// How many blocks a daemon may be behind the highest daemon we know of
+/// before we stop considering it.
+const TOLERATED_BLOCKS_BEHIND: u32 = 5;
+
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Picks the first daemon (in the given order of preference) that responds
+/// and is not lagging behind the other daemons.
+pub async fn select_daemon(hosts: &[String], network: Network) -> Result<String> {
+    let heights = block_heights(hosts, network).await;
+
+    match pick_daemon(&heights) {
+        Some(index) => Ok(hosts[index].clone()),
+        None => bail!("None of the Monero daemons {:?} are reachable", hosts),
+    }
+}
+
+/// Keeps the wallet RPC connected to a healthy Monero daemon.
+///
+/// The daemon currently in use is checked periodically. If it is down or
+/// lagging behind the other configured daemons, the wallet RPC is restarted
+/// against the next healthy daemon and the wallet is moved over to it.
+pub struct DaemonSupervisor {
+    hosts: Vec<String>,
+    current: String,
+    network: Network,
+    wallet_rpc: WalletRpc,
+    _process: WalletRpcProcess,
+    wallet: Arc<Wallet>,
+}
+
+impl DaemonSupervisor {
+    pub fn new(
+        hosts: Vec<String>,
+        current: String,
+        network: Network,
+        wallet_rpc: WalletRpc,
+        process: WalletRpcProcess,
+        wallet: Arc<Wallet>,
+    ) -> Self {
+        Self {
+            hosts,
+            current,
+            network,
+            wallet_rpc,
+            _process: process,
+            wallet,
+        }
+    }
+
+    pub async fn run(mut self) {
+        let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+        interval.tick().await; // the first tick completes immediately
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.ensure_healthy_daemon().await {
+                tracing::warn!("Failed to switch Monero daemon: {:#}", e);
+            }
+        }
+    }
+
+    async fn ensure_healthy_daemon(&mut self) -> Result<()> {
+        if self.hosts.len() < 2 {
+            return Ok(());
+        }
+
+        let heights = block_heights(&self.hosts, self.network).await;
+        let best = heights.iter().flatten().max().copied();
+
+        let current_height = self
+            .hosts
+            .iter()
+            .position(|host| host == &self.current)
+            .and_then(|index| heights[index]);
+
+        if is_acceptable(current_height, best) {
+            return Ok(());
+        }
+
+        let next = match pick_daemon(&heights) {
+            Some(index) => self.hosts[index].clone(),
+            None => bail!("None of the Monero daemons are reachable"),
+        };
+
+        tracing::info!(
+            "Monero daemon {} is down or lagging behind, switching to {}",
+            self.current,
+            next
+        );
+
+        let process = self
+            .wallet
+            .switch_rpc(self.wallet_rpc.run(self.network, next.as_str()))
+            .await?;
+
+        // Dropping the previous process kills it
+        self._process = process;
+        self.current = next;
+
+        Ok(())
+    }
+}
+
+async fn block_heights(hosts: &[String], network: Network) -> Vec<Option<u32>> {
+    let mut heights = Vec::with_capacity(hosts.len());
+
+    for host in hosts {
+        let height = match block_height(host, network).await {
+            Ok(height) => Some(height),
+            Err(e) => {
+                tracing::debug!("Monero daemon {} is not healthy: {:#}", host, e);
+                None
+            }
+        };
+
+        heights.push(height);
+    }
+
+    heights
+}
+
+async fn block_height(host: &str, network: Network) -> Result<u32> {
+    let client = monerod::Client::new(rpc_url(host, network)?)?;
+
+    let response = tokio::time::timeout(HEALTH_CHECK_TIMEOUT, client.get_block_count())
+        .await
+        .context("Monero daemon did not respond in time")??;
+
+    if response.status != "OK" {
+        bail!("Monero daemon reported status {}", response.status)
+    }
+
+    Ok(response.count)
+}
+
+fn rpc_url(host: &str, network: Network) -> Result<Url> {
+    let port = match network {
+        Network::Mainnet => 18081,
+        Network::Stagenet => 38081,
+        Network::Testnet => 28081,
+    };
+
+    Url::parse(&format!("http://{}:{}/json_rpc", host, port))
+        .with_context(|| format!("Invalid Monero daemon host {}", host))
+}
+
+fn pick_daemon(heights: &[Option<u32>]) -> Option<usize> {
+    let best = heights.iter().flatten().max().copied();
+
+    heights
+        .iter()
+        .position(|height| is_acceptable(*height, best))
+}
+
+fn is_acceptable(height: Option<u32>, best: Option<u32>) -> bool {
+    match (height, best) {
+        (Some(height), Some(best)) => best.saturating_sub(height) <= TOLERATED_BLOCKS_BEHIND,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_first_daemon_if_all_are_in_sync() {
+        let index = pick_daemon(&[Some(100), Some(100), Some(101)]);
+
+        assert_eq!(index, Some(0));
+    }
+
+    #[test]
+    fn skips_unreachable_daemon() {
+        let index = pick_daemon(&[None, Some(100)]);
+
+        assert_eq!(index, Some(1));
+    }
+
+    #[test]
+    fn skips_lagging_daemon() {
+        let index = pick_daemon(&[Some(100), Some(100 + TOLERATED_BLOCKS_BEHIND + 1)]);
+
+        assert_eq!(index, Some(1));
+    }
+
+    #[test]
+    fn tolerates_daemon_slightly_behind() {
+        let index = pick_daemon(&[Some(100), Some(100 + TOLERATED_BLOCKS_BEHIND)]);
+
+        assert_eq!(index, Some(0));
+    }
+
+    #[test]
+    fn no_daemon_if_all_are_unreachable() {
+        let index = pick_daemon(&[None, None]);
+
+        assert_eq!(index, None);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::env::Config;
 use crate::monero::{
     Amount, InsufficientFunds, PrivateViewKey, PublicViewKey, TransferProof, TxHash,
+    WalletRpcProcess,
 };
 use ::monero::{Address, Network, PrivateKey, PublicKey};
 use anyhow::{Context, Result};
@@ -25,8 +26,16 @@ pub struct Wallet {
 impl Wallet {
     /// Connect to a wallet RPC and load the given wallet by name.
     pub async fn open_or_create(url: Url, name: String, env_config: Config) -> Result<Self> {
-        let client = wallet::Client::new(url)?;
+        Self::open_or_create_with_client(wallet::Client::new(url)?, name, env_config).await
+    }
 
+    /// Load the given wallet by name using an already configured wallet RPC
+    /// client, e.g. one that authenticates against the wallet RPC.
+    pub async fn open_or_create_with_client(
+        client: wallet::Client,
+        name: String,
+        env_config: Config,
+    ) -> Result<Self> {
         let open_wallet_response = client.open_wallet(name.clone()).await;
         if open_wallet_response.is_err() {
             client.create_wallet(name.clone(), "English".to_owned()).await.context(
@@ -64,6 +73,41 @@ impl Wallet {
         Ok(())
     }
 
+    /// Move the wallet over to a freshly started wallet RPC.
+    ///
+    /// The wallet is closed on the current RPC so its state is saved and then
+    /// re-opened on the new one. The lock is held throughout so no other call
+    /// observes a half-switched wallet.
+    pub async fn switch_rpc(
+        &self,
+        new_rpc: impl Future<Output = Result<WalletRpcProcess>>,
+    ) -> Result<WalletRpcProcess> {
+        let mut client = self.inner.lock().await;
+
+        // The old wallet RPC might be the reason we are switching, so we don't insist
+        // on closing the wallet cleanly
+        if let Err(e) = client.close_wallet().await {
+            tracing::debug!("Failed to close wallet on previous wallet RPC: {:#}", e);
+        }
+
+        let process = match new_rpc.await {
+            Ok(process) => process,
+            Err(e) => {
+                let _ = client.open_wallet(self.name.clone()).await;
+                return Err(e);
+            }
+        };
+
+        let new_client = wallet::Client::new(process.endpoint())?;
+        new_client
+            .open_wallet(self.name.clone())
+            .await
+            .context("Failed to open wallet on new wallet RPC")?;
+        *client = new_client;
+
+        Ok(process)
+    }
+
     pub async fn open(&self, filename: String) -> Result<()> {
         self.inner.lock().await.open_wallet(filename).await?;
         Ok(())
@@ -109,8 +153,8 @@ impl Wallet {
     }
 
     /// Close the wallet and open (load) another wallet by generating it from
-    /// keys. The generated wallet will be opened, all funds sweeped to the
-    /// main_address and then the wallet will be re-loaded using the internally
+    /// keys. The generated wallet will be opened, all funds sweeped to
+    /// `sweep_to` and then the wallet will be re-loaded using the internally
     /// stored name.
     pub async fn create_from(
         &self,
@@ -118,6 +162,7 @@ impl Wallet {
         private_spend_key: PrivateKey,
         private_view_key: PrivateViewKey,
         restore_height: BlockHeight,
+        sweep_to: Address,
     ) -> Result<()> {
         let public_spend_key = PublicKey::from_private_key(&private_spend_key);
         let public_view_key = PublicKey::from_private_key(&private_view_key.into());
@@ -145,16 +190,16 @@ impl Wallet {
 
         // Try to send all the funds from the generated wallet to the default wallet
         match wallet.refresh().await {
-            Ok(_) => match wallet.sweep_all(self.main_address.to_string()).await {
+            Ok(_) => match wallet.sweep_all(sweep_to.to_string()).await {
                 Ok(sweep_all) => {
                     for tx in sweep_all.tx_hash_list {
-                        tracing::info!(%tx, "Monero transferred back to default wallet {}", self.main_address);
+                        tracing::info!(%tx, "Monero transferred back to default wallet {}", sweep_to);
                     }
                 }
                 Err(e) => {
                     tracing::warn!(
                         "Transferring Monero back to default wallet {} failed with {:#}",
-                        self.main_address,
+                        sweep_to,
                         e
                     );
                 }
@@ -259,6 +304,40 @@ impl Wallet {
         Ok(Amount::from_piconero(amount))
     }
 
+    /// Returns the subaddress of the primary account that is labelled with
+    /// `label`, creating it if there is none yet.
+    ///
+    /// Funds received on it count towards the balance of the primary account,
+    /// but can be told apart from deposits to the main address.
+    pub async fn labelled_subaddress(&self, label: String) -> Result<Address> {
+        let inner = self.inner.lock().await;
+
+        let existing = inner
+            .get_address(0)
+            .await
+            .context("Failed to get Monero subaddresses")?
+            .addresses
+            .into_iter()
+            .find(|subaddress| subaddress.label == label);
+
+        if let Some(subaddress) = existing {
+            return Ok(Address::from_str(&subaddress.address)?);
+        }
+
+        let created = inner
+            .create_address(0, label.clone())
+            .await
+            .context("Failed to create Monero subaddress")?;
+
+        tracing::debug!(
+            "Created Monero subaddress {} with label {}",
+            created.address_index,
+            label
+        );
+
+        Ok(Address::from_str(&created.address)?)
+    }
+
     pub async fn block_height(&self) -> Result<BlockHeight> {
         Ok(self.inner.lock().await.get_height().await?)
     }
@@ -1,18 +1,18 @@
 use ::monero::Network;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use big_bytes::BigByte;
-use futures::{StreamExt, TryStreamExt};
+use data_encoding::HEXLOWER;
+use futures::StreamExt;
 use monero_rpc::wallet::{Client, MoneroWalletRpc as _};
 use reqwest::header::CONTENT_LENGTH;
 use reqwest::Url;
+use sha2::{Digest, Sha256};
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use tokio::fs::{remove_file, OpenOptions};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::fs::{remove_file, rename, File, OpenOptions};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
-use tokio_util::codec::{BytesCodec, FramedRead};
-use tokio_util::io::StreamReader;
 
 #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
 compile_error!("unsupported operating system");
@@ -26,6 +26,23 @@ const DOWNLOAD_URL: &str = "https://downloads.getmonero.org/cli/monero-linux-x64
 #[cfg(target_os = "windows")]
 const DOWNLOAD_URL: &str = "https://downloads.getmonero.org/cli/monero-win-x64-v0.17.1.9.zip";
 
+// SHA256 hashes of the archives above. The GPG signature of the release's
+// `hashes.txt` is not verified at runtime, a hash must only be pinned here
+// after checking it against the signed `hashes.txt` of the v0.17.1.9 release.
+// Without a pinned hash the download is refused and `--monero-wallet-rpc-path`
+// has to be used.
+// TODO: Pin the macOS hash once it was checked against the signed hashes.txt
+#[cfg(target_os = "macos")]
+const DOWNLOAD_SHA256: Option<&str> = None;
+
+#[cfg(target_os = "linux")]
+const DOWNLOAD_SHA256: Option<&str> =
+    Some("0fb6f53b7b9b3b205151c652b6c9ca7e735f80bfe78427d1061f042f6228fb6e");
+
+// TODO: Pin the Windows hash once it was checked against the signed hashes.txt
+#[cfg(target_os = "windows")]
+const DOWNLOAD_SHA256: Option<&str> = None;
+
 #[cfg(any(target_os = "macos", target_os = "linux"))]
 const PACKED_FILE: &str = "monero-wallet-rpc";
 
@@ -36,6 +53,17 @@ const PACKED_FILE: &str = "monero-wallet-rpc.exe";
 #[error("monero wallet rpc executable not found in downloaded archive")]
 pub struct ExecutableNotFoundInArchive;
 
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("downloaded monero wallet rpc archive has SHA256 hash {actual}, expected {expected}")]
+pub struct ChecksumMismatch {
+    expected: &'static str,
+    actual: String,
+}
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("no SHA256 hash of the monero wallet rpc archive is pinned for this platform, install monero-wallet-rpc and pass --monero-wallet-rpc-path")]
+pub struct NoPinnedChecksum;
+
 pub struct WalletRpcProcess {
     _child: Child,
     port: u16,
@@ -50,9 +78,51 @@ impl WalletRpcProcess {
 
 pub struct WalletRpc {
     working_dir: PathBuf,
+    exec_path: PathBuf,
 }
 
 impl WalletRpc {
+    /// Use a monero-wallet-rpc executable that is already installed on the
+    /// system instead of downloading one.
+    pub async fn with_executable(
+        working_dir: impl AsRef<Path>,
+        exec_path: impl AsRef<Path>,
+    ) -> Result<WalletRpc> {
+        let working_dir = working_dir.as_ref();
+        let exec_path = exec_path.as_ref();
+
+        if !exec_path.is_file() {
+            bail!(
+                "monero-wallet-rpc executable {} does not exist",
+                exec_path.display()
+            )
+        }
+
+        // We cannot know which build the user installed, logging the hash at
+        // least allows to compare it against the release's `hashes.txt`
+        tracing::info!(
+            "Using monero-wallet-rpc {} with SHA256 hash {}",
+            exec_path.display(),
+            sha256_of_file(exec_path).await?
+        );
+
+        if !working_dir.exists() {
+            tokio::fs::create_dir(working_dir).await?;
+        }
+
+        Ok(WalletRpc {
+            working_dir: working_dir.to_path_buf(),
+            exec_path: exec_path.to_path_buf(),
+        })
+    }
+
+    /// Use the monero-wallet-rpc executable in the working directory,
+    /// downloading it first if it is not there yet.
+    ///
+    /// The hash of the executable is stored when it is unpacked from a verified
+    /// archive and checked on every start, a modified executable is replaced
+    /// by downloading the archive again. An executable unpacked by an earlier
+    /// version, which did not store the hash, is kept as is.
     pub async fn new(working_dir: impl AsRef<Path>) -> Result<WalletRpc> {
         let working_dir = working_dir.as_ref();
 
@@ -62,56 +132,52 @@ impl WalletRpc {
 
         let monero_wallet_rpc = WalletRpc {
             working_dir: working_dir.to_path_buf(),
+            exec_path: working_dir.join(PACKED_FILE),
         };
 
-        if monero_wallet_rpc.archive_path().exists() {
-            remove_file(monero_wallet_rpc.archive_path()).await?;
+        let exec_path = monero_wallet_rpc.exec_path();
+        let exec_hash_path = monero_wallet_rpc.exec_hash_path();
+
+        if exec_path.exists() {
+            let actual = sha256_of_file(&exec_path).await?;
+
+            match tokio::fs::read_to_string(&exec_hash_path).await {
+                Ok(expected) if expected.trim() == actual => return Ok(monero_wallet_rpc),
+                Ok(_) => tracing::warn!(
+                    "monero-wallet-rpc {} was modified, downloading it again",
+                    exec_path.display()
+                ),
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    tracing::info!(
+                        "Using monero-wallet-rpc {} with SHA256 hash {}",
+                        exec_path.display(),
+                        actual
+                    );
+                    tokio::fs::write(&exec_hash_path, actual).await?;
+
+                    return Ok(monero_wallet_rpc);
+                }
+                Err(e) => return Err(e.into()),
+            }
         }
 
-        if !monero_wallet_rpc.exec_path().exists() {
-            let mut options = OpenOptions::new();
-            let mut file = options
-                .read(true)
-                .write(true)
-                .create_new(true)
-                .open(monero_wallet_rpc.archive_path())
-                .await?;
-
-            let response = reqwest::get(DOWNLOAD_URL).await?;
-
-            let content_length = response.headers()[CONTENT_LENGTH]
-                .to_str()
-                .context("Failed to convert content-length to string")?
-                .parse::<u64>()?;
-
-            tracing::info!(
-                "Downloading monero-wallet-rpc ({})",
-                content_length.big_byte(2)
-            );
-
-            let byte_stream = response
-                .bytes_stream()
-                .map_err(|err| std::io::Error::new(ErrorKind::Other, err));
-
-            #[cfg(not(target_os = "windows"))]
-            let mut stream = FramedRead::new(
-                async_compression::tokio::bufread::BzDecoder::new(StreamReader::new(byte_stream)),
-                BytesCodec::new(),
-            )
-            .map_ok(|bytes| bytes.freeze());
-
-            #[cfg(target_os = "windows")]
-            let mut stream = FramedRead::new(StreamReader::new(byte_stream), BytesCodec::new())
-                .map_ok(|bytes| bytes.freeze());
+        let archive_path = monero_wallet_rpc.archive_path();
 
-            while let Some(chunk) = stream.next().await {
-                file.write(&chunk?).await?;
+        if archive_path.exists() {
+            if let Err(e) = verify_archive(&archive_path).await {
+                tracing::warn!("Discarding monero-wallet-rpc archive: {:#}", e);
+                remove_file(&archive_path).await?;
             }
+        }
 
-            file.flush().await?;
-
-            Self::extract_archive(&monero_wallet_rpc).await?;
+        if !archive_path.exists() {
+            download_archive(&archive_path).await?;
         }
+
+        Self::extract_archive(&monero_wallet_rpc).await?;
+        remove_file(&archive_path).await?;
+        tokio::fs::write(&exec_hash_path, sha256_of_file(&exec_path).await?).await?;
+
         Ok(monero_wallet_rpc)
     }
 
@@ -178,12 +244,16 @@ impl WalletRpc {
     }
 
     fn exec_path(&self) -> PathBuf {
-        self.working_dir.join(PACKED_FILE)
+        self.exec_path.clone()
+    }
+
+    fn exec_hash_path(&self) -> PathBuf {
+        self.working_dir.join(format!("{}.sha256", PACKED_FILE))
     }
 
     #[cfg(not(target_os = "windows"))]
     async fn extract_archive(monero_wallet_rpc: &Self) -> Result<()> {
-        use anyhow::bail;
+        use async_compression::tokio::bufread::BzDecoder;
         use tokio_tar::Archive;
 
         let mut options = OpenOptions::new();
@@ -192,7 +262,7 @@ impl WalletRpc {
             .open(monero_wallet_rpc.archive_path())
             .await?;
 
-        let mut ar = Archive::new(file);
+        let mut ar = Archive::new(BzDecoder::new(BufReader::new(file)));
         let mut entries = ar.entries()?;
 
         loop {
@@ -212,8 +282,6 @@ impl WalletRpc {
             }
         }
 
-        remove_file(monero_wallet_rpc.archive_path()).await?;
-
         Ok(())
     }
 
@@ -243,8 +311,88 @@ impl WalletRpc {
         });
         extract.await??;
 
-        remove_file(monero_wallet_rpc.archive_path()).await?;
-
         Ok(())
     }
 }
+
+/// Downloads the archive to `archive_path`, it is only moved there once its
+/// hash matches the pinned one.
+async fn download_archive(archive_path: &Path) -> Result<()> {
+    if DOWNLOAD_SHA256.is_none() {
+        bail!(NoPinnedChecksum)
+    }
+
+    let download_path = archive_path.with_extension("part");
+
+    if download_path.exists() {
+        remove_file(&download_path).await?;
+    }
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&download_path)
+        .await?;
+
+    let response = reqwest::get(DOWNLOAD_URL).await?;
+
+    let content_length = response.headers()[CONTENT_LENGTH]
+        .to_str()
+        .context("Failed to convert content-length to string")?
+        .parse::<u64>()?;
+
+    tracing::info!(
+        "Downloading monero-wallet-rpc ({})",
+        content_length.big_byte(2)
+    );
+
+    let mut hasher = Sha256::new();
+    let mut stream = response.bytes_stream();
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+    }
+
+    file.flush().await?;
+
+    if let Err(e) = ensure_pinned_hash(HEXLOWER.encode(&hasher.finalize())) {
+        remove_file(&download_path).await?;
+        return Err(e);
+    }
+
+    rename(&download_path, archive_path).await?;
+
+    Ok(())
+}
+
+async fn verify_archive(archive_path: &Path) -> Result<()> {
+    ensure_pinned_hash(sha256_of_file(archive_path).await?)
+}
+
+async fn sha256_of_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(HEXLOWER.encode(&hasher.finalize()))
+}
+
+fn ensure_pinned_hash(actual: String) -> Result<()> {
+    let expected = DOWNLOAD_SHA256.ok_or(NoPinnedChecksum)?;
+
+    if actual != expected {
+        bail!(ChecksumMismatch { expected, actual })
+    }
+
+    Ok(())
+}
@@ -10,3 +10,4 @@ pub mod swarm;
 pub mod tor_transport;
 pub mod transfer_proof;
 pub mod transport;
+pub mod watchtower;
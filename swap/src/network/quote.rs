@@ -8,31 +8,78 @@ use libp2p::request_response::{
 };
 use libp2p::PeerId;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use uuid::Uuid;
 
-const PROTOCOL: &str = "/comit/xmr/btc/bid-quote/1.0.0";
-type OutEvent = RequestResponseEvent<(), BidQuote>;
-type Message = RequestResponseMessage<(), BidQuote>;
+const PROTOCOL: &str = "/comit/xmr/btc/bid-quote/1.1.0";
+/// The previous version of the protocol, its quotes don't advertise a minimum
+/// quantity. Alice keeps serving it so older versions of Bob can still request
+/// quotes.
+const PROTOCOL_V1_0: &str = "/comit/xmr/btc/bid-quote/1.0.0";
+type OutEvent = RequestResponseEvent<(), Response>;
+type Message = RequestResponseMessage<(), Response>;
 
-pub type Behaviour = RequestResponse<JsonPullCodec<BidQuoteProtocol, BidQuote>>;
+pub type Behaviour = RequestResponse<JsonPullCodec<BidQuoteProtocol, Response>>;
 
-#[derive(Debug, Clone, Copy, Default)]
-pub struct BidQuoteProtocol;
+#[derive(Debug, Clone, Copy)]
+pub enum BidQuoteProtocol {
+    V1_0,
+    V1_1,
+}
 
 impl ProtocolName for BidQuoteProtocol {
     fn protocol_name(&self) -> &[u8] {
-        PROTOCOL.as_bytes()
+        match self {
+            BidQuoteProtocol::V1_0 => PROTOCOL_V1_0.as_bytes(),
+            BidQuoteProtocol::V1_1 => PROTOCOL.as_bytes(),
+        }
     }
 }
 
 /// Represents a quote for buying XMR.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BidQuote {
     /// The price at which the maker is willing to buy at.
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     pub price: bitcoin::Amount,
+    /// The minimum quantity the maker is willing to buy.
+    #[serde(default, with = "::bitcoin::util::amount::serde::as_sat")]
+    pub min_quantity: bitcoin::Amount,
     /// The maximum quantity the maker is willing to buy.
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     pub max_quantity: bitcoin::Amount,
+    /// Identifies the quote when requesting a spot price, in order to get the
+    /// price of this quote. Older makers don't hand out ids and give their
+    /// current price instead.
+    #[serde(default)]
+    pub id: Option<Uuid>,
+    /// For how long the maker honours the price of this quote.
+    #[serde(default)]
+    pub valid_for: Option<Duration>,
+}
+
+/// The answer to a quote request.
+///
+/// The variants are untagged so that a quote is serialized as before and
+/// older versions of Bob can still read it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Response {
+    Quote(BidQuote),
+    Rejected { rejected: Error },
+}
+
+/// Why the maker refused to hand out a quote.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, thiserror::Error)]
+#[serde(rename_all = "snake_case")]
+pub enum Error {
+    #[error("Too many requests, try again in a minute")]
+    TooManyRequests,
+    #[error("The maker does not have an up-to-date exchange rate, try again later")]
+    RateUnavailable,
+    /// The maker failed to produce a quote for a reason it does not disclose.
+    #[error("The maker failed to produce a quote, try again later")]
+    Other,
 }
 
 /// Constructs a new instance of the `quote` behaviour to be used by Alice.
@@ -41,18 +88,26 @@ pub struct BidQuote {
 pub fn alice() -> Behaviour {
     Behaviour::new(
         JsonPullCodec::default(),
-        vec![(BidQuoteProtocol, ProtocolSupport::Inbound)],
+        vec![
+            (BidQuoteProtocol::V1_1, ProtocolSupport::Inbound),
+            (BidQuoteProtocol::V1_0, ProtocolSupport::Inbound),
+        ],
         RequestResponseConfig::default(),
     )
 }
 
 /// Constructs a new instance of the `quote` behaviour to be used by Bob.
 ///
-/// Bob only supports outbound connections, i.e. requesting quotes.
+/// Bob only supports outbound connections, i.e. requesting quotes. He prefers
+/// the current version but falls back to the previous one to request quotes
+/// from older versions of Alice.
 pub fn bob() -> Behaviour {
     Behaviour::new(
         JsonPullCodec::default(),
-        vec![(BidQuoteProtocol, ProtocolSupport::Outbound)],
+        vec![
+            (BidQuoteProtocol::V1_1, ProtocolSupport::Outbound),
+            (BidQuoteProtocol::V1_0, ProtocolSupport::Outbound),
+        ],
         RequestResponseConfig::default(),
     )
 }
@@ -82,3 +137,70 @@ impl From<(PeerId, Message)> for bob::OutEvent {
     }
 }
 crate::impl_from_rr_event!(OutEvent, bob::OutEvent, PROTOCOL);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_response_is_serialized_as_plain_quote() {
+        let quote = BidQuote {
+            price: bitcoin::Amount::from_sat(1_000),
+            min_quantity: bitcoin::Amount::from_sat(10),
+            max_quantity: bitcoin::Amount::from_sat(100),
+            id: Some(Uuid::nil()),
+            valid_for: Some(Duration::from_secs(60)),
+        };
+
+        let serialized = serde_json::to_value(Response::Quote(quote.clone())).unwrap();
+
+        assert_eq!(serialized, serde_json::to_value(&quote).unwrap());
+        assert_eq!(
+            serde_json::from_value::<Response>(serialized).unwrap(),
+            Response::Quote(quote)
+        );
+    }
+
+    #[test]
+    fn quote_without_id_can_be_deserialized() {
+        let quote = serde_json::from_str::<BidQuote>(
+            r#"{"price":1000,"min_quantity":10,"max_quantity":100}"#,
+        )
+        .unwrap();
+
+        assert_eq!(quote.id, None);
+        assert_eq!(quote.valid_for, None);
+    }
+
+    #[test]
+    fn rejection_roundtrips() {
+        let serialized = serde_json::to_string(&Response::Rejected {
+            rejected: Error::TooManyRequests,
+        })
+        .unwrap();
+
+        assert_eq!(serialized, r#"{"rejected":"too_many_requests"}"#);
+        assert_eq!(
+            serde_json::from_str::<Response>(&serialized).unwrap(),
+            Response::Rejected {
+                rejected: Error::TooManyRequests
+            }
+        );
+    }
+
+    #[test]
+    fn rate_unavailable_rejection_roundtrips() {
+        let serialized = serde_json::to_string(&Response::Rejected {
+            rejected: Error::RateUnavailable,
+        })
+        .unwrap();
+
+        assert_eq!(serialized, r#"{"rejected":"rate_unavailable"}"#);
+        assert_eq!(
+            serde_json::from_str::<Response>(&serialized).unwrap(),
+            Response::Rejected {
+                rejected: Error::RateUnavailable
+            }
+        );
+    }
+}
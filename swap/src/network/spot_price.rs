@@ -1,19 +1,28 @@
-use crate::network::cbor_request_response::CborCodec;
+use crate::network::cbor_request_response::BUF_SIZE;
 use crate::protocol::{alice, bob};
 use crate::{bitcoin, monero};
-use libp2p::core::ProtocolName;
+use async_trait::async_trait;
+use futures::prelude::*;
+use libp2p::core::{upgrade, ProtocolName};
 use libp2p::request_response::{
-    ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
-    RequestResponseMessage,
+    ProtocolSupport, RequestResponse, RequestResponseCodec, RequestResponseConfig,
+    RequestResponseEvent, RequestResponseMessage,
 };
 use libp2p::PeerId;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::io;
+use uuid::Uuid;
 
-const PROTOCOL: &str = "/comit/xmr/btc/spot-price/1.0.0";
+const PROTOCOL: &str = "/comit/xmr/btc/spot-price/2.0.0";
+/// The previous version of the protocol, it neither references quotes nor
+/// tells Bob why Alice refused to give a spot price. Alice keeps serving it and
+/// Bob keeps requesting it so that older versions can still swap.
+const PROTOCOL_V1_0: &str = "/comit/xmr/btc/spot-price/1.0.0";
 type OutEvent = RequestResponseEvent<Request, Response>;
 type Message = RequestResponseMessage<Request, Response>;
 
-pub type Behaviour = RequestResponse<CborCodec<SpotPriceProtocol, Request, Response>>;
+pub type Behaviour = RequestResponse<Codec>;
 
 /// The spot price protocol allows parties to **initiate** a trade by requesting
 /// a spot price.
@@ -24,24 +33,246 @@ pub type Behaviour = RequestResponse<CborCodec<SpotPriceProtocol, Request, Respo
 ///
 /// If a party wishes to only inquire about the current price, they should use
 /// the `quote` protocol instead.
-#[derive(Debug, Clone, Copy, Default)]
-pub struct SpotPriceProtocol;
+#[derive(Debug, Clone, Copy)]
+pub enum SpotPriceProtocol {
+    V1_0,
+    V2_0,
+}
 
 impl ProtocolName for SpotPriceProtocol {
     fn protocol_name(&self) -> &[u8] {
-        PROTOCOL.as_bytes()
+        match self {
+            SpotPriceProtocol::V1_0 => PROTOCOL_V1_0.as_bytes(),
+            SpotPriceProtocol::V2_0 => PROTOCOL.as_bytes(),
+        }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Request {
     #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     pub btc: bitcoin::Amount,
+    /// The quote whose price is requested. Without a quote the current price
+    /// is given.
+    pub quote_id: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Response {
+    Xmr(monero::Amount),
+    Error(Error),
+}
+
+/// The reasons for Alice to refuse giving a spot price.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, thiserror::Error)]
+pub enum Error {
+    #[error("Amount {buy} exceeds the maximum amount of {max}")]
+    MaxBuyAmountExceeded {
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        max: bitcoin::Amount,
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        buy: bitcoin::Amount,
+    },
+    #[error("Amount {buy} is below the minimum amount of {min}")]
+    MinBuyAmountNotReached {
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        min: bitcoin::Amount,
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        buy: bitcoin::Amount,
+    },
+    #[error("Alice does not allow more than {max} unfinished swaps per peer")]
+    TooManyConcurrentSwaps { max: usize },
+    #[error("Amount {buy} exceeds the remaining amount of {remaining} Alice buys from a single peer within 24 hours")]
+    DailyVolumeExceeded {
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        remaining: bitcoin::Amount,
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        buy: bitcoin::Amount,
+    },
+    #[error("Alice does not have enough Monero to buy {buy}")]
+    BalanceTooLow {
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        buy: bitcoin::Amount,
+    },
+    #[error("Alice does not have an exchange rate")]
+    RateUnavailable,
+    /// Alice's latest exchange rate is too old to be used, it is likely to be
+    /// refreshed shortly.
+    #[error("Alice's exchange rate is outdated")]
+    RateStale,
+    /// Alice does not give spot prices at the moment, e.g. because she is
+    /// under maintenance.
+    #[error("Alice does not give spot prices at the moment")]
+    Maintenance,
+    #[error("The quote expired")]
+    QuoteExpired,
+    #[error("Too many requests")]
+    TooManyRequests,
+    /// Alice failed to produce a spot price for a reason she does not
+    /// disclose.
+    #[error("Alice failed to produce a spot price")]
+    Other,
+}
+
+impl Error {
+    /// What Bob can do to get a spot price nevertheless.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            Error::MaxBuyAmountExceeded { .. } => "Swap a smaller amount",
+            Error::MinBuyAmountNotReached { .. } => "Swap a larger amount",
+            Error::TooManyConcurrentSwaps { .. } => {
+                "Wait until one of your swaps with this seller has finished"
+            }
+            Error::DailyVolumeExceeded { .. } => "Swap a smaller amount or try again tomorrow",
+            Error::BalanceTooLow { .. } => "Swap a smaller amount or swap with another seller",
+            Error::RateUnavailable | Error::Other => "Try again later",
+            Error::RateStale => "Try again in a few minutes",
+            Error::Maintenance => "Try again later or swap with another seller",
+            Error::QuoteExpired => "Restart the swap to accept a new quote",
+            Error::TooManyRequests => "Wait a minute before trying again",
+        }
+    }
+}
+
+/// The messages of version 1.0.0 of the protocol.
+mod v1 {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct Request {
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        pub btc: bitcoin::Amount,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct Response {
+        pub xmr: monero::Amount,
+    }
+}
+
+/// Encodes the messages in CBOR, translating them to and from the 1.0.0
+/// messages if that version was negotiated.
+///
+/// A request of version 1.0.0 does not reference a quote, hence Alice gives
+/// her current price. A refusal cannot be expressed in version 1.0.0, hence
+/// Alice closes the substream instead, like she did before 2.0.0.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Codec;
+
+#[async_trait]
+impl RequestResponseCodec for Codec {
+    type Protocol = SpotPriceProtocol;
+    type Request = Request;
+    type Response = Response;
+
+    async fn read_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let message = read_message(io).await?;
+
+        match protocol {
+            SpotPriceProtocol::V1_0 => {
+                let v1::Request { btc } = deserialize(&message)?;
+
+                Ok(Request {
+                    btc,
+                    quote_id: None,
+                })
+            }
+            SpotPriceProtocol::V2_0 => deserialize(&message),
+        }
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let message = read_message(io).await?;
+
+        match protocol {
+            SpotPriceProtocol::V1_0 => {
+                let v1::Response { xmr } = deserialize(&message)?;
+
+                Ok(Response::Xmr(xmr))
+            }
+            SpotPriceProtocol::V2_0 => deserialize(&message),
+        }
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+        req: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let bytes = match protocol {
+            SpotPriceProtocol::V1_0 => serialize(&v1::Request { btc: req.btc })?,
+            SpotPriceProtocol::V2_0 => serialize(&req)?,
+        };
+
+        upgrade::write_one(io, &bytes).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+        res: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let bytes = match (protocol, res) {
+            (SpotPriceProtocol::V1_0, Response::Xmr(xmr)) => serialize(&v1::Response { xmr })?,
+            (SpotPriceProtocol::V1_0, Response::Error(error)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("{} cannot convey the error: {}", PROTOCOL_V1_0, error),
+                ))
+            }
+            (SpotPriceProtocol::V2_0, res) => serialize(&res)?,
+        };
+
+        upgrade::write_one(io, &bytes).await
+    }
+}
+
+async fn read_message<T>(io: &mut T) -> io::Result<Vec<u8>>
+where
+    T: AsyncRead + Unpin + Send,
+{
+    upgrade::read_one(io, BUF_SIZE)
+        .await
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn deserialize<M>(message: &[u8]) -> io::Result<M>
+where
+    M: DeserializeOwned,
+{
+    serde_cbor::from_slice(message).map_err(|e| {
+        tracing::debug!("serde read error: {:?}", e);
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    })
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Response {
-    pub xmr: monero::Amount,
+fn serialize<M>(message: &M) -> io::Result<Vec<u8>>
+where
+    M: Serialize,
+{
+    serde_cbor::to_vec(message).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
 }
 
 /// Constructs a new instance of the `spot-price` behaviour to be used by Alice.
@@ -50,8 +281,11 @@ pub struct Response {
 /// in XMR.
 pub fn alice() -> Behaviour {
     Behaviour::new(
-        CborCodec::default(),
-        vec![(SpotPriceProtocol, ProtocolSupport::Inbound)],
+        Codec::default(),
+        vec![
+            (SpotPriceProtocol::V2_0, ProtocolSupport::Inbound),
+            (SpotPriceProtocol::V1_0, ProtocolSupport::Inbound),
+        ],
         RequestResponseConfig::default(),
     )
 }
@@ -59,11 +293,15 @@ pub fn alice() -> Behaviour {
 /// Constructs a new instance of the `spot-price` behaviour to be used by Bob.
 ///
 /// Bob only supports outbound connections, i.e. requesting a spot price for a
-/// given amount of BTC in XMR.
+/// given amount of BTC in XMR. He prefers the current version but falls back
+/// to the previous one to swap with older versions of Alice.
 pub fn bob() -> Behaviour {
     Behaviour::new(
-        CborCodec::default(),
-        vec![(SpotPriceProtocol, ProtocolSupport::Outbound)],
+        Codec::default(),
+        vec![
+            (SpotPriceProtocol::V2_0, ProtocolSupport::Outbound),
+            (SpotPriceProtocol::V1_0, ProtocolSupport::Outbound),
+        ],
         RequestResponseConfig::default(),
     )
 }
@@ -99,3 +337,87 @@ impl From<(PeerId, Message)> for bob::OutEvent {
     }
 }
 crate::impl_from_rr_event!(OutEvent, bob::OutEvent, PROTOCOL);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::io::Cursor;
+
+    #[tokio::test]
+    async fn request_of_version_1_0_has_no_quote() {
+        let mut io = Cursor::new(Vec::new());
+        Codec
+            .write_request(&SpotPriceProtocol::V1_0, &mut io, Request {
+                btc: bitcoin::Amount::from_sat(10_000),
+                quote_id: Some(Uuid::new_v4()),
+            })
+            .await
+            .unwrap();
+        io.set_position(0);
+
+        let request = Codec
+            .read_request(&SpotPriceProtocol::V1_0, &mut io)
+            .await
+            .unwrap();
+
+        assert_eq!(request, Request {
+            btc: bitcoin::Amount::from_sat(10_000),
+            quote_id: None
+        });
+    }
+
+    #[tokio::test]
+    async fn response_of_version_1_0_is_the_plain_amount() {
+        let xmr = monero::Amount::from_piconero(1_000);
+        let mut io = Cursor::new(Vec::new());
+        Codec
+            .write_response(&SpotPriceProtocol::V1_0, &mut io, Response::Xmr(xmr))
+            .await
+            .unwrap();
+        io.set_position(0);
+
+        let message = read_message(&mut io).await.unwrap();
+
+        assert_eq!(
+            deserialize::<v1::Response>(&message).unwrap(),
+            v1::Response { xmr }
+        );
+    }
+
+    #[tokio::test]
+    async fn errors_cannot_be_sent_via_version_1_0() {
+        for error in vec![Error::TooManyRequests, Error::RateStale, Error::Maintenance] {
+            let mut io = Cursor::new(Vec::new());
+
+            let result = Codec
+                .write_response(&SpotPriceProtocol::V1_0, &mut io, Response::Error(error))
+                .await;
+
+            assert!(result.is_err());
+            assert!(io.into_inner().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn stale_rate_and_maintenance_roundtrip_via_version_2_0() {
+        for error in vec![Error::RateUnavailable, Error::RateStale, Error::Maintenance] {
+            let mut io = Cursor::new(Vec::new());
+            Codec
+                .write_response(
+                    &SpotPriceProtocol::V2_0,
+                    &mut io,
+                    Response::Error(error.clone()),
+                )
+                .await
+                .unwrap();
+            io.set_position(0);
+
+            let response = Codec
+                .read_response(&SpotPriceProtocol::V2_0, &mut io)
+                .await
+                .unwrap();
+
+            assert_eq!(response, Response::Error(error));
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::network::transport;
+use crate::network::{transport, watchtower};
 use crate::protocol::{alice, bob};
 use crate::seed::Seed;
 use crate::tor;
@@ -6,8 +6,8 @@ use anyhow::Result;
 use libp2p::swarm::{NetworkBehaviour, SwarmBuilder};
 use libp2p::{PeerId, Swarm};
 
-pub fn alice(seed: &Seed) -> Result<Swarm<alice::Behaviour>> {
-    with_clear_net(seed, alice::Behaviour::default())
+pub fn alice(seed: &Seed, rate_limiter: alice::RateLimiter) -> Result<Swarm<alice::Behaviour>> {
+    with_clear_net(seed, alice::Behaviour::new(rate_limiter))
 }
 
 pub async fn bob(
@@ -22,6 +22,21 @@ pub async fn bob(
     with_clear_net(seed, bob::Behaviour::new(alice))
 }
 
+pub fn watchtower(seed: &Seed) -> Result<Swarm<watchtower::Behaviour>> {
+    with_clear_net(seed, watchtower::watchtower())
+}
+
+pub async fn watchtower_client(
+    seed: &Seed,
+    tor_socks5_port: u16,
+) -> Result<Swarm<watchtower::Behaviour>> {
+    let client = tor::Client::new(tor_socks5_port);
+    if client.assert_tor_running().await.is_ok() {
+        return with_tor(seed, watchtower::bob(), tor_socks5_port).await;
+    }
+    with_clear_net(seed, watchtower::bob())
+}
+
 fn with_clear_net<B>(seed: &Seed, behaviour: B) -> Result<Swarm<B>>
 where
     B: NetworkBehaviour,
@@ -0,0 +1,246 @@
+//! Protocol for handing a watch job to a watchtower.
+//!
+//! A watch job contains Bob's fully signed cancel transaction, which the
+//! watchtower publishes once the cancel timelock expired, hence the swap is
+//! cancelled even if Bob is offline at that time. Once the cancel transaction
+//! is final, Bob replaces the job with one that also contains the signed
+//! refund transaction, which the watchtower publishes as well. Only the peer
+//! that submitted a job can replace or revoke it.
+//!
+//! The refund transaction reveals Bob's Monero key share `s_b` to Alice. Bob
+//! only hands it out once the cancel transaction spent the lock transaction,
+//! otherwise Alice could learn `s_b` from the watchtower, redeem the Bitcoin
+//! and take back her Monero.
+
+use crate::bitcoin::wallet::Watchable;
+use crate::bitcoin::{CancelTimelock, PunishTimelock, Transaction, Txid};
+use crate::network::cbor_request_response::CborCodec;
+use ::bitcoin::Script;
+use anyhow::{bail, Result};
+use libp2p::core::ProtocolName;
+use libp2p::request_response::{
+    ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
+    RequestResponseMessage,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const PROTOCOL: &str = "/comit/xmr/btc/watchtower/1.0.0";
+pub type OutEvent = RequestResponseEvent<Request, Response>;
+pub type Message = RequestResponseMessage<Request, Response>;
+
+pub type Behaviour = RequestResponse<CborCodec<WatchtowerProtocol, Request, Response>>;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WatchtowerProtocol;
+
+impl ProtocolName for WatchtowerProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        PROTOCOL.as_bytes()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Request {
+    /// Watch the swap, replaces an earlier job for the same swap.
+    Submit(Box<WatchJob>),
+    /// Stop watching the swap, e.g. because it finished.
+    Revoke { swap_id: Uuid },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Response {
+    Accepted,
+    Rejected { reason: String },
+}
+
+/// Everything needed to cancel Bob's swap and, once the cancel transaction is
+/// final, to refund his Bitcoin.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WatchJob {
+    pub swap_id: Uuid,
+    pub tx_lock: WatchedTx,
+    pub cancel_timelock: CancelTimelock,
+    pub punish_timelock: PunishTimelock,
+    pub tx_cancel: Transaction,
+    /// Only handed out once the cancel transaction is final.
+    pub tx_refund: Option<Transaction>,
+}
+
+/// A transaction identified by its id and the script of one of its outputs,
+/// so its status can be looked up without knowing the whole transaction.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WatchedTx {
+    pub txid: Txid,
+    pub script: Script,
+}
+
+impl WatchedTx {
+    pub fn new(tx: &impl Watchable) -> Self {
+        Self {
+            txid: tx.id(),
+            script: tx.script(),
+        }
+    }
+}
+
+impl Watchable for WatchedTx {
+    fn id(&self) -> Txid {
+        self.txid
+    }
+
+    fn script(&self) -> Script {
+        self.script.clone()
+    }
+}
+
+impl WatchJob {
+    pub fn tx_cancel(&self) -> WatchedTx {
+        WatchedTx {
+            txid: self.tx_cancel.txid(),
+            script: self.tx_cancel.output[0].script_pubkey.clone(),
+        }
+    }
+
+    pub fn tx_refund(&self) -> Option<WatchedTx> {
+        self.tx_refund.as_ref().map(|tx_refund| WatchedTx {
+            txid: tx_refund.txid(),
+            script: tx_refund.output[0].script_pubkey.clone(),
+        })
+    }
+
+    /// Checks that the transactions spend each other, the signatures can only
+    /// be checked by the network.
+    pub fn validate(&self) -> Result<()> {
+        if self.tx_cancel.input.len() != 1 || self.tx_cancel.output.is_empty() {
+            bail!("Cancel transaction must have exactly one input and outputs")
+        }
+
+        let cancel_input = &self.tx_cancel.input[0];
+
+        if cancel_input.previous_output.txid != self.tx_lock.txid {
+            bail!("Cancel transaction does not spend the lock transaction")
+        }
+        if cancel_input.sequence != u32::from(self.cancel_timelock) {
+            bail!("Cancel transaction is not timelocked by the cancel timelock")
+        }
+
+        if let Some(tx_refund) = &self.tx_refund {
+            if tx_refund.input.len() != 1 || tx_refund.output.is_empty() {
+                bail!("Refund transaction must have exactly one input and outputs")
+            }
+            if tx_refund.input[0].previous_output.txid != self.tx_cancel.txid() {
+                bail!("Refund transaction does not spend the cancel transaction")
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Constructs the behaviour of the watchtower, which only accepts jobs.
+pub fn watchtower() -> Behaviour {
+    Behaviour::new(
+        CborCodec::default(),
+        vec![(WatchtowerProtocol, ProtocolSupport::Inbound)],
+        RequestResponseConfig::default(),
+    )
+}
+
+/// Constructs the behaviour of Bob, who only submits and revokes jobs.
+pub fn bob() -> Behaviour {
+    Behaviour::new(
+        CborCodec::default(),
+        vec![(WatchtowerProtocol, ProtocolSupport::Outbound)],
+        RequestResponseConfig::default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::bitcoin::{OutPoint, TxIn, TxOut};
+
+    fn transaction(previous_output: OutPoint, sequence: u32) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output,
+                script_sig: Default::default(),
+                sequence,
+                witness: Vec::new(),
+            }],
+            output: vec![TxOut {
+                value: 10_000,
+                script_pubkey: Default::default(),
+            }],
+        }
+    }
+
+    fn job() -> WatchJob {
+        let tx_lock = transaction(OutPoint::default(), 0xFFFF_FFFF);
+        let tx_cancel = transaction(OutPoint::new(tx_lock.txid(), 0), 12);
+        let tx_refund = transaction(OutPoint::new(tx_cancel.txid(), 0), 0xFFFF_FFFF);
+
+        WatchJob {
+            swap_id: Uuid::new_v4(),
+            tx_lock: WatchedTx {
+                txid: tx_lock.txid(),
+                script: Default::default(),
+            },
+            cancel_timelock: CancelTimelock::new(12),
+            punish_timelock: PunishTimelock::new(6),
+            tx_cancel,
+            tx_refund: Some(tx_refund),
+        }
+    }
+
+    #[test]
+    fn accepts_consistent_job() {
+        assert!(job().validate().is_ok());
+    }
+
+    #[test]
+    fn accepts_job_without_refund() {
+        let job = WatchJob {
+            tx_refund: None,
+            ..job()
+        };
+
+        assert!(job.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_cancel_not_spending_lock() {
+        let mut job = job();
+        job.tx_lock.txid = job.tx_cancel.txid();
+
+        assert!(job.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_cancel_without_timelock() {
+        let mut job = job();
+        job.cancel_timelock = CancelTimelock::new(1);
+
+        assert!(job.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_refund_not_spending_cancel() {
+        let mut job = job();
+        job.tx_refund.as_mut().unwrap().input[0].previous_output = OutPoint::default();
+
+        assert!(job.validate().is_err());
+    }
+
+    #[test]
+    fn request_roundtrips_as_cbor() {
+        let request = Request::Submit(Box::new(job()));
+
+        let bytes = serde_cbor::to_vec(&request).unwrap();
+
+        assert_eq!(serde_cbor::from_slice::<Request>(&bytes).unwrap(), request);
+    }
+}
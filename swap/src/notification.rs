@@ -0,0 +1,295 @@
+//! Notifications that require the attention of the operator.
+//!
+//! A notification is serialized as JSON and delivered to every configured
+//! hook: It is `POST`ed to the webhook and passed to the command in the
+//! `SWAP_NOTIFICATION` environment variable. The command is run via `sh -c`.
+
+use crate::{bitcoin, monero};
+use anyhow::{bail, Context, Result};
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use url::Url;
+use uuid::Uuid;
+
+const NOTIFICATION_ENV_VAR: &str = "SWAP_NOTIFICATION";
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where to deliver notifications to.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hooks {
+    pub webhook_url: Option<Url>,
+    pub command: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Notification {
+    /// The XMR balance fell below the threshold, the ASB needs to be refilled.
+    LowXmrBalance {
+        balance: monero::Amount,
+        threshold: monero::Amount,
+    },
+    /// The BTC balance exceeds the threshold.
+    HighBtcBalance {
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        balance: bitcoin::Amount,
+        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        threshold: bitcoin::Amount,
+    },
+    /// The swap was started or resumed.
+    SwapStarted(SwapUpdate),
+    /// The swap transitioned into a new state.
+    SwapStateChanged(SwapUpdate),
+    /// The swap transitioned into a state caused by an expired timelock, i.e.
+    /// it is cancelled, refunded or punished.
+    SwapTimelockExpired(SwapUpdate),
+    /// The swap reached a final state.
+    SwapFinished(SwapUpdate),
+    /// Running the swap failed, it has to be resumed.
+    SwapFailed {
+        #[serde(flatten)]
+        update: SwapUpdate,
+        error: String,
+    },
+    /// The urgency of an unfinished swap increased because a timelock
+    /// expires soon or expired.
+    SwapDeadline {
+        #[serde(flatten)]
+        update: SwapUpdate,
+        urgency: Urgency,
+        /// Blocks left until the next timelock expires.
+        blocks_left: Option<u32>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Alice,
+    Bob,
+}
+
+/// How urgently an unfinished swap has to be attended to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Urgency {
+    /// The next deadline is far away.
+    None,
+    /// The cancel timelock expires soon.
+    Warning,
+    /// The cancel timelock expired, the swap has to be resumed now.
+    Critical,
+    /// The punish timelock expired, Bob can no longer refund safely.
+    Expired,
+}
+
+impl fmt::Display for Urgency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Urgency::None => write!(f, "none"),
+            Urgency::Warning => write!(f, "warning"),
+            Urgency::Critical => write!(f, "critical"),
+            Urgency::Expired => write!(f, "expired"),
+        }
+    }
+}
+
+/// The state of a swap and the ids of the transactions known in this state.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SwapUpdate {
+    pub swap_id: Uuid,
+    pub role: Role,
+    pub state: String,
+    /// Transaction ids by transaction, e.g. `btc_lock` or `xmr_lock`.
+    pub txids: BTreeMap<String, String>,
+}
+
+/// Delivers notifications to the configured [`Hooks`], does nothing by
+/// default.
+///
+/// Clones share the urgency of the deadlines notified so far.
+#[derive(Clone, Debug, Default)]
+pub struct Notifier {
+    hooks: Hooks,
+    client: reqwest::Client,
+    notified_urgencies: Arc<Mutex<HashMap<Uuid, Urgency>>>,
+}
+
+impl Notifier {
+    pub fn new(hooks: Hooks) -> Self {
+        Self {
+            hooks,
+            client: reqwest::Client::new(),
+            notified_urgencies: Default::default(),
+        }
+    }
+
+    /// Delivers a [`Notification::SwapDeadline`] if the urgency of the swap
+    /// increased since the last one, so a deadline watched by both the running
+    /// swap and the watchdog is only notified once.
+    pub async fn notify_deadline(
+        &self,
+        update: SwapUpdate,
+        urgency: Urgency,
+        blocks_left: Option<u32>,
+    ) {
+        if !self.escalated(update.swap_id, urgency) {
+            return;
+        }
+
+        self.notify(Notification::SwapDeadline {
+            update,
+            urgency,
+            blocks_left,
+        })
+        .await
+    }
+
+    fn escalated(&self, swap_id: Uuid, urgency: Urgency) -> bool {
+        let mut notified_urgencies = self
+            .notified_urgencies
+            .lock()
+            .expect("notified urgencies lock not to be poisoned");
+        let previous = notified_urgencies
+            .insert(swap_id, urgency)
+            .unwrap_or(Urgency::None);
+
+        urgency > previous
+    }
+
+    /// Delivers `notification` to all hooks.
+    ///
+    /// Failing hooks are only logged, the caller should carry on regardless.
+    pub async fn notify(&self, notification: Notification) {
+        if self.hooks.webhook_url.is_none() && self.hooks.command.is_none() {
+            return;
+        }
+
+        let body = match serde_json::to_string(&notification) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::warn!("Failed to serialize notification: {:#}", e);
+                return;
+            }
+        };
+
+        if let Some(url) = &self.hooks.webhook_url {
+            if let Err(e) = self.call_webhook(url.clone(), body.clone()).await {
+                tracing::warn!(%url, "Failed to deliver notification to webhook: {:#}", e);
+            }
+        }
+
+        if let Some(command) = &self.hooks.command {
+            if let Err(e) = run_command(command, &body).await {
+                tracing::warn!(%command, "Failed to deliver notification to command: {:#}", e);
+            }
+        }
+    }
+
+    /// Like [`Notifier::notify`] but does not wait for the hooks, so a slow
+    /// webhook cannot delay the caller.
+    pub fn notify_in_background(&self, notification: Notification) {
+        let notifier = self.clone();
+        tokio::spawn(async move { notifier.notify(notification).await });
+    }
+
+    async fn call_webhook(&self, url: Url, body: String) -> Result<()> {
+        self.client
+            .post(url)
+            .timeout(WEBHOOK_TIMEOUT)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+async fn run_command(command: &str, body: &str) -> Result<()> {
+    let status = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env(NOTIFICATION_ENV_VAR, body)
+        .status()
+        .await
+        .context("Failed to run command")?;
+
+    if !status.success() {
+        bail!("Command exited with {}", status)
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notification_is_tagged_with_event() {
+        let notification = Notification::HighBtcBalance {
+            balance: bitcoin::Amount::from_sat(20_000_000),
+            threshold: bitcoin::Amount::from_sat(10_000_000),
+        };
+
+        assert_eq!(
+            serde_json::to_string(&notification).unwrap(),
+            r#"{"event":"high_btc_balance","balance":20000000,"threshold":10000000}"#
+        );
+    }
+
+    #[test]
+    fn swap_failed_contains_update() {
+        let swap_id = Uuid::new_v4();
+        let mut txids = BTreeMap::new();
+        txids.insert("btc_lock".to_string(), "abcd".to_string());
+
+        let notification = Notification::SwapFailed {
+            update: SwapUpdate {
+                swap_id,
+                role: Role::Bob,
+                state: "btc is locked".to_string(),
+                txids,
+            },
+            error: "connection refused".to_string(),
+        };
+
+        assert_eq!(
+            serde_json::to_string(&notification).unwrap(),
+            format!(
+                r#"{{"event":"swap_failed","swap_id":"{}","role":"bob","state":"btc is locked","txids":{{"btc_lock":"abcd"}},"error":"connection refused"}}"#,
+                swap_id
+            )
+        );
+    }
+
+    #[test]
+    fn notifies_deadline_only_on_escalation() {
+        let notifier = Notifier::default();
+        let swap_id = Uuid::new_v4();
+
+        assert!(!notifier.escalated(swap_id, Urgency::None));
+        assert!(notifier.escalated(swap_id, Urgency::Warning));
+        assert!(!notifier.clone().escalated(swap_id, Urgency::Warning));
+        assert!(notifier.escalated(swap_id, Urgency::Critical));
+        assert!(!notifier.escalated(swap_id, Urgency::Critical));
+        assert!(notifier.escalated(Uuid::new_v4(), Urgency::Critical));
+    }
+
+    #[tokio::test]
+    async fn command_receives_notification() {
+        let body = r#"{"event":"test"}"#;
+
+        run_command(r#"test "$SWAP_NOTIFICATION" = '{"event":"test"}'"#, body)
+            .await
+            .unwrap();
+        assert!(run_command("exit 1", body).await.is_err());
+    }
+}
@@ -2,18 +2,23 @@
 //! Alice holds XMR and wishes receive BTC.
 use crate::database::Database;
 use crate::env::Config;
+use crate::notification::Notifier;
 use crate::{bitcoin, monero};
 use std::sync::Arc;
 use uuid::Uuid;
 
 pub use self::behaviour::{Behaviour, OutEvent};
-pub use self::event_loop::{EventLoop, EventLoopHandle};
+pub use self::event_loop::{EventLoop, EventLoopHandle, PeerLimits};
+pub use self::rate_limit::RateLimiter;
+pub use self::reputation::Violation;
 pub use self::state::*;
 pub use self::swap::{run, run_until};
 
 mod behaviour;
 pub mod event_loop;
 mod execution_setup;
+pub mod rate_limit;
+mod reputation;
 pub mod state;
 pub mod swap;
 
@@ -25,4 +30,5 @@ pub struct Swap {
     pub env_config: Config,
     pub swap_id: Uuid,
     pub db: Arc<Database>,
+    pub notifier: Notifier,
 }
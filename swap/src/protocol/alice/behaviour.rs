@@ -1,6 +1,5 @@
-use crate::network::quote::BidQuote;
 use crate::network::{encrypted_signature, quote, spot_price, transfer_proof};
-use crate::protocol::alice::{execution_setup, State3};
+use crate::protocol::alice::{execution_setup, RateLimiter, State3};
 use anyhow::{anyhow, Error};
 use libp2p::request_response::{RequestId, ResponseChannel};
 use libp2p::{NetworkBehaviour, PeerId};
@@ -14,7 +13,7 @@ pub enum OutEvent {
         peer: PeerId,
     },
     QuoteRequested {
-        channel: ResponseChannel<BidQuote>,
+        channel: ResponseChannel<quote::Response>,
         peer: PeerId,
     },
     ExecutionSetupDone {
@@ -22,6 +21,10 @@ pub enum OutEvent {
         swap_id: Uuid,
         state3: Box<State3>,
     },
+    ExecutionSetupFailed {
+        peer: PeerId,
+        error: Error,
+    },
     TransferProofAcknowledged {
         peer: PeerId,
         id: RequestId,
@@ -35,6 +38,11 @@ pub enum OutEvent {
         peer: PeerId,
         error: Error,
     },
+    /// The peer did something that is not allowed by the protocol.
+    ProtocolViolation {
+        peer: PeerId,
+        error: Error,
+    },
     /// "Fallback" variant that allows the event mapping code to swallow certain
     /// events that we don't want the caller to deal with.
     Other,
@@ -42,14 +50,14 @@ pub enum OutEvent {
 
 impl OutEvent {
     pub fn unexpected_request(peer: PeerId) -> OutEvent {
-        OutEvent::Failure {
+        OutEvent::ProtocolViolation {
             peer,
             error: anyhow!("Unexpected request received"),
         }
     }
 
     pub fn unexpected_response(peer: PeerId) -> OutEvent {
-        OutEvent::Failure {
+        OutEvent::ProtocolViolation {
             peer,
             error: anyhow!("Unexpected response received"),
         }
@@ -66,16 +74,27 @@ pub struct Behaviour {
     pub execution_setup: execution_setup::Behaviour,
     pub transfer_proof: transfer_proof::Behaviour,
     pub encrypted_signature: encrypted_signature::Behaviour,
+
+    /// Limits the requests of the protocols that are costly for us to serve.
+    #[behaviour(ignore)]
+    pub rate_limiter: RateLimiter,
 }
 
-impl Default for Behaviour {
-    fn default() -> Self {
+impl Behaviour {
+    pub fn new(rate_limiter: RateLimiter) -> Self {
         Self {
             quote: quote::alice(),
             spot_price: spot_price::alice(),
             execution_setup: Default::default(),
             transfer_proof: transfer_proof::alice(),
             encrypted_signature: encrypted_signature::alice(),
+            rate_limiter,
         }
     }
 }
+
+impl Default for Behaviour {
+    fn default() -> Self {
+        Self::new(RateLimiter::default())
+    }
+}
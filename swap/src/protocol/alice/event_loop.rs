@@ -1,9 +1,15 @@
-use crate::asb::Rate;
+use crate::asb::pricing::{PriceHistory, StaleRate, VOLATILITY_WINDOW};
+use crate::asb::recorder::{Price, Recorder};
+use crate::asb::{Pricing, Rate};
 use crate::database::Database;
 use crate::env::Config;
 use crate::monero::BalanceTooLow;
 use crate::network::quote::BidQuote;
-use crate::network::{spot_price, transfer_proof};
+use crate::network::{quote, spot_price, transfer_proof};
+use crate::notification::Notifier;
+use crate::protocol::alice::execution_setup::Misbehaviour;
+use crate::protocol::alice::rate_limit::{self, RateLimitExceeded};
+use crate::protocol::alice::reputation::{Reputation, Violation};
 use crate::protocol::alice::{AliceState, Behaviour, OutEvent, State0, State3, Swap};
 use crate::{bitcoin, kraken, monero};
 use anyhow::{bail, Context, Result};
@@ -15,12 +21,26 @@ use libp2p::swarm::SwarmEvent;
 use libp2p::{PeerId, Swarm};
 use rand::rngs::OsRng;
 use rust_decimal::Decimal;
+use std::cmp::min;
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
+/// How long XMR promised in a spot price stay reserved while waiting for the
+/// execution setup with the peer to complete.
+const PENDING_XMR_RESERVATION_TIMEOUT: Duration = Duration::from_secs(120);
+
+const ONE_DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// For how long we honour the price of a quote.
+const QUOTE_VALIDITY: Duration = Duration::from_secs(60);
+
+/// How often the number of served and rate limited requests is logged.
+const RATE_LIMIT_STATS_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
 /// A future that resolves to a tuple of `PeerId`, `transfer_proof::Request` and
 /// `Responder`.
 ///
@@ -39,10 +59,29 @@ pub struct EventLoop<RS> {
     monero_wallet: Arc<monero::Wallet>,
     db: Arc<Database>,
     latest_rate: RS,
+    min_buy: bitcoin::Amount,
     max_buy: bitcoin::Amount,
+    peer_limits: PeerLimits,
+    /// Records the quotes and spot prices we give to peers, if enabled.
+    recorder: Option<Recorder>,
+    /// Handed to every swap to notify about its progress.
+    notifier: Notifier,
 
     swap_sender: mpsc::Sender<Swap>,
 
+    /// XMR promised to peers in a spot price whose execution setup has not
+    /// completed yet. Once a swap is set up, its reservation is tracked in the
+    /// database until the XMR are locked or the swap is aborted.
+    pending_xmr_reservations: HashMap<PeerId, (monero::Amount, Instant)>,
+
+    /// Quotes we handed out and still honour.
+    quotes: HashMap<Uuid, IssuedQuote>,
+
+    reputation: Reputation,
+    /// Violations reported by running swaps.
+    report_violation_sender: mpsc::UnboundedSender<(PeerId, Violation)>,
+    report_violation_receiver: mpsc::UnboundedReceiver<(PeerId, Violation)>,
+
     /// Stores incoming [`EncryptedSignature`]s per swap.
     recv_encrypted_signature: HashMap<Uuid, bmrng::RequestSender<bitcoin::EncryptedSignature, ()>>,
     inflight_encrypted_signatures: FuturesUnordered<BoxFuture<'static, ResponseChannel<()>>>,
@@ -62,6 +101,7 @@ impl<LR> EventLoop<LR>
 where
     LR: LatestRate,
 {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         swarm: Swarm<Behaviour>,
         env_config: Config,
@@ -69,9 +109,13 @@ where
         monero_wallet: Arc<monero::Wallet>,
         db: Arc<Database>,
         latest_rate: LR,
+        min_buy: bitcoin::Amount,
         max_buy: bitcoin::Amount,
+        peer_limits: PeerLimits,
+        recorder: Option<Recorder>,
     ) -> Result<(Self, mpsc::Receiver<Swap>)> {
         let swap_channel = MpscChannels::default();
+        let (report_violation_sender, report_violation_receiver) = mpsc::unbounded_channel();
 
         let event_loop = EventLoop {
             swarm,
@@ -81,7 +125,16 @@ where
             db,
             latest_rate,
             swap_sender: swap_channel.sender,
+            min_buy,
             max_buy,
+            peer_limits,
+            recorder,
+            notifier: Notifier::default(),
+            pending_xmr_reservations: Default::default(),
+            quotes: Default::default(),
+            reputation: Reputation::default(),
+            report_violation_sender,
+            report_violation_receiver,
             recv_encrypted_signature: Default::default(),
             inflight_encrypted_signatures: Default::default(),
             send_transfer_proof: Default::default(),
@@ -91,6 +144,11 @@ where
         Ok((event_loop, swap_channel.receiver))
     }
 
+    /// Notifies about the progress of the swaps via `notifier`.
+    pub fn with_notifier(self, notifier: Notifier) -> Self {
+        Self { notifier, ..self }
+    }
+
     pub fn peer_id(&self) -> PeerId {
         *Swarm::local_peer_id(&self.swarm)
     }
@@ -102,6 +160,18 @@ where
         self.inflight_encrypted_signatures
             .push(future::pending().boxed());
 
+        match self.db.banned_peers() {
+            Ok(banned_peers) => {
+                for (peer, _) in banned_peers {
+                    Swarm::ban_peer_id(&mut self.swarm, peer);
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to load banned peers: {:#}", e);
+                return;
+            }
+        }
+
         let unfinished_swaps = match self.db.unfinished_alice() {
             Ok(unfinished_swaps) => unfinished_swaps,
             Err(_) => {
@@ -129,6 +199,7 @@ where
                 db: self.db.clone(),
                 state: state.into(),
                 swap_id,
+                notifier: self.notifier.clone(),
             };
 
             match self.swap_sender.send(swap).await {
@@ -139,21 +210,43 @@ where
             }
         }
 
+        let mut rate_limit_stats = tokio::time::interval(RATE_LIMIT_STATS_INTERVAL);
+        rate_limit_stats.tick().await; // the first tick completes immediately
+
         loop {
             tokio::select! {
                 swarm_event = self.swarm.next_event() => {
                     match swarm_event {
                         SwarmEvent::Behaviour(OutEvent::SpotPriceRequested { request, channel, peer }) => {
+                            // A spot price is followed by an execution setup, hence both have to be within budget
+                            if let Err(e) = self.check_rate_limit(peer, &[rate_limit::Protocol::SpotPrice, rate_limit::Protocol::ExecutionSetup]) {
+                                tracing::debug!(%peer, "Rejecting spot price request: {}", e);
+
+                                let response = spot_price::Response::Error(spot_price::Error::TooManyRequests);
+                                self.record_served_price(peer, Price::SpotPrice { request, response: response.clone() });
+                                let _ = self.swarm.behaviour_mut().spot_price.send_response(channel, response);
+                                continue;
+                            }
+
+                            let started = Instant::now();
                             let btc = request.btc;
-                            let xmr = match self.handle_spot_price_request(btc, self.monero_wallet.clone()).await {
+                            let xmr = match self.handle_spot_price_request(peer, btc, request.quote_id, self.monero_wallet.clone()).await {
                                 Ok(xmr) => xmr,
                                 Err(e) => {
                                     tracing::warn!(%peer, "Failed to produce spot price for {}: {:#}", btc, e);
+
+                                    let response = spot_price::Response::Error(spot_price_error(&e, btc));
+                                    self.record_served_price(peer, Price::SpotPrice { request, response: response.clone() });
+                                    if self.swarm.behaviour_mut().spot_price.send_response(channel, response).is_err() {
+                                        tracing::debug!(%peer, "Failed to respond with spot price error");
+                                    }
                                     continue;
                                 }
                             };
 
-                            match self.swarm.behaviour_mut().spot_price.send_response(channel, spot_price::Response { xmr }) {
+                            self.record_served_price(peer, Price::SpotPrice { request, response: spot_price::Response::Xmr(xmr) });
+
+                            match self.swarm.behaviour_mut().spot_price.send_response(channel, spot_price::Response::Xmr(xmr)) {
                                 Ok(_) => {},
                                 Err(_) => {
                                     // if we can't respond, the peer probably just disconnected so it is not a huge deal, only log this on debug
@@ -170,24 +263,54 @@ where
                                 }
                             };
 
+                            tracing::debug!(%peer, elapsed = ?started.elapsed(), "Served spot price request");
+
                             self.swarm.behaviour_mut().execution_setup.run(peer, state0);
                         }
                         SwarmEvent::Behaviour(OutEvent::QuoteRequested { channel, peer }) => {
-                            let quote = match self.make_quote(self.max_buy).await {
+                            if let Err(e) = self.check_rate_limit(peer, &[rate_limit::Protocol::Quote]) {
+                                tracing::debug!(%peer, "Rejecting quote request: {}", e);
+
+                                let response = quote::Response::Rejected { rejected: quote::Error::TooManyRequests };
+                                let _ = self.swarm.behaviour_mut().quote.send_response(channel, response);
+                                continue;
+                            }
+
+                            let started = Instant::now();
+                            let quote = match self.make_quote(peer, self.min_buy, self.max_buy).await {
                                 Ok(quote) => quote,
                                 Err(e) => {
                                     tracing::warn!(%peer, "Failed to make quote: {:#}", e);
+
+                                    let response = quote::Response::Rejected { rejected: quote_error(&e) };
+                                    if self.swarm.behaviour_mut().quote.send_response(channel, response).is_err() {
+                                        tracing::debug!(%peer, "Failed to respond with quote rejection");
+                                    }
                                     continue;
                                 }
                             };
 
-                            if self.swarm.behaviour_mut().quote.send_response(channel, quote).is_err() {
+                            self.record_served_price(peer, Price::Quote(quote.clone()));
+
+                            if self.swarm.behaviour_mut().quote.send_response(channel, quote::Response::Quote(quote)).is_err() {
                                 tracing::debug!(%peer, "Failed to respond with quote");
+                                continue;
                             }
+
+                            tracing::debug!(%peer, elapsed = ?started.elapsed(), "Served quote request");
                         }
                         SwarmEvent::Behaviour(OutEvent::ExecutionSetupDone{bob_peer_id, swap_id, state3}) => {
                             let _ = self.handle_execution_setup_done(bob_peer_id, swap_id, *state3).await;
                         }
+                        SwarmEvent::Behaviour(OutEvent::ExecutionSetupFailed { peer, error }) => {
+                            tracing::warn!(%peer, "Execution setup failed: {:#}", error);
+
+                            self.pending_xmr_reservations.remove(&peer);
+
+                            if error.downcast_ref::<Misbehaviour>().is_some() {
+                                self.record_violation(peer, Violation::AbandonedExecutionSetup).await;
+                            }
+                        }
                         SwarmEvent::Behaviour(OutEvent::TransferProofAcknowledged { peer, id }) => {
                             tracing::debug!(%peer, "Bob acknowledged transfer proof");
                             if let Some(responder) = self.inflight_transfer_proofs.remove(&id) {
@@ -213,6 +336,7 @@ where
                                     "Ignoring malicious encrypted signature from {}, expected to receive it from {}",
                                     peer,
                                     swap_peer);
+                                self.record_violation(peer, Violation::ProtocolViolation).await;
                                 continue;
                             }
 
@@ -241,6 +365,13 @@ where
                         }
                         SwarmEvent::Behaviour(OutEvent::Failure {peer, error}) => {
                             tracing::error!(%peer, "Communication error: {:#}", error);
+
+                            self.pending_xmr_reservations.remove(&peer);
+                        }
+                        SwarmEvent::Behaviour(OutEvent::ProtocolViolation { peer, error }) => {
+                            tracing::warn!(%peer, "Protocol violation: {:#}", error);
+
+                            self.record_violation(peer, Violation::ProtocolViolation).await;
                         }
                         SwarmEvent::ConnectionEstablished { peer_id: peer, endpoint, .. } => {
                             tracing::debug!(%peer, address = %endpoint.get_remote_address(), "New connection established");
@@ -296,50 +427,170 @@ where
                 Some(response_channel) = self.inflight_encrypted_signatures.next() => {
                     let _ = self.swarm.behaviour_mut().encrypted_signature.send_response(response_channel, ());
                 }
+                Some((peer, violation)) = self.report_violation_receiver.recv() => {
+                    self.record_violation(peer, violation).await;
+                }
+                _ = rate_limit_stats.tick() => {
+                    self.log_rate_limit_stats();
+                }
+            }
+        }
+    }
+
+    fn check_rate_limit(
+        &mut self,
+        peer: PeerId,
+        protocols: &[rate_limit::Protocol],
+    ) -> Result<(), RateLimitExceeded> {
+        self.swarm
+            .behaviour_mut()
+            .rate_limiter
+            .check_all(peer, protocols)
+    }
+
+    fn record_served_price(&self, peer: PeerId, price: Price) {
+        if let Some(recorder) = self.recorder.as_ref() {
+            recorder.record_served_price(peer, price);
+        }
+    }
+
+    fn log_rate_limit_stats(&mut self) {
+        for (protocol, stats) in self.swarm.behaviour_mut().rate_limiter.take_stats() {
+            if stats.rejected > 0 {
+                tracing::warn!(
+                    served = stats.served,
+                    rejected = stats.rejected,
+                    "Rate limited {} requests in the last {} minutes",
+                    protocol,
+                    RATE_LIMIT_STATS_INTERVAL.as_secs() / 60
+                );
+            } else {
+                tracing::info!(
+                    served = stats.served,
+                    "Served {} requests in the last {} minutes",
+                    protocol,
+                    RATE_LIMIT_STATS_INTERVAL.as_secs() / 60
+                );
             }
         }
     }
 
+    /// Records a violation of `peer` and bans the peer once it committed too
+    /// many of them.
+    async fn record_violation(&mut self, peer: PeerId, violation: Violation) {
+        if !self.reputation.record(peer, violation) {
+            return;
+        }
+
+        tracing::warn!(%peer, "Banning peer after {}", violation);
+
+        if let Err(e) = self.db.ban_peer(peer, violation.to_string()).await {
+            tracing::error!(%peer, "Failed to persist ban of peer: {:#}", e);
+        }
+
+        Swarm::ban_peer_id(&mut self.swarm, peer);
+    }
+
     async fn handle_spot_price_request(
         &mut self,
+        peer: PeerId,
         btc: bitcoin::Amount,
+        quote_id: Option<Uuid>,
         monero_wallet: Arc<monero::Wallet>,
     ) -> Result<monero::Amount> {
-        let rate = self
-            .latest_rate
-            .latest_rate()
-            .context("Failed to get latest rate")?;
-
-        if btc > self.max_buy {
-            bail!(MaximumBuyAmountExceeded {
-                actual: btc,
-                max: self.max_buy
-            })
-        }
+        let rate = match quote_id {
+            Some(quote_id) => self.quoted_rate(peer, quote_id)?,
+            None => self.latest_rate.latest_rate().context(RateUnavailable)?,
+        };
 
-        let xmr_balance = monero_wallet.get_balance().await?;
-        let xmr_lock_fees = monero_wallet.static_tx_fee_estimate();
-        let xmr = rate.sell_quote(btc)?;
+        let xmr_balance = monero_wallet
+            .get_balance()
+            .await
+            .context(MoneroWalletUnavailable)?;
+
+        // A new spot price supersedes any previous one given to the same peer
+        self.pending_xmr_reservations.remove(&peer);
+
+        let context = SpotPriceContext {
+            min_buy: self.min_buy,
+            max_buy: self.max_buy,
+            peer_limits: self.peer_limits,
+            unfinished_swaps_with_peer: self.db.unfinished_swaps_with_peer(peer)?,
+            peer_daily_volume: self
+                .db
+                .peer_volume_since(peer, SystemTime::now() - ONE_DAY)?,
+            xmr_available: available_xmr(xmr_balance, self.xmr_reserved()?),
+            xmr_lock_fee: monero_wallet.static_tx_fee_estimate(),
+        };
+        let xmr = decide_spot_price(rate, btc, &context)?;
 
-        if xmr_balance < xmr + xmr_lock_fees {
-            bail!(BalanceTooLow {
-                balance: xmr_balance
-            })
-        }
+        self.pending_xmr_reservations
+            .insert(peer, (xmr + context.xmr_lock_fee, Instant::now()));
 
         Ok(xmr)
     }
 
-    async fn make_quote(&mut self, max_buy: bitcoin::Amount) -> Result<BidQuote> {
-        let rate = self
-            .latest_rate
-            .latest_rate()
-            .context("Failed to get latest rate")?;
+    /// The XMR reserved for swaps that are set up but have not locked their
+    /// XMR yet, plus those promised in recent spot prices.
+    fn xmr_reserved(&mut self) -> Result<monero::Amount> {
+        self.pending_xmr_reservations
+            .retain(|_, (_, reserved_at)| reserved_at.elapsed() < PENDING_XMR_RESERVATION_TIMEOUT);
+
+        let pending = self
+            .pending_xmr_reservations
+            .values()
+            .fold(monero::Amount::ZERO, |total, (amount, _)| total + *amount);
+
+        Ok(self.db.total_xmr_reserved()? + pending)
+    }
+
+    async fn make_quote(
+        &mut self,
+        peer: PeerId,
+        min_buy: bitcoin::Amount,
+        max_buy: bitcoin::Amount,
+    ) -> Result<BidQuote> {
+        let rate = self.latest_rate.latest_rate().context(RateUnavailable)?;
+
+        // Don't advertise more than we can deliver with the XMR left
+        let xmr_balance = self.monero_wallet.get_balance().await?;
+        let xmr_deliverable = available_xmr(
+            xmr_balance,
+            self.xmr_reserved()? + self.monero_wallet.static_tx_fee_estimate(),
+        );
+        let max_quantity = max_quantity(min_buy, max_buy, rate.max_quote(xmr_deliverable)?);
+        if max_quantity == bitcoin::Amount::ZERO {
+            tracing::warn!(%peer, %min_buy, "Quoting no liquidity, the Monero balance does not cover the minimum buy amount");
+        }
 
-        Ok(BidQuote {
+        let id = Uuid::new_v4();
+        let quote = BidQuote {
             price: rate.ask().context("Failed to compute asking price")?,
-            max_quantity: max_buy,
-        })
+            min_quantity: min_buy,
+            max_quantity,
+            id: Some(id),
+            valid_for: Some(QUOTE_VALIDITY),
+        };
+
+        let now = Instant::now();
+        self.quotes.retain(|_, quote| quote.expires_at > now);
+        self.quotes.insert(id, IssuedQuote {
+            peer,
+            rate,
+            expires_at: now + QUOTE_VALIDITY,
+        });
+
+        Ok(quote)
+    }
+
+    /// The rate of a quote we handed out to `peer` that has not expired yet.
+    fn quoted_rate(&self, peer: PeerId, quote_id: Uuid) -> Result<Rate> {
+        match self.quotes.get(&quote_id) {
+            Some(quote) if quote.peer == peer && quote.expires_at > Instant::now() => {
+                Ok(quote.rate)
+            }
+            _ => bail!(QuoteExpired { quote_id }),
+        }
     }
 
     async fn handle_execution_setup_done(
@@ -348,6 +599,29 @@ where
         swap_id: Uuid,
         state3: State3,
     ) {
+        if let Err(error) = self
+            .db
+            .insert_peer_swap(
+                swap_id,
+                bob_peer_id,
+                state3.tx_lock.lock_amount(),
+                SystemTime::now(),
+            )
+            .await
+        {
+            tracing::warn!(%swap_id, "Unable to record swap with peer, swap cannot be spawned: {:#}", error);
+            return;
+        }
+
+        // The reservation is released by the swap once the XMR are locked
+        let xmr_reserved =
+            state3.lock_xmr_transfer_request().amount + self.monero_wallet.static_tx_fee_estimate();
+        self.pending_xmr_reservations.remove(&bob_peer_id);
+        if let Err(error) = self.db.insert_xmr_reservation(swap_id, xmr_reserved).await {
+            tracing::warn!(%swap_id, "Unable to reserve XMR, swap cannot be spawned: {:#}", error);
+            return;
+        }
+
         let handle = self.new_handle(bob_peer_id, swap_id);
 
         let initial_state = AliceState::Started {
@@ -362,6 +636,7 @@ where
             db: self.db.clone(),
             state: initial_state,
             swap_id,
+            notifier: self.notifier.clone(),
         };
 
         // TODO: Consider adding separate components for start/resume of swaps
@@ -406,12 +681,32 @@ where
         );
 
         EventLoopHandle {
+            peer,
             recv_encrypted_signature: Some(encrypted_signature.1),
             send_transfer_proof: Some(transfer_proof_sender),
+            report_violation: self.report_violation_sender.clone(),
         }
     }
 }
 
+/// A quote handed out to `peer` whose price we honour until it expires.
+#[derive(Debug, Clone, Copy)]
+struct IssuedQuote {
+    peer: PeerId,
+    rate: Rate,
+    expires_at: Instant,
+}
+
+/// Limits on how much a single peer may trade with us.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PeerLimits {
+    /// The maximum number of swaps with the same peer that may run at the
+    /// same time.
+    pub max_concurrent_swaps: Option<usize>,
+    /// The maximum amount of BTC the same peer may sell to us within 24 hours.
+    pub max_daily_volume: Option<bitcoin::Amount>,
+}
+
 pub trait LatestRate {
     type Error: std::error::Error + Send + Sync + 'static;
 
@@ -450,37 +745,80 @@ impl LatestRate for FixedRate {
 /// spread.
 #[derive(Debug)]
 pub struct KrakenRate {
-    ask_spread: Decimal,
+    pricing: Pricing,
     price_updates: kraken::PriceUpdates,
+    history: Arc<std::sync::Mutex<PriceHistory>>,
 }
 
 impl KrakenRate {
-    pub fn new(ask_spread: Decimal, price_updates: kraken::PriceUpdates) -> Self {
+    /// Records the history of the asking price in a background task, hence
+    /// this has to be called within a tokio runtime.
+    pub fn new(pricing: Pricing, price_updates: kraken::PriceUpdates) -> Self {
+        let history = Arc::new(std::sync::Mutex::new(PriceHistory::new(VOLATILITY_WINDOW)));
+
+        tokio::spawn(record_price_history(price_updates.clone(), history.clone()));
+
         Self {
-            ask_spread,
+            pricing,
             price_updates,
+            history,
         }
     }
 }
 
 impl LatestRate for KrakenRate {
-    type Error = kraken::Error;
+    type Error = KrakenRateError;
 
     fn latest_rate(&mut self) -> Result<Rate, Self::Error> {
-        let update = self.price_updates.latest_update()?;
-        let rate = Rate::new(update.ask, self.ask_spread);
+        // Fail with the error of the price updates if there is one
+        let _ = self.price_updates.latest_update()?;
+
+        let history = self
+            .history
+            .lock()
+            .expect("price history lock not to be poisoned");
+        let rate = self.pricing.rate(&history, Instant::now())?;
 
         Ok(rate)
     }
 }
 
+async fn record_price_history(
+    mut price_updates: kraken::PriceUpdates,
+    history: Arc<std::sync::Mutex<PriceHistory>>,
+) {
+    while let Ok(update) = price_updates.wait_for_next_update().await {
+        if let Ok(update) = update {
+            history
+                .lock()
+                .expect("price history lock not to be poisoned")
+                .record(Instant::now(), update.ask);
+        }
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum KrakenRateError {
+    #[error(transparent)]
+    Kraken(#[from] kraken::Error),
+    #[error(transparent)]
+    Stale(#[from] StaleRate),
+}
+
 #[derive(Debug)]
 pub struct EventLoopHandle {
+    peer: PeerId,
     recv_encrypted_signature: Option<bmrng::RequestReceiver<bitcoin::EncryptedSignature, ()>>,
     send_transfer_proof: Option<bmrng::RequestSender<monero::TransferProof, ()>>,
+    report_violation: mpsc::UnboundedSender<(PeerId, Violation)>,
 }
 
 impl EventLoopHandle {
+    /// Report that the peer of this swap violated the protocol.
+    pub fn report_violation(&self, violation: Violation) {
+        let _ = self.report_violation.send((self.peer, violation));
+    }
+
     pub async fn recv_encrypted_signature(&mut self) -> Result<bitcoin::EncryptedSignature> {
         let (tx_redeem_encsig, responder) = self
             .recv_encrypted_signature
@@ -508,6 +846,181 @@ impl EventLoopHandle {
     }
 }
 
+/// What we know about our funds and the peer when deciding whether to give a
+/// spot price.
+#[derive(Debug, Clone, Copy)]
+pub struct SpotPriceContext {
+    pub min_buy: bitcoin::Amount,
+    pub max_buy: bitcoin::Amount,
+    pub peer_limits: PeerLimits,
+    /// The swaps with the peer that have not finished yet.
+    pub unfinished_swaps_with_peer: usize,
+    /// The BTC the peer sold to us within the last 24 hours.
+    pub peer_daily_volume: bitcoin::Amount,
+    /// The XMR that is not reserved for other swaps.
+    pub xmr_available: monero::Amount,
+    /// The fee reserved on top of the XMR for the lock transaction.
+    pub xmr_lock_fee: monero::Amount,
+}
+
+/// Decides whether to give a spot price for `btc` at `rate` and returns the
+/// XMR we sell for it.
+///
+/// The ASB and the backtest both decide through this function, so the
+/// backtest accepts exactly the requests the ASB would accept.
+pub fn decide_spot_price(
+    rate: Rate,
+    btc: bitcoin::Amount,
+    context: &SpotPriceContext,
+) -> Result<monero::Amount> {
+    if btc > context.max_buy {
+        bail!(MaximumBuyAmountExceeded {
+            actual: btc,
+            max: context.max_buy
+        })
+    }
+
+    if btc < context.min_buy {
+        bail!(MinimumBuyAmountNotReached {
+            actual: btc,
+            min: context.min_buy
+        })
+    }
+
+    if let Some(max) = context.peer_limits.max_concurrent_swaps {
+        if context.unfinished_swaps_with_peer >= max {
+            bail!(TooManyConcurrentSwaps { max })
+        }
+    }
+
+    if let Some(max) = context.peer_limits.max_daily_volume {
+        if context.peer_daily_volume + btc > max {
+            bail!(DailyVolumeExceeded {
+                actual: btc,
+                volume: context.peer_daily_volume,
+                max
+            })
+        }
+    }
+
+    let xmr = rate.sell_quote(btc)?;
+
+    if context.xmr_available < xmr + context.xmr_lock_fee {
+        bail!(BalanceTooLow {
+            balance: context.xmr_available
+        })
+    }
+
+    Ok(xmr)
+}
+
+/// The most we can buy, zero if we cannot deliver the XMR for `min_buy`. A
+/// quote with a maximum below its minimum cannot be taken.
+fn max_quantity(
+    min_buy: bitcoin::Amount,
+    max_buy: bitcoin::Amount,
+    max_deliverable: bitcoin::Amount,
+) -> bitcoin::Amount {
+    let max_quantity = min(max_buy, max_deliverable);
+
+    if max_quantity < min_buy {
+        return bitcoin::Amount::ZERO;
+    }
+
+    max_quantity
+}
+
+fn available_xmr(balance: monero::Amount, reserved: monero::Amount) -> monero::Amount {
+    if reserved > balance {
+        return monero::Amount::ZERO;
+    }
+
+    balance - reserved
+}
+
+/// Translates the reason for refusing a spot price into the error sent to the
+/// peer. Details of internal failures are not disclosed.
+pub(crate) fn spot_price_error(error: &anyhow::Error, btc: bitcoin::Amount) -> spot_price::Error {
+    if let Some(e) = error.downcast_ref::<MaximumBuyAmountExceeded>() {
+        return spot_price::Error::MaxBuyAmountExceeded {
+            max: e.max,
+            buy: e.actual,
+        };
+    }
+
+    if let Some(e) = error.downcast_ref::<MinimumBuyAmountNotReached>() {
+        return spot_price::Error::MinBuyAmountNotReached {
+            min: e.min,
+            buy: e.actual,
+        };
+    }
+
+    if let Some(e) = error.downcast_ref::<TooManyConcurrentSwaps>() {
+        return spot_price::Error::TooManyConcurrentSwaps { max: e.max };
+    }
+
+    if let Some(e) = error.downcast_ref::<DailyVolumeExceeded>() {
+        return spot_price::Error::DailyVolumeExceeded {
+            remaining: e.max.checked_sub(e.volume).unwrap_or(bitcoin::Amount::ZERO),
+            buy: e.actual,
+        };
+    }
+
+    if error.downcast_ref::<BalanceTooLow>().is_some() {
+        return spot_price::Error::BalanceTooLow { buy: btc };
+    }
+
+    if error.downcast_ref::<RateUnavailable>().is_some() {
+        return match is_stale_rate(error) {
+            true => spot_price::Error::RateStale,
+            false => spot_price::Error::RateUnavailable,
+        };
+    }
+
+    if error.downcast_ref::<MoneroWalletUnavailable>().is_some() {
+        return spot_price::Error::Maintenance;
+    }
+
+    if error.downcast_ref::<QuoteExpired>().is_some() {
+        return spot_price::Error::QuoteExpired;
+    }
+
+    spot_price::Error::Other
+}
+
+/// Whether we had a rate but it is too old to be used, as opposed to not
+/// having a rate at all.
+fn is_stale_rate(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<KrakenRateError>(),
+        Some(KrakenRateError::Stale(_))
+    ) || error.downcast_ref::<StaleRate>().is_some()
+}
+
+/// Tells Bob why we did not give him a quote, without disclosing internal
+/// errors.
+fn quote_error(error: &anyhow::Error) -> quote::Error {
+    if error.downcast_ref::<RateUnavailable>().is_some() {
+        return quote::Error::RateUnavailable;
+    }
+
+    quote::Error::Other
+}
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Failed to get latest rate")]
+pub struct RateUnavailable;
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Failed to reach the Monero wallet")]
+pub struct MoneroWalletUnavailable;
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Quote {quote_id} is unknown or expired")]
+pub struct QuoteExpired {
+    pub quote_id: Uuid,
+}
+
 #[derive(Debug, Clone, Copy, thiserror::Error)]
 #[error("Refusing to buy {actual} because the maximum configured limit is {max}")]
 pub struct MaximumBuyAmountExceeded {
@@ -515,6 +1028,27 @@ pub struct MaximumBuyAmountExceeded {
     pub actual: bitcoin::Amount,
 }
 
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Refusing to buy {actual} because the minimum configured amount is {min}")]
+pub struct MinimumBuyAmountNotReached {
+    pub min: bitcoin::Amount,
+    pub actual: bitcoin::Amount,
+}
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Refusing to start another swap because the peer already has {max} unfinished swaps")]
+pub struct TooManyConcurrentSwaps {
+    pub max: usize,
+}
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Refusing to buy {actual} because the peer already sold {volume} within the last 24 hours and the limit is {max}")]
+pub struct DailyVolumeExceeded {
+    pub max: bitcoin::Amount,
+    pub volume: bitcoin::Amount,
+    pub actual: bitcoin::Amount,
+}
+
 #[allow(missing_debug_implementations)]
 struct MpscChannels<T> {
     sender: mpsc::Sender<T>,
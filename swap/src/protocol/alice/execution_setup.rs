@@ -4,8 +4,24 @@ use crate::protocol::{alice, Message0, Message2, Message4};
 use anyhow::{Context, Error};
 use libp2p::PeerId;
 use libp2p_async_await::BehaviourOutEvent;
+use std::time::Duration;
+use tokio::time::timeout;
 use uuid::Uuid;
 
+/// How long Bob may take to send his next message once he started the
+/// execution setup.
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A failure of the execution setup that is Bob's fault, as opposed to e.g.
+/// the connection breaking down or a failure on our side.
+#[derive(Debug, thiserror::Error)]
+pub enum Misbehaviour {
+    #[error("Bob did not send {0} in time")]
+    Timeout(&'static str),
+    #[error("Bob sent an invalid {0}")]
+    InvalidMessage(&'static str),
+}
+
 #[derive(Debug)]
 pub enum OutEvent {
     Done {
@@ -51,10 +67,14 @@ impl Behaviour {
     pub fn run(&mut self, bob: PeerId, state0: State0) {
         self.inner
             .do_protocol_listener(bob, move |mut substream| async move {
-                let message0 =
-                    serde_cbor::from_slice::<Message0>(&substream.read_message(BUF_SIZE).await?)
-                        .context("Failed to deserialize message0")?;
-                let (swap_id, state1) = state0.receive(message0)?;
+                let message0 = timeout(MESSAGE_TIMEOUT, substream.read_message(BUF_SIZE))
+                    .await
+                    .map_err(|_| Misbehaviour::Timeout("message0"))??;
+                let message0 = serde_cbor::from_slice::<Message0>(&message0)
+                    .context(Misbehaviour::InvalidMessage("message0"))?;
+                let (swap_id, state1) = state0
+                    .receive(message0)
+                    .context(Misbehaviour::InvalidMessage("message0"))?;
 
                 substream
                     .write_message(
@@ -63,12 +83,14 @@ impl Behaviour {
                     )
                     .await?;
 
-                let message2 =
-                    serde_cbor::from_slice::<Message2>(&substream.read_message(BUF_SIZE).await?)
-                        .context("Failed to deserialize message2")?;
+                let message2 = timeout(MESSAGE_TIMEOUT, substream.read_message(BUF_SIZE))
+                    .await
+                    .map_err(|_| Misbehaviour::Timeout("message2"))??;
+                let message2 = serde_cbor::from_slice::<Message2>(&message2)
+                    .context(Misbehaviour::InvalidMessage("message2"))?;
                 let state2 = state1
                     .receive(message2)
-                    .context("Failed to receive Message2")?;
+                    .context(Misbehaviour::InvalidMessage("message2"))?;
 
                 substream
                     .write_message(
@@ -77,10 +99,14 @@ impl Behaviour {
                     )
                     .await?;
 
-                let message4 =
-                    serde_cbor::from_slice::<Message4>(&substream.read_message(BUF_SIZE).await?)
-                        .context("Failed to deserialize message4")?;
-                let state3 = state2.receive(message4)?;
+                let message4 = timeout(MESSAGE_TIMEOUT, substream.read_message(BUF_SIZE))
+                    .await
+                    .map_err(|_| Misbehaviour::Timeout("message4"))??;
+                let message4 = serde_cbor::from_slice::<Message4>(&message4)
+                    .context(Misbehaviour::InvalidMessage("message4"))?;
+                let state3 = state2
+                    .receive(message4)
+                    .context(Misbehaviour::InvalidMessage("message4"))?;
 
                 Ok((bob, (swap_id, state3)))
             })
@@ -99,7 +125,7 @@ impl From<OutEvent> for alice::OutEvent {
                 state3: Box::new(state3),
                 swap_id,
             },
-            OutEvent::Failure { peer, error } => Self::Failure { peer, error },
+            OutEvent::Failure { peer, error } => Self::ExecutionSetupFailed { peer, error },
         }
     }
 }
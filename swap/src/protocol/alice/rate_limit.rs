@@ -0,0 +1,306 @@
+use crate::asb::config::{
+    DEFAULT_GLOBAL_REQUESTS_PER_MINUTE, DEFAULT_PER_PEER_REQUESTS_PER_MINUTE,
+};
+use libp2p::PeerId;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+const ONE_MINUTE: Duration = Duration::from_secs(60);
+
+/// The protocols whose requests are rate limited because answering them is
+/// costly for us.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Quote,
+    SpotPrice,
+    ExecutionSetup,
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Protocol::Quote => write!(f, "quote"),
+            Protocol::SpotPrice => write!(f, "spot price"),
+            Protocol::ExecutionSetup => write!(f, "execution setup"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum RateLimitExceeded {
+    #[error("Peer exceeded the limit of {limit} {protocol} requests per minute")]
+    Peer { protocol: Protocol, limit: usize },
+    #[error("All peers together exceeded the limit of {limit} {protocol} requests per minute")]
+    Global { protocol: Protocol, limit: usize },
+}
+
+/// Limits how many requests per minute we answer, both for each peer and for
+/// all peers together. Each protocol has its own budget.
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_peer_limit: usize,
+    global_limit: usize,
+    per_peer: HashMap<(PeerId, Protocol), Window>,
+    global: HashMap<Protocol, Window>,
+    stats: HashMap<Protocol, Stats>,
+}
+
+/// How many requests were served and rejected since the stats were last
+/// taken.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    pub served: u64,
+    pub rejected: u64,
+}
+
+impl RateLimiter {
+    pub fn new(per_peer_requests_per_minute: usize, global_requests_per_minute: usize) -> Self {
+        Self {
+            per_peer_limit: per_peer_requests_per_minute,
+            global_limit: global_requests_per_minute,
+            per_peer: HashMap::new(),
+            global: HashMap::new(),
+            stats: HashMap::new(),
+        }
+    }
+
+    /// Accounts for a request of `peer` that is served by all of `protocols`,
+    /// e.g. a spot price that is followed by an execution setup, unless it
+    /// exceeds one of the limits. A rejected request consumes no budget.
+    pub fn check_all(
+        &mut self,
+        peer: PeerId,
+        protocols: &[Protocol],
+    ) -> Result<(), RateLimitExceeded> {
+        self.check_all_at(peer, protocols, Instant::now())
+    }
+
+    #[cfg(test)]
+    fn check_at(
+        &mut self,
+        peer: PeerId,
+        protocol: Protocol,
+        now: Instant,
+    ) -> Result<(), RateLimitExceeded> {
+        self.check_all_at(peer, &[protocol], now)
+    }
+
+    fn check_all_at(
+        &mut self,
+        peer: PeerId,
+        protocols: &[Protocol],
+        now: Instant,
+    ) -> Result<(), RateLimitExceeded> {
+        self.per_peer
+            .values_mut()
+            .for_each(|window| window.prune(now));
+        self.per_peer.retain(|_, window| !window.is_empty());
+        self.global
+            .values_mut()
+            .for_each(|window| window.prune(now));
+
+        for protocol in protocols {
+            if let Err(e) = self.capacity(peer, *protocol) {
+                self.stats.entry(*protocol).or_default().rejected += 1;
+                return Err(e);
+            }
+        }
+
+        let per_peer_limit = self.per_peer_limit;
+        let global_limit = self.global_limit;
+
+        for protocol in protocols {
+            self.per_peer
+                .entry((peer, *protocol))
+                .or_insert_with(|| Window::new(per_peer_limit))
+                .record(now);
+            self.global
+                .entry(*protocol)
+                .or_insert_with(|| Window::new(global_limit))
+                .record(now);
+            self.stats.entry(*protocol).or_default().served += 1;
+        }
+
+        Ok(())
+    }
+
+    fn capacity(&self, peer: PeerId, protocol: Protocol) -> Result<(), RateLimitExceeded> {
+        if !self
+            .per_peer
+            .get(&(peer, protocol))
+            .map_or(true, Window::has_capacity)
+        {
+            return Err(RateLimitExceeded::Peer {
+                protocol,
+                limit: self.per_peer_limit,
+            });
+        }
+
+        if !self
+            .global
+            .get(&protocol)
+            .map_or(true, Window::has_capacity)
+        {
+            return Err(RateLimitExceeded::Global {
+                protocol,
+                limit: self.global_limit,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the stats of each protocol and resets them.
+    pub fn take_stats(&mut self) -> HashMap<Protocol, Stats> {
+        std::mem::take(&mut self.stats)
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_PER_PEER_REQUESTS_PER_MINUTE,
+            DEFAULT_GLOBAL_REQUESTS_PER_MINUTE,
+        )
+    }
+}
+
+/// The requests within the last minute.
+#[derive(Debug)]
+struct Window {
+    limit: usize,
+    requests: VecDeque<Instant>,
+}
+
+impl Window {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            requests: VecDeque::new(),
+        }
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some(oldest) = self.requests.front() {
+            if now.duration_since(*oldest) < ONE_MINUTE {
+                break;
+            }
+
+            self.requests.pop_front();
+        }
+    }
+
+    fn has_capacity(&self) -> bool {
+        self.requests.len() < self.limit
+    }
+
+    fn record(&mut self, now: Instant) {
+        self.requests.push_back(now);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_peer_exceeding_its_limit() {
+        let mut rate_limiter = RateLimiter::new(2, 10);
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        assert!(rate_limiter.check_at(peer, Protocol::Quote, now).is_ok());
+        assert!(rate_limiter.check_at(peer, Protocol::Quote, now).is_ok());
+        assert_eq!(
+            rate_limiter.check_at(peer, Protocol::Quote, now),
+            Err(RateLimitExceeded::Peer {
+                protocol: Protocol::Quote,
+                limit: 2
+            })
+        );
+
+        // Other protocols and peers have their own budget
+        assert!(rate_limiter
+            .check_at(peer, Protocol::SpotPrice, now)
+            .is_ok());
+        assert!(rate_limiter
+            .check_at(PeerId::random(), Protocol::Quote, now)
+            .is_ok());
+    }
+
+    #[test]
+    fn rejects_requests_exceeding_global_limit() {
+        let mut rate_limiter = RateLimiter::new(2, 2);
+        let now = Instant::now();
+
+        assert!(rate_limiter
+            .check_at(PeerId::random(), Protocol::SpotPrice, now)
+            .is_ok());
+        assert!(rate_limiter
+            .check_at(PeerId::random(), Protocol::SpotPrice, now)
+            .is_ok());
+        assert_eq!(
+            rate_limiter.check_at(PeerId::random(), Protocol::SpotPrice, now),
+            Err(RateLimitExceeded::Global {
+                protocol: Protocol::SpotPrice,
+                limit: 2
+            })
+        );
+    }
+
+    #[test]
+    fn rejected_combined_request_does_not_consume_any_budget() {
+        let mut rate_limiter = RateLimiter::new(1, 10);
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        assert!(rate_limiter
+            .check_at(peer, Protocol::ExecutionSetup, now)
+            .is_ok());
+        assert_eq!(
+            rate_limiter.check_all_at(peer, &[Protocol::SpotPrice, Protocol::ExecutionSetup], now),
+            Err(RateLimitExceeded::Peer {
+                protocol: Protocol::ExecutionSetup,
+                limit: 1
+            })
+        );
+        assert!(rate_limiter
+            .check_at(peer, Protocol::SpotPrice, now)
+            .is_ok());
+    }
+
+    #[test]
+    fn budget_is_restored_after_a_minute() {
+        let mut rate_limiter = RateLimiter::new(1, 10);
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        assert!(rate_limiter.check_at(peer, Protocol::Quote, now).is_ok());
+        assert!(rate_limiter.check_at(peer, Protocol::Quote, now).is_err());
+        assert!(rate_limiter
+            .check_at(peer, Protocol::Quote, now + ONE_MINUTE)
+            .is_ok());
+    }
+
+    #[test]
+    fn stats_count_served_and_rejected_requests() {
+        let mut rate_limiter = RateLimiter::new(1, 10);
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        let _ = rate_limiter.check_at(peer, Protocol::Quote, now);
+        let _ = rate_limiter.check_at(peer, Protocol::Quote, now);
+
+        let stats = rate_limiter.take_stats();
+        assert_eq!(stats[&Protocol::Quote], Stats {
+            served: 1,
+            rejected: 1
+        });
+        assert!(rate_limiter.take_stats().is_empty());
+    }
+}
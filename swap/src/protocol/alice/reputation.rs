@@ -0,0 +1,122 @@
+use libp2p::PeerId;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// The number of violations after which a peer is banned.
+pub const DEFAULT_BAN_THRESHOLD: usize = 3;
+
+/// How long a violation counts towards a ban.
+pub const DEFAULT_VIOLATION_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Violation {
+    /// The peer sent a message that does not fit the protocol, e.g. an
+    /// encrypted signature for a swap of another peer.
+    ProtocolViolation,
+    /// The peer started the execution setup of a swap but stalled or sent an
+    /// invalid message.
+    AbandonedExecutionSetup,
+    /// The peer set up a swap but did not fund TxLock in time.
+    UnfundedTxLock,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::ProtocolViolation => write!(f, "protocol violation"),
+            Violation::AbandonedExecutionSetup => write!(f, "abandoned execution setup"),
+            Violation::UnfundedTxLock => write!(f, "unfunded TxLock"),
+        }
+    }
+}
+
+/// Counts the recent violations of each peer to decide when a peer is to be
+/// banned. Violations expire, hence an honest peer that occasionally runs into
+/// problems is not banned eventually.
+#[derive(Debug)]
+pub struct Reputation {
+    violations: HashMap<PeerId, Vec<Instant>>,
+    ban_threshold: usize,
+    expiry: Duration,
+}
+
+impl Reputation {
+    pub fn new(ban_threshold: usize, expiry: Duration) -> Self {
+        Self {
+            violations: HashMap::new(),
+            ban_threshold,
+            expiry,
+        }
+    }
+
+    /// Records a violation of `peer` and returns whether the peer has to be
+    /// banned now.
+    pub fn record(&mut self, peer: PeerId, violation: Violation) -> bool {
+        self.record_at(peer, violation, Instant::now())
+    }
+
+    fn record_at(&mut self, peer: PeerId, violation: Violation, now: Instant) -> bool {
+        let expiry = self.expiry;
+
+        let violations = self.violations.entry(peer).or_default();
+        violations.retain(|committed_at| now.duration_since(*committed_at) < expiry);
+        violations.push(now);
+
+        tracing::debug!(
+            %peer,
+            "Recorded {}, {} within the last {} hours",
+            violation,
+            violations.len(),
+            expiry.as_secs() / 3600
+        );
+
+        violations.len() >= self.ban_threshold
+    }
+}
+
+impl Default for Reputation {
+    fn default() -> Self {
+        Self::new(DEFAULT_BAN_THRESHOLD, DEFAULT_VIOLATION_EXPIRY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPIRY: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn peer_is_banned_once_threshold_is_reached() {
+        let mut reputation = Reputation::new(2, EXPIRY);
+        let peer = PeerId::random();
+
+        assert!(!reputation.record(peer, Violation::AbandonedExecutionSetup));
+        assert!(reputation.record(peer, Violation::UnfundedTxLock));
+    }
+
+    #[test]
+    fn violations_are_counted_per_peer() {
+        let mut reputation = Reputation::new(2, EXPIRY);
+        let peer = PeerId::random();
+        let other_peer = PeerId::random();
+
+        assert!(!reputation.record(peer, Violation::ProtocolViolation));
+        assert!(!reputation.record(other_peer, Violation::ProtocolViolation));
+    }
+
+    #[test]
+    fn expired_violations_are_not_counted() {
+        let mut reputation = Reputation::new(2, EXPIRY);
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        assert!(!reputation.record_at(peer, Violation::ProtocolViolation, now));
+        assert!(!reputation.record_at(
+            peer,
+            Violation::ProtocolViolation,
+            now + EXPIRY + Duration::from_secs(1)
+        ));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::bitcoin::{
-    current_epoch, CancelTimelock, ExpiredTimelocks, PunishTimelock, TxCancel, TxPunish, TxRefund,
+    current_epoch, timelock_status, CancelTimelock, ExpiredTimelocks, PunishTimelock,
+    TimelockStatus, TxCancel, TxPunish, TxRefund,
 };
 use crate::env::Config;
 use crate::monero::wallet::{TransferRequest, WatchRequest};
@@ -12,10 +13,11 @@ use monero_rpc::wallet::BlockHeight;
 use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use sigma_fun::ext::dl_secp256k1_ed25519_eq::CrossCurveDLEQProof;
+use std::collections::BTreeMap;
 use std::fmt;
 use uuid::Uuid;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum AliceState {
     Started {
         state3: Box<State3>,
@@ -94,6 +96,86 @@ impl fmt::Display for AliceState {
     }
 }
 
+impl AliceState {
+    /// The ids of the transactions known in this state, by transaction.
+    pub fn txids(&self) -> BTreeMap<String, String> {
+        let mut txids = BTreeMap::new();
+
+        match self {
+            AliceState::Started { state3 } | AliceState::BtcLocked { state3 } => {
+                txids.insert("btc_lock".to_string(), state3.tx_lock.txid().to_string());
+            }
+            AliceState::XmrLockTransactionSent {
+                transfer_proof,
+                state3,
+                ..
+            }
+            | AliceState::XmrLocked {
+                transfer_proof,
+                state3,
+                ..
+            }
+            | AliceState::XmrLockTransferProofSent {
+                transfer_proof,
+                state3,
+                ..
+            }
+            | AliceState::EncSigLearned {
+                transfer_proof,
+                state3,
+                ..
+            }
+            | AliceState::CancelTimelockExpired {
+                transfer_proof,
+                state3,
+                ..
+            } => {
+                txids.insert("btc_lock".to_string(), state3.tx_lock.txid().to_string());
+                txids.insert("xmr_lock".to_string(), transfer_proof.tx_hash().to_string());
+            }
+            AliceState::BtcCancelled {
+                transfer_proof,
+                state3,
+                ..
+            }
+            | AliceState::BtcPunishable {
+                transfer_proof,
+                state3,
+                ..
+            } => {
+                txids.insert("btc_lock".to_string(), state3.tx_lock.txid().to_string());
+                txids.insert("xmr_lock".to_string(), transfer_proof.tx_hash().to_string());
+                txids.insert(
+                    "btc_cancel".to_string(),
+                    state3.tx_cancel().txid().to_string(),
+                );
+            }
+            AliceState::BtcRefunded {
+                transfer_proof,
+                state3,
+                ..
+            } => {
+                txids.insert("btc_lock".to_string(), state3.tx_lock.txid().to_string());
+                txids.insert("xmr_lock".to_string(), transfer_proof.tx_hash().to_string());
+                txids.insert(
+                    "btc_cancel".to_string(),
+                    state3.tx_cancel().txid().to_string(),
+                );
+                txids.insert(
+                    "btc_refund".to_string(),
+                    state3.tx_refund().txid().to_string(),
+                );
+            }
+            AliceState::BtcRedeemed
+            | AliceState::XmrRefunded
+            | AliceState::BtcPunished
+            | AliceState::SafelyAborted => {}
+        }
+
+        txids
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct State0 {
     a: bitcoin::SecretKey,
@@ -352,6 +434,23 @@ impl State3 {
         ))
     }
 
+    pub async fn timelock_status(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+    ) -> Result<TimelockStatus> {
+        let tx_cancel = self.tx_cancel();
+
+        let tx_lock_status = bitcoin_wallet.status_of_script(&self.tx_lock).await?;
+        let tx_cancel_status = bitcoin_wallet.status_of_script(&tx_cancel).await?;
+
+        Ok(timelock_status(
+            self.cancel_timelock,
+            self.punish_timelock,
+            tx_lock_status,
+            tx_cancel_status,
+        ))
+    }
+
     pub fn lock_xmr_transfer_request(&self) -> TransferRequest {
         let S_a = monero::PublicKey::from_private_key(&monero::PrivateKey { scalar: self.s_a });
 
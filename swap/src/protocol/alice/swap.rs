@@ -2,11 +2,14 @@
 //! Alice holds XMR and wishes receive BTC.
 use crate::bitcoin::ExpiredTimelocks;
 use crate::env::Config;
+use crate::notification::{Notification, Role, SwapUpdate};
 use crate::protocol::alice::event_loop::EventLoopHandle;
-use crate::protocol::alice::{AliceState, Swap};
+use crate::protocol::alice::{AliceState, Swap, Violation};
+use crate::protocol::watchdog;
 use crate::{bitcoin, database, monero};
 use anyhow::{bail, Context, Result};
 use tokio::select;
+use tokio::sync::watch;
 use tokio::time::timeout;
 use tracing::{error, info};
 use uuid::Uuid;
@@ -16,11 +19,49 @@ pub async fn run(swap: Swap) -> Result<AliceState> {
 }
 
 #[tracing::instrument(name = "swap", skip(swap,exit_early), fields(id = %swap.swap_id), err)]
-pub async fn run_until(mut swap: Swap, exit_early: fn(&AliceState) -> bool) -> Result<AliceState> {
+pub async fn run_until(swap: Swap, exit_early: fn(&AliceState) -> bool) -> Result<AliceState> {
+    let swap_id = swap.swap_id;
+    let db = swap.db.clone();
+
+    // Resuming a swap that has not locked the XMR yet reserves them again
+    if let Some(amount) = xmr_to_reserve(&swap.state, swap.monero_wallet.static_tx_fee_estimate()) {
+        db.insert_xmr_reservation(swap_id, amount).await?;
+    }
+
+    let result = drive(swap, exit_early).await;
+
+    // A swap that stopped does not lock any XMR until it is resumed, hence it
+    // must not keep them from being quoted
+    if let Err(e) = db.remove_xmr_reservation(swap_id).await {
+        tracing::warn!("Failed to release XMR reservation: {:#}", e);
+    }
+
+    result
+}
+
+async fn drive(mut swap: Swap, exit_early: fn(&AliceState) -> bool) -> Result<AliceState> {
     let mut current_state = swap.state;
 
+    swap.notifier
+        .notify_in_background(Notification::SwapStarted(swap_update(
+            swap.swap_id,
+            &current_state,
+        )));
+
+    // Stops watching the deadline once dropped, i.e. when the swap returns
+    let (state_sender, state_receiver) = watch::channel(current_state.clone());
+    tokio::spawn(watchdog::monitor(
+        swap.swap_id,
+        state_receiver,
+        swap.bitcoin_wallet.clone(),
+        swap.notifier.clone(),
+        watchdog::CHECK_INTERVAL,
+    ));
+
     while !is_complete(&current_state) && !exit_early(&current_state) {
-        current_state = next_state(
+        let update = swap_update(swap.swap_id, &current_state);
+
+        current_state = match next_state(
             swap.swap_id,
             current_state,
             &mut swap.event_loop_handle,
@@ -28,15 +69,53 @@ pub async fn run_until(mut swap: Swap, exit_early: fn(&AliceState) -> bool) -> R
             swap.monero_wallet.as_ref(),
             &swap.env_config,
         )
+        .await
+        {
+            Ok(state) => state,
+            Err(error) => {
+                swap.notifier
+                    .notify(Notification::SwapFailed {
+                        update,
+                        error: format!("{:#}", error),
+                    })
+                    .await;
+                return Err(error);
+            }
+        };
+
+        if let Err(error) = persist(swap.swap_id, &current_state, &swap.db).await {
+            swap.notifier
+                .notify(Notification::SwapFailed {
+                    update: swap_update(swap.swap_id, &current_state),
+                    error: format!("{:#}", error),
+                })
+                .await;
+            return Err(error);
+        }
+        let _ = state_sender.send(current_state.clone());
+
+        // Don't delay the swap for the hooks, unless the process may exit next
+        let notification = notification(swap.swap_id, &current_state);
+        if is_complete(&current_state) || exit_early(&current_state) {
+            swap.notifier.notify(notification).await;
+        } else {
+            swap.notifier.notify_in_background(notification);
+        }
+    }
+
+    Ok(current_state)
+}
+
+async fn persist(swap_id: Uuid, state: &AliceState, db: &database::Database) -> Result<()> {
+    let db_state = state.into();
+    db.insert_latest_state(swap_id, database::Swap::Alice(db_state))
         .await?;
 
-        let db_state = (&current_state).into();
-        swap.db
-            .insert_latest_state(swap.swap_id, database::Swap::Alice(db_state))
-            .await?;
+    if xmr_to_reserve(state, monero::Amount::ZERO).is_none() {
+        db.remove_xmr_reservation(swap_id).await?;
     }
 
-    Ok(current_state)
+    Ok(())
 }
 
 async fn next_state(
@@ -64,6 +143,8 @@ async fn next_state(
                         env_config.bitcoin_finality_confirmations,
                         env_config.bitcoin_lock_confirmed_timeout.as_secs_f64() / 60.0
                     );
+                    event_loop_handle.report_violation(Violation::UnfundedTxLock);
+
                     AliceState::SafelyAborted
                 }
                 Ok(res) => {
@@ -299,12 +380,19 @@ async fn next_state(
                 .watch_for_transfer(state3.lock_xmr_watch_request(transfer_proof, 10))
                 .await?;
 
+            // Sweep the refunded XMR to a subaddress labelled with the swap id so they can
+            // be told apart from deposits, retries reuse the subaddress
+            let refund_address = monero_wallet
+                .labelled_subaddress(swap_id.to_string())
+                .await?;
+
             monero_wallet
                 .create_from(
                     swap_id.to_string(),
                     spend_key,
                     view_key,
                     monero_wallet_restore_blockheight,
+                    refund_address,
                 )
                 .await?;
 
@@ -372,3 +460,36 @@ fn is_complete(state: &AliceState) -> bool {
             | AliceState::SafelyAborted
     )
 }
+
+fn notification(swap_id: Uuid, state: &AliceState) -> Notification {
+    let update = swap_update(swap_id, state);
+
+    match state {
+        state if is_complete(state) => Notification::SwapFinished(update),
+        AliceState::CancelTimelockExpired { .. } | AliceState::BtcPunishable { .. } => {
+            Notification::SwapTimelockExpired(update)
+        }
+        _ => Notification::SwapStateChanged(update),
+    }
+}
+
+fn swap_update(swap_id: Uuid, state: &AliceState) -> SwapUpdate {
+    SwapUpdate {
+        swap_id,
+        role: Role::Alice,
+        state: state.to_string(),
+        txids: state.txids(),
+    }
+}
+
+/// XMR are reserved for a running swap from the moment it is set up until the
+/// XMR are locked or the swap is aborted, including the fee of the XMR lock
+/// transaction.
+fn xmr_to_reserve(state: &AliceState, lock_fee: monero::Amount) -> Option<monero::Amount> {
+    match state {
+        AliceState::Started { state3 } | AliceState::BtcLocked { state3 } => {
+            Some(state3.lock_xmr_transfer_request().amount + lock_fee)
+        }
+        _ => None,
+    }
+}
@@ -1,4 +1,5 @@
 use crate::database::Database;
+use crate::notification::Notifier;
 use crate::{bitcoin, env, monero};
 use anyhow::Result;
 use std::sync::Arc;
@@ -10,6 +11,7 @@ pub use self::event_loop::{EventLoop, EventLoopHandle};
 pub use self::refund::refund;
 pub use self::state::*;
 pub use self::swap::{run, run_until};
+pub use self::tx_lock_signer::TxLockSigner;
 
 mod behaviour;
 pub mod cancel;
@@ -18,22 +20,39 @@ mod execution_setup;
 pub mod refund;
 pub mod state;
 pub mod swap;
+mod tx_lock_signer;
 
 pub struct Swap {
     pub state: BobState,
     pub event_loop_handle: EventLoopHandle,
-    pub db: Database,
+    pub db: Arc<Database>,
     pub bitcoin_wallet: Arc<bitcoin::Wallet>,
     pub monero_wallet: Arc<monero::Wallet>,
     pub env_config: env::Config,
     pub id: Uuid,
     pub receive_monero_address: monero::Address,
+    pub tx_lock_signer: TxLockSigner,
+    pub notifier: Notifier,
 }
 
 impl Swap {
+    /// Signs the lock transaction with `tx_lock_signer` instead of the
+    /// internal wallet.
+    pub fn with_tx_lock_signer(self, tx_lock_signer: TxLockSigner) -> Self {
+        Self {
+            tx_lock_signer,
+            ..self
+        }
+    }
+
+    /// Notifies about the progress of the swap via `notifier`.
+    pub fn with_notifier(self, notifier: Notifier) -> Self {
+        Self { notifier, ..self }
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn new(
-        db: Database,
+        db: Arc<Database>,
         id: Uuid,
         bitcoin_wallet: Arc<bitcoin::Wallet>,
         monero_wallet: Arc<monero::Wallet>,
@@ -41,9 +60,15 @@ impl Swap {
         event_loop_handle: EventLoopHandle,
         receive_monero_address: monero::Address,
         btc_amount: bitcoin::Amount,
+        funding: bitcoin::TxLockFunding,
+        refund_address: Option<bitcoin::Address>,
     ) -> Self {
         Self {
-            state: BobState::Started { btc_amount },
+            state: BobState::Started {
+                btc_amount,
+                funding,
+                refund_address,
+            },
             event_loop_handle,
             db,
             bitcoin_wallet,
@@ -51,11 +76,13 @@ impl Swap {
             env_config,
             id,
             receive_monero_address,
+            tx_lock_signer: TxLockSigner::default(),
+            notifier: Notifier::default(),
         }
     }
 
     pub fn from_db(
-        db: Database,
+        db: Arc<Database>,
         id: Uuid,
         bitcoin_wallet: Arc<bitcoin::Wallet>,
         monero_wallet: Arc<monero::Wallet>,
@@ -74,6 +101,8 @@ impl Swap {
             env_config,
             id,
             receive_monero_address,
+            tx_lock_signer: TxLockSigner::default(),
+            notifier: Notifier::default(),
         })
     }
 }
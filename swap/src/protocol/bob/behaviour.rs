@@ -1,4 +1,3 @@
-use crate::network::quote::BidQuote;
 use crate::network::{encrypted_signature, quote, redial, spot_price, transfer_proof};
 use crate::protocol::bob::{execution_setup, State2};
 use anyhow::{anyhow, Error, Result};
@@ -11,7 +10,7 @@ use std::time::Duration;
 pub enum OutEvent {
     QuoteReceived {
         id: RequestId,
-        response: BidQuote,
+        response: quote::Response,
     },
     SpotPriceReceived {
         id: RequestId,
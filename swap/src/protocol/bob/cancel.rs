@@ -15,7 +15,7 @@ pub async fn cancel(
     swap_id: Uuid,
     state: BobState,
     bitcoin_wallet: Arc<Wallet>,
-    db: Database,
+    db: Arc<Database>,
     force: bool,
 ) -> Result<Result<(Txid, BobState), Error>> {
     let state6 = match state {
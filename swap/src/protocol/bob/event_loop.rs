@@ -1,6 +1,6 @@
 use crate::bitcoin::EncryptedSignature;
 use crate::network::quote::BidQuote;
-use crate::network::{encrypted_signature, spot_price};
+use crate::network::{encrypted_signature, quote, spot_price};
 use crate::protocol::bob::{Behaviour, OutEvent, State0, State2};
 use crate::{bitcoin, monero};
 use anyhow::{Context, Result};
@@ -11,9 +11,13 @@ use libp2p::swarm::SwarmEvent;
 use libp2p::{PeerId, Swarm};
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
+/// A quote is considered expired this long before Alice stops honouring it,
+/// to account for the time it takes to request the spot price.
+const QUOTE_EXPIRY_MARGIN: Duration = Duration::from_secs(5);
+
 #[allow(missing_debug_implementations)]
 pub struct EventLoop {
     swap_id: Uuid,
@@ -22,7 +26,7 @@ pub struct EventLoop {
     alice_peer_id: PeerId,
 
     // these streams represents outgoing requests that we have to make
-    quote_requests: bmrng::RequestReceiverStream<(), BidQuote>,
+    quote_requests: bmrng::RequestReceiverStream<(), quote::Response>,
     spot_price_requests: bmrng::RequestReceiverStream<spot_price::Request, spot_price::Response>,
     encrypted_signatures: bmrng::RequestReceiverStream<EncryptedSignature, ()>,
     execution_setup_requests: bmrng::RequestReceiverStream<State0, Result<State2>>,
@@ -31,7 +35,7 @@ pub struct EventLoop {
     // once we get a response to a matching [`RequestId`], we will use the responder to relay the
     // response.
     inflight_spot_price_requests: HashMap<RequestId, bmrng::Responder<spot_price::Response>>,
-    inflight_quote_requests: HashMap<RequestId, bmrng::Responder<BidQuote>>,
+    inflight_quote_requests: HashMap<RequestId, bmrng::Responder<quote::Response>>,
     inflight_encrypted_signature_requests: HashMap<RequestId, bmrng::Responder<()>>,
     inflight_execution_setup: Option<bmrng::Responder<Result<State2>>>,
 
@@ -83,6 +87,7 @@ impl EventLoop {
             encrypted_signature: encrypted_signature.0,
             spot_price: spot_price.0,
             quote: quote.0,
+            last_quote: None,
         };
 
         Ok((event_loop, handle))
@@ -239,7 +244,9 @@ pub struct EventLoopHandle {
     transfer_proof: bmrng::RequestReceiver<monero::TransferProof, ()>,
     encrypted_signature: bmrng::RequestSender<EncryptedSignature, ()>,
     spot_price: bmrng::RequestSender<spot_price::Request, spot_price::Response>,
-    quote: bmrng::RequestSender<(), BidQuote>,
+    quote: bmrng::RequestSender<(), quote::Response>,
+    /// The last quote received from Alice and when it was received.
+    last_quote: Option<(BidQuote, Instant)>,
 }
 
 impl EventLoopHandle {
@@ -260,16 +267,55 @@ impl EventLoopHandle {
         Ok(transfer_proof)
     }
 
+    /// Requests the spot price for `btc` at the price of the last quote.
+    ///
+    /// Fails if the last quote expired, the user has to accept a new quote
+    /// instead of swapping at a price they have not seen. If no quote was
+    /// requested yet, e.g. when resuming a swap, a new quote is requested.
     pub async fn request_spot_price(&mut self, btc: bitcoin::Amount) -> Result<monero::Amount> {
-        Ok(self
+        let quote_id = self.valid_quote_id().await?;
+        let response = self
             .spot_price
-            .send_receive(spot_price::Request { btc })
-            .await?
-            .xmr)
+            .send_receive(spot_price::Request { btc, quote_id })
+            .await?;
+
+        match response {
+            spot_price::Response::Xmr(xmr) => Ok(xmr),
+            spot_price::Response::Error(error) => Err(SpotPriceRejected { error }.into()),
+        }
     }
 
     pub async fn request_quote(&mut self) -> Result<BidQuote> {
-        Ok(self.quote.send_receive(()).await?)
+        let quote = match self.quote.send_receive(()).await? {
+            quote::Response::Quote(quote) => quote,
+            quote::Response::Rejected { rejected } => {
+                return Err(QuoteRejected { error: rejected }.into())
+            }
+        };
+        self.last_quote = Some((quote.clone(), Instant::now()));
+
+        Ok(quote)
+    }
+
+    /// The id of the last quote, unless it expired.
+    async fn valid_quote_id(&mut self) -> Result<Option<Uuid>> {
+        let (quote, received_at) = match self.last_quote.clone() {
+            Some(last_quote) => last_quote,
+            None => {
+                let quote = self.request_quote().await?;
+                tracing::info!("Received quote: 1 XMR ~ {}", quote.price);
+
+                (quote, Instant::now())
+            }
+        };
+
+        if let Some(valid_for) = quote.valid_for {
+            if received_at.elapsed() + QUOTE_EXPIRY_MARGIN >= valid_for {
+                return Err(QuoteExpired { price: quote.price }.into());
+            }
+        }
+
+        Ok(quote.id)
     }
 
     pub async fn send_encrypted_signature(
@@ -282,3 +328,21 @@ impl EventLoopHandle {
             .await?)
     }
 }
+
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("The quote of 1 XMR ~ {price} expired before the swap started, restart the swap to accept a new quote")]
+pub struct QuoteExpired {
+    pub price: bitcoin::Amount,
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Alice refused to give a quote: {error}")]
+pub struct QuoteRejected {
+    pub error: quote::Error,
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Alice refused to give a spot price: {error}. {}.", .error.hint())]
+pub struct SpotPriceRejected {
+    pub error: spot_price::Error,
+}
@@ -13,7 +13,7 @@ pub async fn refund(
     swap_id: Uuid,
     state: BobState,
     bitcoin_wallet: Arc<Wallet>,
-    db: Database,
+    db: Arc<Database>,
     force: bool,
 ) -> Result<Result<BobState, SwapNotCancelledYet>> {
     let state6 = if force {
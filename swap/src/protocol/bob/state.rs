@@ -1,11 +1,14 @@
+use crate::bitcoin::wallet::ScriptStatus;
 use crate::bitcoin::{
-    self, current_epoch, CancelTimelock, ExpiredTimelocks, PunishTimelock, Transaction, TxCancel,
-    TxLock, Txid,
+    self, current_epoch, timelock_status, CancelTimelock, ExpiredTimelocks,
+    PartiallySignedTransaction, PunishTimelock, TimelockStatus, Transaction, TxCancel, TxLock,
+    Txid,
 };
 use crate::monero;
 use crate::monero::wallet::WatchRequest;
 use crate::monero::{monero_private_key, TransferProof};
 use crate::monero_ext::ScalarExt;
+use crate::network::watchtower::{WatchJob, WatchedTx};
 use crate::protocol::{Message0, Message1, Message2, Message3, Message4, CROSS_CURVE_PROOF_SYSTEM};
 use anyhow::{anyhow, bail, Context, Result};
 use ecdsa_fun::adaptor::{Adaptor, HashTranscript};
@@ -16,6 +19,7 @@ use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use sigma_fun::ext::dl_secp256k1_ed25519_eq::CrossCurveDLEQProof;
+use std::collections::BTreeMap;
 use std::fmt;
 use uuid::Uuid;
 
@@ -23,6 +27,8 @@ use uuid::Uuid;
 pub enum BobState {
     Started {
         btc_amount: bitcoin::Amount,
+        funding: bitcoin::TxLockFunding,
+        refund_address: Option<bitcoin::Address>,
     },
     ExecutionSetupDone(State2),
     BtcLocked(State3),
@@ -68,6 +74,111 @@ impl fmt::Display for BobState {
     }
 }
 
+impl BobState {
+    /// The watch job for a watchtower, from the moment the Bitcoin are
+    /// locked until they are refunded.
+    ///
+    /// The job only contains the cancel transaction. The refund transaction
+    /// reveals `s_b`, see [`BobState::watch_job_with_refund`].
+    pub fn watch_job(&self, swap_id: Uuid) -> Result<WatchJob> {
+        let state6 = self.refundable(swap_id)?;
+
+        state6.watch_job(swap_id, false)
+    }
+
+    /// The watch job including the refund transaction, only once the cancel
+    /// transaction, whose status is given as `tx_cancel`, is final.
+    ///
+    /// The refund transaction reveals `s_b`. Before the cancel transaction
+    /// spent the lock transaction for good, Alice could learn `s_b` from the
+    /// watchtower, redeem the Bitcoin and take back her Monero.
+    pub fn watch_job_with_refund(
+        &self,
+        swap_id: Uuid,
+        tx_cancel: ScriptStatus,
+        finality_confirmations: u32,
+    ) -> Result<WatchJob> {
+        let state6 = self.refundable(swap_id)?;
+
+        if !tx_cancel.is_confirmed_with(finality_confirmations) {
+            bail!(
+                "Cannot hand out the refund transaction of swap {} before the cancel transaction is final because it would reveal the Monero key share while the Bitcoin can still be redeemed.",
+                swap_id
+            )
+        }
+
+        state6.watch_job(swap_id, true)
+    }
+
+    /// The cancel transaction of the swap, if the Bitcoin are locked and not
+    /// yet refunded.
+    pub fn tx_cancel(&self, swap_id: Uuid) -> Result<bitcoin::TxCancel> {
+        Ok(self.refundable(swap_id)?.tx_cancel())
+    }
+
+    fn refundable(&self, swap_id: Uuid) -> Result<State6> {
+        match self {
+            BobState::BtcLocked(state3) | BobState::XmrLockProofReceived { state: state3, .. } => {
+                Ok(state3.cancel())
+            }
+            BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => {
+                Ok(state4.clone().cancel())
+            }
+            BobState::CancelTimelockExpired(state6) | BobState::BtcCancelled(state6) => {
+                Ok(state6.clone())
+            }
+            state => bail!(
+                "Cannot watch swap {} because it is in state {} which is not refundable.",
+                swap_id,
+                state
+            ),
+        }
+    }
+
+    /// The ids of the transactions known in this state, by transaction.
+    pub fn txids(&self) -> BTreeMap<String, String> {
+        let mut txids = BTreeMap::new();
+
+        let tx_lock_id = match self {
+            BobState::BtcLocked(state3) => Some(state3.tx_lock_id()),
+            BobState::XmrLockProofReceived {
+                state,
+                lock_transfer_proof,
+                ..
+            } => {
+                txids.insert(
+                    "xmr_lock".to_string(),
+                    lock_transfer_proof.tx_hash().to_string(),
+                );
+                Some(state.tx_lock_id())
+            }
+            BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => {
+                Some(state4.tx_lock.txid())
+            }
+            BobState::BtcRedeemed(state5) => Some(state5.tx_lock_id()),
+            BobState::CancelTimelockExpired(state6)
+            | BobState::BtcCancelled(state6)
+            | BobState::BtcRefunded(state6) => {
+                txids.insert("btc_cancel".to_string(), state6.tx_cancel_id().to_string());
+                txids.insert("btc_refund".to_string(), state6.tx_refund_id().to_string());
+                Some(state6.tx_lock_id())
+            }
+            BobState::XmrRedeemed { tx_lock_id } | BobState::BtcPunished { tx_lock_id } => {
+                Some(*tx_lock_id)
+            }
+            BobState::Started { .. }
+            | BobState::ExecutionSetupDone(..)
+            | BobState::SafelyAborted => None,
+        };
+
+        if let Some(tx_lock_id) = tx_lock_id {
+            txids.insert("btc_lock".to_string(), tx_lock_id.to_string());
+        }
+
+        txids
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct State0 {
     swap_id: Uuid,
@@ -83,6 +194,7 @@ pub struct State0 {
     punish_timelock: PunishTimelock,
     refund_address: bitcoin::Address,
     min_monero_confirmations: u64,
+    funding: bitcoin::TxLockFunding,
 }
 
 impl State0 {
@@ -96,6 +208,7 @@ impl State0 {
         punish_timelock: PunishTimelock,
         refund_address: bitcoin::Address,
         min_monero_confirmations: u64,
+        funding: bitcoin::TxLockFunding,
     ) -> Self {
         let b = bitcoin::SecretKey::new_random(rng);
 
@@ -120,6 +233,7 @@ impl State0 {
             punish_timelock,
             refund_address,
             min_monero_confirmations,
+            funding,
         }
     }
 
@@ -151,7 +265,14 @@ impl State0 {
             bail!("Alice's dleq proof doesn't verify")
         }
 
-        let tx_lock = bitcoin::TxLock::new(wallet, self.btc, msg.A, self.b.public()).await?;
+        let tx_lock = match &self.funding {
+            bitcoin::TxLockFunding::Wallet { utxos } => {
+                bitcoin::TxLock::new(wallet, self.btc, msg.A, self.b.public(), utxos).await?
+            }
+            bitcoin::TxLockFunding::External(funding) => {
+                bitcoin::TxLock::from_external_funding(funding, self.btc, msg.A, self.b.public())?
+            }
+        };
         let v = msg.v_a + self.v_b;
 
         Ok(State1 {
@@ -252,6 +373,31 @@ pub struct State2 {
 }
 
 impl State2 {
+    /// The unsigned PSBT of the lock transaction agreed with Alice.
+    pub fn tx_lock_psbt(&self) -> PartiallySignedTransaction {
+        self.tx_lock.clone().into()
+    }
+
+    /// Validates the PSBT of the lock transaction after it was signed by an
+    /// external wallet and extracts the signed transaction.
+    pub fn signed_tx_lock(&self, psbt: PartiallySignedTransaction) -> Result<Transaction> {
+        let tx_lock =
+            bitcoin::TxLock::from_psbt(psbt, self.A, self.b.public(), self.tx_lock.lock_amount())?;
+        let transaction = tx_lock.finalized_tx()?;
+
+        // Alice signed TxCancel spending the output of the agreed transaction,
+        // hence the signed transaction must have the same txid
+        if transaction.txid() != self.tx_lock.txid() {
+            bail!(
+                "Signed lock transaction {} differs from the agreed lock transaction {}, only segwit inputs can be used and the transaction must not be modified",
+                transaction.txid(),
+                self.tx_lock.txid()
+            )
+        }
+
+        Ok(transaction)
+    }
+
     pub fn next_message(&self) -> Message4 {
         let tx_cancel = TxCancel::new(&self.tx_lock, self.cancel_timelock, self.A, self.b.public());
         let tx_cancel_sig = self.b.sign(tx_cancel.digest());
@@ -375,6 +521,23 @@ impl State3 {
             tx_cancel_status,
         ))
     }
+
+    pub async fn timelock_status(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+    ) -> Result<TimelockStatus> {
+        let tx_cancel = TxCancel::new(&self.tx_lock, self.cancel_timelock, self.A, self.b.public());
+
+        let tx_lock_status = bitcoin_wallet.status_of_script(&self.tx_lock).await?;
+        let tx_cancel_status = bitcoin_wallet.status_of_script(&tx_cancel).await?;
+
+        Ok(timelock_status(
+            self.cancel_timelock,
+            self.punish_timelock,
+            tx_lock_status,
+            tx_cancel_status,
+        ))
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
@@ -402,7 +565,6 @@ impl State4 {
 
     pub async fn watch_for_redeem_btc(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<State5> {
         let tx_redeem = bitcoin::TxRedeem::new(&self.tx_lock, &self.redeem_address);
-        let tx_redeem_encsig = self.b.encsign(self.S_a_bitcoin, tx_redeem.digest());
 
         bitcoin_wallet
             .subscribe_to(tx_redeem.clone())
@@ -412,6 +574,33 @@ impl State4 {
 
         let tx_redeem_candidate = bitcoin_wallet.get_raw_transaction(tx_redeem.txid()).await?;
 
+        self.state5_from_tx_redeem(&tx_redeem, tx_redeem_candidate)
+    }
+
+    /// Like [`State4::watch_for_redeem_btc`] but returns `None` instead of
+    /// waiting if Alice has not redeemed the Bitcoin yet.
+    pub async fn check_for_tx_redeem(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+    ) -> Result<Option<State5>> {
+        let tx_redeem = bitcoin::TxRedeem::new(&self.tx_lock, &self.redeem_address);
+
+        let tx_redeem_candidate = match bitcoin_wallet.get_raw_transaction(tx_redeem.txid()).await {
+            Ok(tx_redeem_candidate) => tx_redeem_candidate,
+            Err(_) => return Ok(None),
+        };
+
+        Ok(Some(
+            self.state5_from_tx_redeem(&tx_redeem, tx_redeem_candidate)?,
+        ))
+    }
+
+    fn state5_from_tx_redeem(
+        &self,
+        tx_redeem: &bitcoin::TxRedeem,
+        tx_redeem_candidate: Transaction,
+    ) -> Result<State5> {
+        let tx_redeem_encsig = self.b.encsign(self.S_a_bitcoin, tx_redeem.digest());
         let tx_redeem_sig =
             tx_redeem.extract_signature_by_key(tx_redeem_candidate, self.b.public())?;
         let s_a = bitcoin::recover(self.S_a_bitcoin, tx_redeem_sig, tx_redeem_encsig)?;
@@ -443,6 +632,23 @@ impl State4 {
         ))
     }
 
+    pub async fn timelock_status(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+    ) -> Result<TimelockStatus> {
+        let tx_cancel = TxCancel::new(&self.tx_lock, self.cancel_timelock, self.A, self.b.public());
+
+        let tx_lock_status = bitcoin_wallet.status_of_script(&self.tx_lock).await?;
+        let tx_cancel_status = bitcoin_wallet.status_of_script(&tx_cancel).await?;
+
+        Ok(timelock_status(
+            self.cancel_timelock,
+            self.punish_timelock,
+            tx_lock_status,
+            tx_cancel_status,
+        ))
+    }
+
     pub fn cancel(self) -> State6 {
         State6 {
             A: self.A,
@@ -512,6 +718,23 @@ impl State6 {
         ))
     }
 
+    pub async fn timelock_status(
+        &self,
+        bitcoin_wallet: &bitcoin::Wallet,
+    ) -> Result<TimelockStatus> {
+        let tx_cancel = TxCancel::new(&self.tx_lock, self.cancel_timelock, self.A, self.b.public());
+
+        let tx_lock_status = bitcoin_wallet.status_of_script(&self.tx_lock).await?;
+        let tx_cancel_status = bitcoin_wallet.status_of_script(&tx_cancel).await?;
+
+        Ok(timelock_status(
+            self.cancel_timelock,
+            self.punish_timelock,
+            tx_lock_status,
+            tx_cancel_status,
+        ))
+    }
+
     pub async fn check_for_tx_cancel(
         &self,
         bitcoin_wallet: &bitcoin::Wallet,
@@ -525,10 +748,7 @@ impl State6 {
     }
 
     pub async fn submit_tx_cancel(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<Txid> {
-        let transaction =
-            bitcoin::TxCancel::new(&self.tx_lock, self.cancel_timelock, self.A, self.b.public())
-                .complete_as_bob(self.A, self.b.clone(), self.tx_cancel_sig_a.clone())
-                .context("Failed to complete Bitcoin cancel transaction")?;
+        let transaction = self.signed_tx_cancel()?;
 
         let (tx_id, _) = bitcoin_wallet.broadcast(transaction, "cancel").await?;
 
@@ -536,6 +756,48 @@ impl State6 {
     }
 
     pub async fn refund_btc(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<()> {
+        let signed_tx_refund = self.signed_tx_refund()?;
+
+        let (_, subscription) = bitcoin_wallet.broadcast(signed_tx_refund, "refund").await?;
+
+        subscription.wait_until_final().await?;
+
+        Ok(())
+    }
+
+    /// Hands the signed cancel and, if `with_refund`, refund transactions to a
+    /// watchtower, which can then cancel the swap and refund the Bitcoin
+    /// without Bob being online.
+    ///
+    /// See [`BobState::watch_job_with_refund`] for when the refund transaction
+    /// may be handed out.
+    fn watch_job(&self, swap_id: Uuid, with_refund: bool) -> Result<WatchJob> {
+        let tx_refund = match with_refund {
+            true => Some(self.signed_tx_refund()?),
+            false => None,
+        };
+
+        Ok(WatchJob {
+            swap_id,
+            tx_lock: WatchedTx::new(&self.tx_lock),
+            cancel_timelock: self.cancel_timelock,
+            punish_timelock: self.punish_timelock,
+            tx_cancel: self.signed_tx_cancel()?,
+            tx_refund,
+        })
+    }
+
+    pub fn tx_cancel(&self) -> bitcoin::TxCancel {
+        bitcoin::TxCancel::new(&self.tx_lock, self.cancel_timelock, self.A, self.b.public())
+    }
+
+    fn signed_tx_cancel(&self) -> Result<Transaction> {
+        bitcoin::TxCancel::new(&self.tx_lock, self.cancel_timelock, self.A, self.b.public())
+            .complete_as_bob(self.A, self.b.clone(), self.tx_cancel_sig_a.clone())
+            .context("Failed to complete Bitcoin cancel transaction")
+    }
+
+    fn signed_tx_refund(&self) -> Result<Transaction> {
         let tx_cancel =
             bitcoin::TxCancel::new(&self.tx_lock, self.cancel_timelock, self.A, self.b.public());
         let tx_refund = bitcoin::TxRefund::new(&tx_cancel, &self.refund_address);
@@ -546,17 +808,163 @@ impl State6 {
         let sig_a =
             adaptor.decrypt_signature(&self.s_b.to_secpfun_scalar(), self.tx_refund_encsig.clone());
 
-        let signed_tx_refund =
-            tx_refund.add_signatures((self.A, sig_a), (self.b.public(), sig_b))?;
+        tx_refund.add_signatures((self.A, sig_a), (self.b.public(), sig_b))
+    }
 
-        let (_, subscription) = bitcoin_wallet.broadcast(signed_tx_refund, "refund").await?;
+    pub fn tx_lock_id(&self) -> bitcoin::Txid {
+        self.tx_lock.txid()
+    }
 
-        subscription.wait_until_final().await?;
+    pub fn tx_cancel_id(&self) -> bitcoin::Txid {
+        bitcoin::TxCancel::new(&self.tx_lock, self.cancel_timelock, self.A, self.b.public()).txid()
+    }
 
-        Ok(())
+    pub fn tx_refund_id(&self) -> bitcoin::Txid {
+        let tx_cancel =
+            bitcoin::TxCancel::new(&self.tx_lock, self.cancel_timelock, self.A, self.b.public());
+
+        bitcoin::TxRefund::new(&tx_cancel, &self.refund_address).txid()
     }
+}
 
-    pub fn tx_lock_id(&self) -> bitcoin::Txid {
-        self.tx_lock.txid()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::{Address, Amount, TxRefund, Wallet};
+    use rand::rngs::OsRng;
+
+    const FINALITY: u32 = 3;
+
+    #[tokio::test]
+    async fn watch_job_only_contains_cancel() {
+        let (state4, _) = state4().await;
+
+        let states = vec![
+            BobState::XmrLocked(state4.clone()),
+            BobState::EncSigSent(state4.clone()),
+            BobState::CancelTimelockExpired(state4.clone().cancel()),
+            BobState::BtcCancelled(state4.cancel()),
+        ];
+
+        for state in states {
+            let job = state.watch_job(Uuid::new_v4()).unwrap();
+
+            assert_eq!(job.tx_refund, None, "refund handed out in state {}", state);
+            assert!(job.validate().is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn no_refund_before_cancel_is_final() {
+        let (state4, _) = state4().await;
+        let state = BobState::BtcCancelled(state4.cancel());
+
+        for tx_cancel in vec![
+            ScriptStatus::Unseen,
+            ScriptStatus::InMempool,
+            ScriptStatus::from_confirmations(FINALITY - 1),
+        ] {
+            assert!(
+                state
+                    .watch_job_with_refund(Uuid::new_v4(), tx_cancel, FINALITY)
+                    .is_err(),
+                "refund handed out with cancel {}",
+                tx_cancel
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn refund_of_watch_job_reveals_s_b() {
+        let (state4, alice) = state4().await;
+        let tx_cancel = TxCancel::new(
+            &state4.tx_lock,
+            state4.cancel_timelock,
+            state4.A,
+            state4.b.public(),
+        );
+        let tx_refund = TxRefund::new(&tx_cancel, &state4.refund_address);
+
+        let job = BobState::BtcCancelled(state4.cancel())
+            .watch_job_with_refund(
+                Uuid::new_v4(),
+                ScriptStatus::from_confirmations(FINALITY),
+                FINALITY,
+            )
+            .unwrap();
+        let spend_key = tx_refund
+            .extract_monero_private_key(
+                job.tx_refund.unwrap(),
+                alice.s_a,
+                alice.a,
+                alice.S_b_bitcoin,
+            )
+            .unwrap();
+
+        assert_eq!(
+            spend_key,
+            monero::PrivateKey { scalar: alice.s_a } + monero::PrivateKey { scalar: state4.s_b }
+        );
+    }
+
+    struct Alice {
+        a: bitcoin::SecretKey,
+        s_a: monero::Scalar,
+        S_b_bitcoin: bitcoin::PublicKey,
+    }
+
+    /// Bob's state after Alice locked the Monero, with the signatures Alice
+    /// hands out during the execution setup.
+    async fn state4() -> (State4, Alice) {
+        let a = bitcoin::SecretKey::new_random(&mut OsRng);
+        let b = bitcoin::SecretKey::new_random(&mut OsRng);
+        let s_a = monero::Scalar::random(&mut OsRng);
+        let s_b = monero::Scalar::random(&mut OsRng);
+        let S_b_bitcoin = bitcoin::PublicKey::from(s_b.to_secpfun_scalar());
+        let cancel_timelock = CancelTimelock::new(12);
+        let refund_address = address();
+
+        let wallet = Wallet::new_funded(50_000);
+        let tx_lock = TxLock::new(
+            &wallet,
+            Amount::from_sat(10_000),
+            a.public(),
+            b.public(),
+            &[],
+        )
+        .await
+        .unwrap();
+        let tx_cancel = TxCancel::new(&tx_lock, cancel_timelock, a.public(), b.public());
+        let tx_refund = TxRefund::new(&tx_cancel, &refund_address);
+
+        let state4 = State4 {
+            A: a.public(),
+            b,
+            s_b,
+            S_a_bitcoin: bitcoin::PublicKey::random(),
+            v: monero::PrivateViewKey::new_random(&mut OsRng),
+            cancel_timelock,
+            punish_timelock: PunishTimelock::new(6),
+            refund_address,
+            redeem_address: address(),
+            tx_lock,
+            tx_cancel_sig_a: a.sign(tx_cancel.digest()),
+            tx_refund_encsig: a.encsign(S_b_bitcoin, tx_refund.digest()),
+            monero_wallet_restore_blockheight: BlockHeight { height: 0 },
+        };
+
+        (state4, Alice {
+            a,
+            s_a,
+            S_b_bitcoin,
+        })
+    }
+
+    fn address() -> Address {
+        Address::p2wpkh(
+            &bitcoin::PublicKey::random().into(),
+            ::bitcoin::Network::Testnet,
+        )
+        .unwrap()
     }
 }
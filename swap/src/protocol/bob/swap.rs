@@ -1,13 +1,16 @@
 use crate::bitcoin::ExpiredTimelocks;
 use crate::database::Swap;
 use crate::env::Config;
-use crate::protocol::bob;
+use crate::notification::{Notification, Role, SwapUpdate};
 use crate::protocol::bob::event_loop::EventLoopHandle;
 use crate::protocol::bob::state::*;
+use crate::protocol::bob::TxLockSigner;
+use crate::protocol::{bob, watchdog};
 use crate::{bitcoin, monero};
 use anyhow::{bail, Context, Result};
 use rand::rngs::OsRng;
 use tokio::select;
+use tokio::sync::watch;
 use uuid::Uuid;
 
 pub fn is_complete(state: &BobState) -> bool {
@@ -31,8 +34,26 @@ pub async fn run_until(
 ) -> Result<BobState> {
     let mut current_state = swap.state;
 
+    swap.notifier
+        .notify_in_background(Notification::SwapStarted(swap_update(
+            swap.id,
+            &current_state,
+        )));
+
+    // Stops watching the deadline once dropped, i.e. when the swap returns
+    let (state_sender, state_receiver) = watch::channel(current_state.clone());
+    tokio::spawn(watchdog::monitor(
+        swap.id,
+        state_receiver,
+        swap.bitcoin_wallet.clone(),
+        swap.notifier.clone(),
+        watchdog::CHECK_INTERVAL,
+    ));
+
     while !is_target_state(&current_state) {
-        current_state = next_state(
+        let update = swap_update(swap.id, &current_state);
+
+        current_state = match next_state(
             swap.id,
             current_state,
             &mut swap.event_loop_handle,
@@ -40,18 +61,70 @@ pub async fn run_until(
             swap.monero_wallet.as_ref(),
             &swap.env_config,
             swap.receive_monero_address,
+            &swap.tx_lock_signer,
         )
-        .await?;
+        .await
+        {
+            Ok(state) => state,
+            Err(error) => {
+                swap.notifier
+                    .notify(Notification::SwapFailed {
+                        update,
+                        error: format!("{:#}", error),
+                    })
+                    .await;
+                return Err(error);
+            }
+        };
 
         let db_state = current_state.clone().into();
-        swap.db
+        if let Err(error) = swap
+            .db
             .insert_latest_state(swap.id, Swap::Bob(db_state))
-            .await?;
+            .await
+        {
+            swap.notifier
+                .notify(Notification::SwapFailed {
+                    update: swap_update(swap.id, &current_state),
+                    error: format!("{:#}", error),
+                })
+                .await;
+            return Err(error);
+        }
+        let _ = state_sender.send(current_state.clone());
+
+        // Don't delay the swap for the hooks, unless the process may exit next
+        let notification = notification(swap.id, &current_state);
+        if is_target_state(&current_state) {
+            swap.notifier.notify(notification).await;
+        } else {
+            swap.notifier.notify_in_background(notification);
+        }
     }
 
     Ok(current_state)
 }
 
+fn notification(swap_id: Uuid, state: &BobState) -> Notification {
+    let update = swap_update(swap_id, state);
+
+    match state {
+        state if is_complete(state) => Notification::SwapFinished(update),
+        BobState::CancelTimelockExpired(..) => Notification::SwapTimelockExpired(update),
+        _ => Notification::SwapStateChanged(update),
+    }
+}
+
+fn swap_update(swap_id: Uuid, state: &BobState) -> SwapUpdate {
+    SwapUpdate {
+        swap_id,
+        role: Role::Bob,
+        state: state.to_string(),
+        txids: state.txids(),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn next_state(
     swap_id: Uuid,
     state: BobState,
@@ -60,12 +133,20 @@ async fn next_state(
     monero_wallet: &monero::Wallet,
     env_config: &Config,
     receive_monero_address: monero::Address,
+    tx_lock_signer: &TxLockSigner,
 ) -> Result<BobState> {
     tracing::trace!("Current state: {}", state);
 
     Ok(match state {
-        BobState::Started { btc_amount } => {
-            let bitcoin_refund_address = bitcoin_wallet.new_address().await?;
+        BobState::Started {
+            btc_amount,
+            funding,
+            refund_address,
+        } => {
+            let bitcoin_refund_address = match refund_address {
+                Some(refund_address) => refund_address,
+                None => bitcoin_wallet.new_address().await?,
+            };
 
             let state2 = request_price_and_setup(
                 swap_id,
@@ -73,6 +154,7 @@ async fn next_state(
                 event_loop_handle,
                 env_config,
                 bitcoin_refund_address,
+                funding,
             )
             .await?;
 
@@ -80,11 +162,18 @@ async fn next_state(
         }
         BobState::ExecutionSetupDone(state2) => {
             // Alice and Bob have exchanged info
-            let (state3, tx_lock) = state2.lock_btc().await?;
-            let signed_tx = bitcoin_wallet
-                .sign_and_finalize(tx_lock.clone().into())
+            // Alice aborts if TxLock is not confirmed within the timeout, so leave time for
+            // the confirmation
+            let signed_tx = tx_lock_signer
+                .sign(
+                    swap_id,
+                    &state2,
+                    bitcoin_wallet,
+                    env_config.bitcoin_lock_confirmed_timeout / 2,
+                )
                 .await
                 .context("Failed to sign Bitcoin lock transaction")?;
+            let (state3, _) = state2.lock_btc().await?;
             let (..) = bitcoin_wallet.broadcast(signed_tx, "lock").await?;
 
             BobState::BtcLocked(state3)
@@ -268,6 +357,7 @@ pub async fn request_price_and_setup(
     event_loop_handle: &mut EventLoopHandle,
     env_config: &Config,
     bitcoin_refund_address: bitcoin::Address,
+    funding: bitcoin::TxLockFunding,
 ) -> Result<bob::state::State2> {
     let xmr = event_loop_handle.request_spot_price(btc).await?;
 
@@ -282,6 +372,7 @@ pub async fn request_price_and_setup(
         env_config.bitcoin_punish_timelock,
         bitcoin_refund_address,
         env_config.monero_finality_confirmations,
+        funding,
     );
 
     let state2 = event_loop_handle.execution_setup(state0).await?;
@@ -0,0 +1,286 @@
+use crate::bitcoin::{self, Address, Amount, PartiallySignedTransaction, Transaction};
+use crate::protocol::bob::State2;
+use ::bitcoin::consensus::encode;
+use ::bitcoin::OutPoint;
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How often the directory is checked for the signed PSBT.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Determines who signs Bob's lock transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TxLockSigner {
+    /// The internal wallet of the CLI.
+    Wallet,
+    /// An external wallet, e.g. a hardware wallet, that signs the inputs of an
+    /// externally funded lock transaction.
+    ///
+    /// The unsigned PSBT is exported to `dir` and the swap waits until the
+    /// signed PSBT is saved next to it.
+    External { dir: PathBuf },
+}
+
+impl Default for TxLockSigner {
+    fn default() -> Self {
+        TxLockSigner::Wallet
+    }
+}
+
+impl TxLockSigner {
+    /// Signs the lock transaction, waiting at most `timeout` for an external
+    /// wallet.
+    pub async fn sign(
+        &self,
+        swap_id: Uuid,
+        state2: &State2,
+        bitcoin_wallet: &bitcoin::Wallet,
+        timeout: Duration,
+    ) -> Result<Transaction> {
+        match self {
+            TxLockSigner::Wallet => {
+                bitcoin_wallet
+                    .sign_and_finalize(state2.tx_lock_psbt())
+                    .await
+            }
+            TxLockSigner::External { dir } => {
+                let unsigned_path = dir.join(format!("{}-tx_lock.psbt", swap_id));
+
+                let wallet_outpoints = bitcoin_wallet
+                    .list_utxos()
+                    .await?
+                    .into_iter()
+                    .map(|utxo| utxo.outpoint)
+                    .collect::<Vec<_>>();
+                if let Some(outpoint) =
+                    spent_wallet_outpoint(&state2.tx_lock_psbt(), &wallet_outpoints)
+                {
+                    bail!(
+                        "The lock transaction spends {} of the internal wallet which an external wallet cannot sign, fund the swap with --fund-from to sign it externally",
+                        outpoint
+                    );
+                }
+
+                show_payment_request(&state2.tx_lock_psbt(), bitcoin_wallet.get_network());
+
+                match tokio::time::timeout(timeout, sign_externally(swap_id, state2, dir)).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        // Don't let the user sign a transaction that is not going to be used
+                        let _ = tokio::fs::remove_file(&unsigned_path).await;
+
+                        Err(anyhow!(
+                            "The signed PSBT was not saved within {:?}, the seller is about to abort the swap",
+                            timeout
+                        ))
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn sign_externally(swap_id: Uuid, state2: &State2, dir: &Path) -> Result<Transaction> {
+    let unsigned_path = dir.join(format!("{}-tx_lock.psbt", swap_id));
+    let signed_path = dir.join(format!("{}-tx_lock-signed.psbt", swap_id));
+
+    tokio::fs::create_dir_all(dir)
+        .await
+        .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+    tokio::fs::write(
+        &unsigned_path,
+        base64::encode(encode::serialize(&state2.tx_lock_psbt())),
+    )
+    .await
+    .with_context(|| format!("Failed to write PSBT to {}", unsigned_path.display()))?;
+
+    tracing::info!(
+        "Sign the Bitcoin lock transaction in {} with your wallet and save the finalized PSBT as {}",
+        unsigned_path.display(),
+        signed_path.display()
+    );
+
+    let mut rejected = None;
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        let contents = match tokio::fs::read(&signed_path).await {
+            Ok(contents) => contents,
+            Err(_) => continue,
+        };
+
+        // Don't complain about the same file over and over again
+        if rejected.as_ref() == Some(&contents) {
+            continue;
+        }
+
+        match parse_psbt(&contents).and_then(|psbt| state2.signed_tx_lock(psbt)) {
+            Ok(transaction) => {
+                tracing::info!(txid = %transaction.txid(), "Imported signed Bitcoin lock transaction");
+                return Ok(transaction);
+            }
+            Err(e) => {
+                tracing::warn!("Rejecting signed PSBT {}: {:#}", signed_path.display(), e);
+                rejected = Some(contents);
+            }
+        }
+    }
+}
+
+/// Shows what the external wallet is asked to sign, so the user can compare
+/// it with what the wallet displays.
+fn show_payment_request(psbt: &PartiallySignedTransaction, network: bitcoin::Network) {
+    let transaction = &psbt.global.unsigned_tx;
+
+    for input in transaction.input.iter() {
+        tracing::info!(outpoint = %input.previous_output, "The lock transaction spends");
+    }
+
+    for output in transaction.output.iter() {
+        let amount = Amount::from_sat(output.value);
+
+        match Address::from_script(&output.script_pubkey, network) {
+            Some(address) => tracing::info!(%amount, %address, "The lock transaction pays"),
+            None => {
+                tracing::info!(%amount, script = %output.script_pubkey, "The lock transaction pays")
+            }
+        }
+    }
+
+    match tx_lock_fee(psbt) {
+        Some(fee) => tracing::info!(%fee, "The lock transaction pays a fee"),
+        None => {
+            tracing::warn!("The fee of the lock transaction is unknown, check it in your wallet")
+        }
+    }
+}
+
+/// The first input of the lock transaction that spends an output of the
+/// internal wallet, if any.
+fn spent_wallet_outpoint(
+    psbt: &PartiallySignedTransaction,
+    wallet_outpoints: &[OutPoint],
+) -> Option<OutPoint> {
+    psbt.global
+        .unsigned_tx
+        .input
+        .iter()
+        .map(|input| input.previous_output)
+        .find(|outpoint| wallet_outpoints.contains(outpoint))
+}
+
+/// The fee of the lock transaction, if the values of all its inputs are
+/// known.
+fn tx_lock_fee(psbt: &PartiallySignedTransaction) -> Option<Amount> {
+    let inputs = psbt
+        .inputs
+        .iter()
+        .map(|input| input.witness_utxo.as_ref().map(|utxo| utxo.value))
+        .sum::<Option<u64>>()?;
+    let outputs = psbt
+        .global
+        .unsigned_tx
+        .output
+        .iter()
+        .map(|output| output.value)
+        .sum::<u64>();
+
+    inputs.checked_sub(outputs).map(Amount::from_sat)
+}
+
+/// Parses a PSBT given either in base64 or in binary encoding.
+fn parse_psbt(contents: &[u8]) -> Result<PartiallySignedTransaction> {
+    let base64 = String::from_utf8_lossy(contents);
+
+    let bytes = match base64::decode(base64.trim()) {
+        Ok(bytes) => bytes,
+        Err(_) => contents.to_vec(),
+    };
+
+    encode::deserialize(&bytes).context("Failed to deserialize PSBT")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::bitcoin::{TxIn, TxOut};
+
+    fn psbt() -> PartiallySignedTransaction {
+        PartiallySignedTransaction::from_unsigned_tx(Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::default(),
+                script_sig: Default::default(),
+                sequence: 0xFFFF_FFFF,
+                witness: Vec::new(),
+            }],
+            output: vec![TxOut {
+                value: 10_000,
+                script_pubkey: Default::default(),
+            }],
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn detects_inputs_of_the_internal_wallet() {
+        let psbt = psbt();
+
+        assert_eq!(
+            spent_wallet_outpoint(&psbt, &[OutPoint::default()]),
+            Some(OutPoint::default())
+        );
+    }
+
+    #[test]
+    fn accepts_inputs_the_internal_wallet_does_not_own() {
+        let psbt = psbt();
+        let other = OutPoint {
+            txid: Default::default(),
+            vout: 1,
+        };
+
+        assert_eq!(spent_wallet_outpoint(&psbt, &[other]), None);
+    }
+
+    #[test]
+    fn fee_is_inputs_minus_outputs() {
+        let mut psbt = psbt();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: 10_153,
+            script_pubkey: Default::default(),
+        });
+
+        assert_eq!(tx_lock_fee(&psbt), Some(Amount::from_sat(153)));
+    }
+
+    #[test]
+    fn fee_is_unknown_without_input_values() {
+        assert_eq!(tx_lock_fee(&psbt()), None);
+    }
+
+    #[test]
+    fn parses_base64_psbt() {
+        let psbt = psbt();
+        let contents = format!("{}\n", base64::encode(encode::serialize(&psbt)));
+
+        assert_eq!(parse_psbt(contents.as_bytes()).unwrap(), psbt);
+    }
+
+    #[test]
+    fn parses_binary_psbt() {
+        let psbt = psbt();
+        let contents = encode::serialize(&psbt);
+
+        assert_eq!(parse_psbt(&contents).unwrap(), psbt);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(parse_psbt(b"not a psbt").is_err());
+    }
+}
@@ -0,0 +1,382 @@
+//! Watches the timelocks of all unfinished swaps in the database and warns
+//! before a deadline is missed.
+//!
+//! A swap that is not resumed in time can lose funds: Bob has to refund
+//! before the punish timelock expires, Alice has to refund or punish once the
+//! cancel timelock expired. The [`Urgency`] of a swap escalates as these
+//! deadlines approach.
+
+use crate::bitcoin::{self, ExpiredTimelocks, TimelockStatus};
+use crate::database::{Database, Swap};
+use crate::notification::{Notification, Notifier, Role, SwapUpdate, Urgency};
+use crate::protocol::alice::{self, AliceState};
+use crate::protocol::bob::{self, BobState};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use uuid::Uuid;
+
+/// Warn this many blocks before the cancel timelock expires.
+pub const WARNING_BLOCKS: u32 = 6;
+
+/// How often the timelocks are checked while a binary is running.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// The timelock status of an unfinished swap.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Deadline {
+    pub update: SwapUpdate,
+    pub status: TimelockStatus,
+    pub urgency: Urgency,
+}
+
+impl Deadline {
+    pub fn notification(&self) -> Notification {
+        Notification::SwapDeadline {
+            update: self.update.clone(),
+            urgency: self.urgency,
+            blocks_left: self.status.blocks_left,
+        }
+    }
+
+    /// Notifies if the urgency escalated since the last notification about
+    /// this swap.
+    pub async fn notify(&self, notifier: &Notifier) {
+        notifier
+            .notify_deadline(self.update.clone(), self.urgency, self.status.blocks_left)
+            .await
+    }
+}
+
+/// A swap state whose timelocks can be watched.
+#[async_trait]
+pub trait Watched {
+    /// The deadline of the swap, if its timelocks have to be watched in this
+    /// state.
+    async fn deadline(
+        &self,
+        swap_id: Uuid,
+        bitcoin_wallet: &bitcoin::Wallet,
+    ) -> Result<Option<Deadline>>;
+}
+
+#[async_trait]
+impl Watched for AliceState {
+    async fn deadline(
+        &self,
+        swap_id: Uuid,
+        bitcoin_wallet: &bitcoin::Wallet,
+    ) -> Result<Option<Deadline>> {
+        let state3 = match watched_alice_state(self) {
+            Some(state3) => state3,
+            None => return Ok(None),
+        };
+        let status = state3.timelock_status(bitcoin_wallet).await?;
+
+        Ok(Some(Deadline {
+            update: SwapUpdate {
+                swap_id,
+                role: Role::Alice,
+                state: self.to_string(),
+                txids: self.txids(),
+            },
+            status,
+            urgency: urgency(Role::Alice, status),
+        }))
+    }
+}
+
+#[async_trait]
+impl Watched for BobState {
+    async fn deadline(
+        &self,
+        swap_id: Uuid,
+        bitcoin_wallet: &bitcoin::Wallet,
+    ) -> Result<Option<Deadline>> {
+        let status = match bob_timelock_status(self, bitcoin_wallet).await? {
+            Some(status) => status,
+            None => return Ok(None),
+        };
+
+        Ok(Some(Deadline {
+            update: SwapUpdate {
+                swap_id,
+                role: Role::Bob,
+                state: self.to_string(),
+                txids: self.txids(),
+            },
+            status,
+            urgency: urgency(Role::Bob, status),
+        }))
+    }
+}
+
+/// Computes the deadlines of all unfinished swaps of `role` whose Bitcoin are
+/// locked.
+///
+/// Swaps whose timelocks cannot be checked are logged and skipped.
+pub async fn deadlines(
+    role: Role,
+    db: &Database,
+    bitcoin_wallet: &bitcoin::Wallet,
+) -> Result<Vec<Deadline>> {
+    let mut deadlines = Vec::new();
+
+    match role {
+        Role::Alice => {
+            for (swap_id, state) in db.unfinished_alice()? {
+                let state = AliceState::from(state);
+
+                match state.deadline(swap_id, bitcoin_wallet).await {
+                    Ok(Some(deadline)) => deadlines.push(deadline),
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!(%swap_id, "Failed to check timelocks: {:#}", e),
+                }
+            }
+        }
+        Role::Bob => {
+            for (swap_id, state) in db.unfinished_bob()? {
+                let state = BobState::from(state);
+
+                match state.deadline(swap_id, bitcoin_wallet).await {
+                    Ok(Some(deadline)) => deadlines.push(deadline),
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!(%swap_id, "Failed to check timelocks: {:#}", e),
+                }
+            }
+        }
+    }
+
+    Ok(deadlines)
+}
+
+/// Checks the deadlines every `interval` and notifies whenever the urgency of
+/// a swap escalates.
+///
+/// Only warns, resuming the swap or running `check --refund` is left to the
+/// user to not interfere with a swap that is currently running.
+pub async fn run(
+    role: Role,
+    db: Arc<Database>,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    notifier: Notifier,
+    interval: Duration,
+) {
+    loop {
+        match deadlines(role, db.as_ref(), bitcoin_wallet.as_ref()).await {
+            Ok(deadlines) => {
+                for deadline in deadlines {
+                    log(&deadline);
+                    deadline.notify(&notifier).await;
+                }
+            }
+            Err(e) => tracing::warn!("Failed to check timelocks of unfinished swaps: {:#}", e),
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Watches the deadline of a running swap, checking it every `interval` and
+/// whenever the swap transitions into a new state, until the sender of `state`
+/// is dropped.
+///
+/// Notifies as soon as the urgency escalates, in particular while the cancel
+/// timelock is about to expire and the swap is still waiting for the other
+/// party.
+pub async fn monitor<S>(
+    swap_id: Uuid,
+    mut state: watch::Receiver<S>,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    notifier: Notifier,
+    interval: Duration,
+) where
+    S: Watched + Clone + Send + Sync,
+{
+    loop {
+        let current_state = state.borrow().clone();
+
+        match current_state
+            .deadline(swap_id, bitcoin_wallet.as_ref())
+            .await
+        {
+            Ok(Some(deadline)) => {
+                log(&deadline);
+                deadline.notify(&notifier).await;
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!(%swap_id, "Failed to check timelocks: {:#}", e),
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            changed = state.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Cancels the swap if necessary and refunds Bob's Bitcoin.
+///
+/// If Alice already redeemed the Bitcoin, the swap is moved on to redeeming
+/// the Monero instead, which happens once it is resumed. Fails if the cancel
+/// timelock has not expired yet. Returns the new state of the swap.
+pub async fn cancel_and_refund(
+    swap_id: Uuid,
+    state: BobState,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    db: Arc<Database>,
+) -> Result<BobState> {
+    if let BobState::EncSigSent(state4) = &state {
+        if let Some(state5) = state4.check_for_tx_redeem(bitcoin_wallet.as_ref()).await? {
+            tracing::info!(%swap_id, "Alice redeemed the Bitcoin, resume the swap to redeem the Monero");
+
+            let state = BobState::BtcRedeemed(state5);
+            db.insert_latest_state(swap_id, Swap::Bob(state.clone().into()))
+                .await?;
+
+            return Ok(state);
+        }
+    }
+
+    let state = match state {
+        BobState::BtcCancelled(..) => state,
+        state => {
+            match bob::cancel(swap_id, state, bitcoin_wallet.clone(), db.clone(), false).await? {
+                Ok((txid, state)) => {
+                    tracing::info!(%swap_id, %txid, "Cancelled swap");
+                    state
+                }
+                Err(e) => bail!("Cannot refund swap {}: {}", swap_id, e),
+            }
+        }
+    };
+
+    match bob::refund(swap_id, state, bitcoin_wallet, db, false).await? {
+        Ok(state) => {
+            tracing::info!(%swap_id, "Refunded Bitcoin");
+            Ok(state)
+        }
+        Err(e) => bail!(e),
+    }
+}
+
+/// The states in which Alice has locked her Monero and has to act once the
+/// cancel timelock expired.
+fn watched_alice_state(state: &AliceState) -> Option<&alice::State3> {
+    match state {
+        AliceState::XmrLockTransactionSent { state3, .. }
+        | AliceState::XmrLocked { state3, .. }
+        | AliceState::XmrLockTransferProofSent { state3, .. }
+        | AliceState::EncSigLearned { state3, .. }
+        | AliceState::CancelTimelockExpired { state3, .. }
+        | AliceState::BtcCancelled { state3, .. }
+        | AliceState::BtcPunishable { state3, .. } => Some(state3.as_ref()),
+        _ => None,
+    }
+}
+
+/// The timelock status of the states in which Bob has locked his Bitcoin and
+/// has to refund once the cancel timelock expired.
+async fn bob_timelock_status(
+    state: &BobState,
+    bitcoin_wallet: &bitcoin::Wallet,
+) -> Result<Option<TimelockStatus>> {
+    let status = match state {
+        BobState::BtcLocked(state3) | BobState::XmrLockProofReceived { state: state3, .. } => {
+            state3.timelock_status(bitcoin_wallet).await?
+        }
+        BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => {
+            state4.timelock_status(bitcoin_wallet).await?
+        }
+        BobState::CancelTimelockExpired(state6) | BobState::BtcCancelled(state6) => {
+            state6.timelock_status(bitcoin_wallet).await?
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(status))
+}
+
+fn urgency(role: Role, status: TimelockStatus) -> Urgency {
+    match (status.epoch, role) {
+        (ExpiredTimelocks::None, _) => match status.blocks_left {
+            Some(blocks_left) if blocks_left <= WARNING_BLOCKS => Urgency::Warning,
+            _ => Urgency::None,
+        },
+        (ExpiredTimelocks::Cancel, _) => Urgency::Critical,
+        // Alice can still punish, Bob might already have lost his Bitcoin
+        (ExpiredTimelocks::Punish, Role::Alice) => Urgency::Critical,
+        (ExpiredTimelocks::Punish, Role::Bob) => Urgency::Expired,
+    }
+}
+
+fn log(deadline: &Deadline) {
+    let swap_id = deadline.update.swap_id;
+    let state = &deadline.update.state;
+    let blocks_left = deadline.status.blocks_left;
+
+    match deadline.urgency {
+        Urgency::None => {
+            tracing::debug!(%swap_id, %state, ?blocks_left, "Timelock deadline is far away")
+        }
+        Urgency::Warning => tracing::warn!(
+            %swap_id,
+            %state,
+            ?blocks_left,
+            "Cancel timelock expires soon, make sure the swap is running"
+        ),
+        Urgency::Critical => tracing::error!(
+            %swap_id,
+            %state,
+            ?blocks_left,
+            "Cancel timelock expired, resume the swap now"
+        ),
+        Urgency::Expired => tracing::error!(
+            %swap_id,
+            %state,
+            "Punish timelock expired, the Bitcoin may be lost"
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(epoch: ExpiredTimelocks, blocks_left: Option<u32>) -> TimelockStatus {
+        TimelockStatus { epoch, blocks_left }
+    }
+
+    #[test]
+    fn warns_shortly_before_cancel_timelock_expires() {
+        assert_eq!(
+            urgency(Role::Bob, status(ExpiredTimelocks::None, Some(7))),
+            Urgency::None
+        );
+        assert_eq!(
+            urgency(
+                Role::Bob,
+                status(ExpiredTimelocks::None, Some(WARNING_BLOCKS))
+            ),
+            Urgency::Warning
+        );
+        assert_eq!(
+            urgency(Role::Bob, status(ExpiredTimelocks::Cancel, Some(30))),
+            Urgency::Critical
+        );
+    }
+
+    #[test]
+    fn expired_punish_timelock_is_only_fatal_for_bob() {
+        let punish = status(ExpiredTimelocks::Punish, None);
+
+        assert_eq!(urgency(Role::Alice, punish), Urgency::Critical);
+        assert_eq!(urgency(Role::Bob, punish), Urgency::Expired);
+    }
+}
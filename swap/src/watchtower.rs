@@ -0,0 +1,535 @@
+//! A watchtower refunds Bob's Bitcoin while his CLI is offline.
+//!
+//! Bob submits the signed cancel transaction once his Bitcoin are locked and
+//! adds the signed refund transaction once the cancel transaction is final,
+//! see [`WatchJob`]. The watchtower publishes the cancel transaction when the
+//! cancel timelock expires and the refund transaction once the cancel
+//! transaction is confirmed. The job is done once the refund is final.
+//!
+//! If Bob does not hand over the refund transaction before the punish
+//! timelock expires, or the cancel transaction cannot be published because
+//! the Bitcoin were redeemed, the job is given up.
+//!
+//! The jobs are stored in a sled database, the status of the transactions is
+//! taken from the blockchain on every check. Hence the watchtower can be
+//! restarted at any time.
+
+use crate::bitcoin::wallet::ScriptStatus;
+use crate::bitcoin::{self, Transaction};
+use crate::database::{deserialize, serialize};
+use crate::network::watchtower::{Behaviour, Message, OutEvent, Request, Response, WatchJob};
+use anyhow::{bail, Context, Result};
+use libp2p::swarm::SwarmEvent;
+use libp2p::{PeerId, Swarm};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub mod command;
+
+const JOBS_TREE_NAME: &str = "watch_jobs";
+
+/// How often the transactions of all jobs are checked.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct StoredJob {
+    /// The peer that submitted the job, only this peer can revoke it.
+    peer_id: String,
+    job: WatchJob,
+}
+
+pub struct Watchtower {
+    jobs: sled::Tree,
+}
+
+impl Watchtower {
+    pub fn open(dir: &Path) -> Result<Self> {
+        let db = sled::open(dir)
+            .with_context(|| format!("Failed to open database at {}", dir.display()))?;
+        let jobs = db.open_tree(JOBS_TREE_NAME)?;
+
+        Ok(Self { jobs })
+    }
+
+    pub async fn handle(&self, peer: PeerId, request: Request) -> Response {
+        let result = match request {
+            Request::Submit(job) => {
+                let swap_id = job.swap_id;
+                let result = self.submit(peer, *job).await;
+
+                if result.is_ok() {
+                    tracing::info!(%peer, %swap_id, "Accepted watch job");
+                }
+
+                result
+            }
+            Request::Revoke { swap_id } => {
+                let result = self.revoke(peer, swap_id).await;
+
+                if result.is_ok() {
+                    tracing::info!(%peer, %swap_id, "Revoked watch job");
+                }
+
+                result
+            }
+        };
+
+        match result {
+            Ok(()) => Response::Accepted,
+            Err(e) => {
+                tracing::warn!(%peer, "Rejected watch job request: {:#}", e);
+
+                Response::Rejected {
+                    reason: format!("{:#}", e),
+                }
+            }
+        }
+    }
+
+    pub async fn submit(&self, peer: PeerId, job: WatchJob) -> Result<()> {
+        job.validate()?;
+
+        if let Some(stored) = self.get(job.swap_id)? {
+            if stored.peer_id != peer.to_string() {
+                bail!("Swap {} is watched on behalf of another peer", job.swap_id)
+            }
+        }
+
+        let stored = StoredJob {
+            peer_id: peer.to_string(),
+            job,
+        };
+        self.jobs
+            .insert(stored.job.swap_id.as_bytes(), serialize(&stored)?)?;
+        self.jobs.flush_async().await?;
+
+        Ok(())
+    }
+
+    pub async fn revoke(&self, peer: PeerId, swap_id: Uuid) -> Result<()> {
+        match self.get(swap_id)? {
+            Some(stored) if stored.peer_id == peer.to_string() => self.remove(swap_id).await,
+            Some(_) => bail!("Swap {} is watched on behalf of another peer", swap_id),
+            None => bail!("Swap {} is not watched", swap_id),
+        }
+    }
+
+    pub fn jobs(&self) -> Result<Vec<WatchJob>> {
+        self.jobs
+            .iter()
+            .map(|item| {
+                let (_, value) = item.context("Failed to read watch job from database")?;
+                let stored = deserialize::<StoredJob>(&value)?;
+
+                Ok(stored.job)
+            })
+            .collect()
+    }
+
+    fn get(&self, swap_id: Uuid) -> Result<Option<StoredJob>> {
+        self.jobs
+            .get(swap_id.as_bytes())?
+            .map(|value| deserialize(&value))
+            .transpose()
+    }
+
+    async fn remove(&self, swap_id: Uuid) -> Result<()> {
+        self.jobs.remove(swap_id.as_bytes())?;
+        self.jobs.flush_async().await?;
+
+        Ok(())
+    }
+}
+
+/// Answers the requests of Bobs until the swarm is closed.
+pub async fn serve(mut swarm: Swarm<Behaviour>, watchtower: Arc<Watchtower>) {
+    loop {
+        match swarm.next_event().await {
+            SwarmEvent::Behaviour(OutEvent::Message {
+                peer,
+                message:
+                    Message::Request {
+                        request, channel, ..
+                    },
+            }) => {
+                let response = watchtower.handle(peer, request).await;
+
+                if swarm
+                    .behaviour_mut()
+                    .send_response(channel, response)
+                    .is_err()
+                {
+                    tracing::debug!(%peer, "Failed to respond, connection closed");
+                }
+            }
+            SwarmEvent::Behaviour(OutEvent::InboundFailure { peer, error, .. }) => {
+                tracing::debug!(%peer, "Inbound request failed: {:?}", error);
+            }
+            SwarmEvent::NewListenAddr(address) => {
+                tracing::info!(%address, "Listening");
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Sends `request` to `watchtower` and waits for the response, used by Bob.
+pub async fn send_request(
+    swarm: &mut Swarm<Behaviour>,
+    watchtower: PeerId,
+    request: Request,
+) -> Result<()> {
+    let id = swarm.behaviour_mut().send_request(&watchtower, request);
+
+    loop {
+        match swarm.next_event().await {
+            SwarmEvent::Behaviour(OutEvent::Message {
+                message:
+                    Message::Response {
+                        request_id,
+                        response,
+                    },
+                ..
+            }) if request_id == id => {
+                return match response {
+                    Response::Accepted => Ok(()),
+                    Response::Rejected { reason } => {
+                        bail!("Watchtower rejected the request: {}", reason)
+                    }
+                };
+            }
+            SwarmEvent::Behaviour(OutEvent::OutboundFailure {
+                request_id, error, ..
+            }) if request_id == id => {
+                bail!("Failed to send request to watchtower: {:?}", error)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Checks the transactions of all jobs every `interval` and publishes the
+/// cancel and refund transactions when they are due.
+pub async fn watch(
+    watchtower: Arc<Watchtower>,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    finality_confirmations: u32,
+    interval: Duration,
+) {
+    loop {
+        match watchtower.jobs() {
+            Ok(jobs) => {
+                for job in jobs {
+                    let swap_id = job.swap_id;
+
+                    match check(&job, bitcoin_wallet.as_ref(), finality_confirmations).await {
+                        Ok(Action::Refunded) | Ok(Action::GiveUp) => {
+                            if let Err(e) = watchtower.remove(swap_id).await {
+                                tracing::warn!(%swap_id, "Failed to remove watch job: {:#}", e);
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(e) => tracing::warn!(%swap_id, "Failed to check watch job: {:#}", e),
+                    }
+                }
+            }
+            Err(e) => tracing::error!("Failed to load watch jobs: {:#}", e),
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Action {
+    Wait,
+    PublishCancel,
+    PublishRefund,
+    /// The refund transaction is final.
+    Refunded,
+    /// The Bitcoin can no longer be refunded by the watchtower, either because
+    /// the punish timelock expired before Bob handed over the refund
+    /// transaction or because the lock transaction was spent otherwise.
+    GiveUp,
+}
+
+async fn check(
+    job: &WatchJob,
+    bitcoin_wallet: &bitcoin::Wallet,
+    finality_confirmations: u32,
+) -> Result<Action> {
+    let swap_id = job.swap_id;
+
+    let tx_refund = match job.tx_refund() {
+        Some(tx_refund) => Some(
+            bitcoin_wallet
+                .least_confirmed_status_of_script(&tx_refund)
+                .await?,
+        ),
+        None => None,
+    };
+
+    let action = next_action(
+        job,
+        bitcoin_wallet.status_of_script(&job.tx_lock).await?,
+        bitcoin_wallet
+            .least_confirmed_status_of_script(&job.tx_cancel())
+            .await?,
+        tx_refund,
+        finality_confirmations,
+    );
+
+    match action {
+        Action::Wait => {}
+        Action::PublishCancel => publish(bitcoin_wallet, job.tx_cancel.clone(), "cancel").await,
+        Action::PublishRefund => {
+            if let Some(tx_refund) = job.tx_refund.clone() {
+                publish(bitcoin_wallet, tx_refund, "refund").await
+            }
+        }
+        Action::Refunded => tracing::info!(%swap_id, "Bitcoin were refunded, done watching"),
+        Action::GiveUp => tracing::warn!(
+            %swap_id,
+            "Bitcoin can no longer be refunded by the watchtower, giving up"
+        ),
+    }
+
+    Ok(action)
+}
+
+/// Failing to publish is only logged, it is tried again on the next check.
+async fn publish(bitcoin_wallet: &bitcoin::Wallet, transaction: Transaction, kind: &str) {
+    if let Err(e) = bitcoin_wallet.broadcast(transaction, kind).await {
+        tracing::warn!("{:#}", e);
+    }
+}
+
+/// `tx_refund` is `None` until Bob handed over the refund transaction.
+fn next_action(
+    job: &WatchJob,
+    tx_lock: ScriptStatus,
+    tx_cancel: ScriptStatus,
+    tx_refund: Option<ScriptStatus>,
+    finality_confirmations: u32,
+) -> Action {
+    if let Some(tx_refund) = tx_refund {
+        if tx_refund.is_confirmed_with(finality_confirmations) {
+            return Action::Refunded;
+        }
+        if tx_refund.has_been_seen() {
+            return Action::Wait;
+        }
+        // The refund transaction reveals `s_b`, only publish it once the lock
+        // transaction can no longer be redeemed
+        if tx_cancel.is_confirmed() {
+            return Action::PublishRefund;
+        }
+    }
+
+    if tx_cancel.is_confirmed_with(job.punish_timelock) {
+        return Action::GiveUp;
+    }
+    if tx_cancel.has_been_seen() {
+        return Action::Wait;
+    }
+
+    // The cancel transaction could not be published for as long as the punish
+    // timelock, the lock transaction was most likely redeemed
+    if tx_lock.is_confirmed_with(u32::from(job.cancel_timelock) + u32::from(job.punish_timelock)) {
+        return Action::GiveUp;
+    }
+    if tx_lock.is_confirmed_with(job.cancel_timelock) {
+        return Action::PublishCancel;
+    }
+
+    Action::Wait
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::{CancelTimelock, PunishTimelock};
+    use crate::network::watchtower::WatchedTx;
+    use ::bitcoin::{OutPoint, TxIn, TxOut};
+
+    const FINALITY: u32 = 3;
+
+    fn transaction(previous_output: OutPoint, sequence: u32) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output,
+                script_sig: Default::default(),
+                sequence,
+                witness: Vec::new(),
+            }],
+            output: vec![TxOut {
+                value: 10_000,
+                script_pubkey: Default::default(),
+            }],
+        }
+    }
+
+    fn job() -> WatchJob {
+        let tx_lock = transaction(OutPoint::default(), 0xFFFF_FFFF);
+        let tx_cancel = transaction(OutPoint::new(tx_lock.txid(), 0), 12);
+        let tx_refund = transaction(OutPoint::new(tx_cancel.txid(), 0), 0xFFFF_FFFF);
+
+        WatchJob {
+            swap_id: Uuid::new_v4(),
+            tx_lock: WatchedTx {
+                txid: tx_lock.txid(),
+                script: Default::default(),
+            },
+            cancel_timelock: CancelTimelock::new(12),
+            punish_timelock: PunishTimelock::new(6),
+            tx_cancel,
+            tx_refund: Some(tx_refund),
+        }
+    }
+
+    fn job_without_refund() -> WatchJob {
+        WatchJob {
+            tx_refund: None,
+            ..job()
+        }
+    }
+
+    #[test]
+    fn waits_for_cancel_timelock() {
+        let action = next_action(
+            &job_without_refund(),
+            ScriptStatus::from_confirmations(11),
+            ScriptStatus::Unseen,
+            None,
+            FINALITY,
+        );
+
+        assert_eq!(action, Action::Wait);
+    }
+
+    #[test]
+    fn publishes_cancel_once_cancel_timelock_expired_without_refund() {
+        let action = next_action(
+            &job_without_refund(),
+            ScriptStatus::from_confirmations(12),
+            ScriptStatus::Unseen,
+            None,
+            FINALITY,
+        );
+
+        assert_eq!(action, Action::PublishCancel);
+    }
+
+    #[test]
+    fn waits_for_refund_once_cancel_is_seen() {
+        let job = job_without_refund();
+        let lock = ScriptStatus::from_confirmations(13);
+
+        assert_eq!(
+            next_action(&job, lock, ScriptStatus::InMempool, None, FINALITY),
+            Action::Wait
+        );
+        assert_eq!(
+            next_action(
+                &job,
+                lock,
+                ScriptStatus::from_confirmations(5),
+                None,
+                FINALITY
+            ),
+            Action::Wait
+        );
+    }
+
+    #[test]
+    fn does_not_publish_refund_before_cancel_is_confirmed() {
+        let action = next_action(
+            &job(),
+            ScriptStatus::from_confirmations(13),
+            ScriptStatus::InMempool,
+            Some(ScriptStatus::Unseen),
+            FINALITY,
+        );
+
+        assert_eq!(action, Action::Wait);
+    }
+
+    #[test]
+    fn publishes_refund_once_cancel_is_confirmed() {
+        let action = next_action(
+            &job(),
+            ScriptStatus::from_confirmations(14),
+            ScriptStatus::from_confirmations(1),
+            Some(ScriptStatus::Unseen),
+            FINALITY,
+        );
+
+        assert_eq!(action, Action::PublishRefund);
+    }
+
+    #[test]
+    fn gives_up_once_punish_timelock_expired_without_refund() {
+        let action = next_action(
+            &job_without_refund(),
+            ScriptStatus::from_confirmations(20),
+            ScriptStatus::from_confirmations(6),
+            None,
+            FINALITY,
+        );
+
+        assert_eq!(action, Action::GiveUp);
+    }
+
+    #[test]
+    fn gives_up_if_cancel_cannot_be_published() {
+        let action = next_action(
+            &job_without_refund(),
+            ScriptStatus::from_confirmations(18),
+            ScriptStatus::Unseen,
+            None,
+            FINALITY,
+        );
+
+        assert_eq!(action, Action::GiveUp);
+    }
+
+    #[test]
+    fn done_once_refund_is_final() {
+        let job = job();
+        let lock = ScriptStatus::from_confirmations(20);
+        let cancel = ScriptStatus::from_confirmations(8);
+
+        let action = |refund| next_action(&job, lock, cancel, Some(refund), FINALITY);
+
+        assert_eq!(action(ScriptStatus::from_confirmations(2)), Action::Wait);
+        assert_eq!(
+            action(ScriptStatus::from_confirmations(FINALITY)),
+            Action::Refunded
+        );
+    }
+
+    #[tokio::test]
+    async fn only_submitter_can_replace_or_revoke_job() {
+        let dir = tempfile::tempdir().unwrap();
+        let watchtower = Watchtower::open(dir.path()).unwrap();
+        let bob = PeerId::random();
+        let mallory = PeerId::random();
+        let job = job();
+
+        watchtower.submit(bob, job.clone()).await.unwrap();
+
+        assert!(watchtower.submit(mallory, job.clone()).await.is_err());
+        assert!(watchtower.revoke(mallory, job.swap_id).await.is_err());
+        assert_eq!(watchtower.jobs().unwrap(), vec![job.clone()]);
+
+        watchtower.revoke(bob, job.swap_id).await.unwrap();
+
+        assert!(watchtower.jobs().unwrap().is_empty());
+        assert!(watchtower.revoke(bob, job.swap_id).await.is_err());
+    }
+}
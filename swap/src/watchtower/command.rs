@@ -0,0 +1,90 @@
+use crate::fs::system_data_dir;
+use libp2p::core::Multiaddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use url::Url;
+
+const DEFAULT_ELECTRUM_RPC_URL: &str = "ssl://electrum.blockstream.info:60002";
+const DEFAULT_LISTEN_ADDRESS: &str = "/ip4/0.0.0.0/tcp/9940";
+
+#[derive(structopt::StructOpt, Debug)]
+#[structopt(
+    name = "watchtower",
+    about = "Refunds the BTC of swaps while the CLI is offline",
+    author
+)]
+pub struct Arguments {
+    #[structopt(
+        long = "--data-dir",
+        help = "Provide the data directory path to be used to store application data",
+        default_value
+    )]
+    pub data: Data,
+
+    #[structopt(long, help = "Activate debug logging.")]
+    pub debug: bool,
+
+    #[structopt(subcommand)]
+    pub cmd: Command,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+pub enum Command {
+    /// Accept watch jobs and refund the swaps when their cancel timelock
+    /// expires
+    Start {
+        #[structopt(
+            long = "listen",
+            help = "The address to accept watch jobs on, can be given multiple times",
+            default_value = DEFAULT_LISTEN_ADDRESS
+        )]
+        listen: Vec<Multiaddr>,
+
+        #[structopt(long = "electrum-rpc",
+        help = "Provide the Bitcoin Electrum RPC URL",
+        default_value = DEFAULT_ELECTRUM_RPC_URL
+        )]
+        electrum_rpc_url: Url,
+
+        #[structopt(
+            long = "electrum-cross-check-rpc",
+            help = "Provide an additional Electrum RPC URL to cross-check the data of the primary Electrum server against, can be given multiple times"
+        )]
+        cross_check_electrum_rpc_urls: Vec<Url>,
+    },
+    /// Show the swaps that are watched
+    Jobs,
+}
+
+#[derive(Clone, Debug)]
+pub struct Data(pub PathBuf);
+
+/// Default location for storing data for the watchtower
+// Takes the default system data-dir and adds a `/watchtower`
+impl Default for Data {
+    fn default() -> Self {
+        Data(
+            system_data_dir()
+                .map(|proj_dir| Path::join(&proj_dir, "watchtower"))
+                .expect("computed valid path for data dir"),
+        )
+    }
+}
+
+impl FromStr for Data {
+    type Err = core::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Data(PathBuf::from_str(s)?))
+    }
+}
+
+impl ToString for Data {
+    fn to_string(&self) -> String {
+        self.0
+            .clone()
+            .into_os_string()
+            .into_string()
+            .expect("default datadir to be convertible to string")
+    }
+}
@@ -223,7 +223,7 @@ fn start_alice(
 ) -> (AliceApplicationHandle, Receiver<alice::Swap>) {
     let db = Arc::new(Database::open(db_path.as_path()).unwrap());
 
-    let mut swarm = swarm::alice(&seed).unwrap();
+    let mut swarm = swarm::alice(&seed, alice::RateLimiter::default()).unwrap();
     swarm.listen_on(listen_address).unwrap();
 
     let (event_loop, swap_handle) = alice::EventLoop::new(
@@ -233,7 +233,10 @@ fn start_alice(
         monero_wallet,
         db,
         FixedRate::default(),
+        bitcoin::Amount::ZERO,
         bitcoin::Amount::ONE_BTC,
+        alice::PeerLimits::default(),
+        None,
     )
     .unwrap();
 
@@ -281,6 +284,7 @@ async fn init_test_wallets(
 
     let btc_wallet = swap::bitcoin::Wallet::new(
         electrum_rpc_url,
+        vec![],
         datadir,
         seed.derive_extended_private_key(env_config.bitcoin_network)
             .expect("Could not create extended private key from seed"),
@@ -385,7 +389,7 @@ struct BobParams {
 impl BobParams {
     pub async fn new_swap_from_db(&self, swap_id: Uuid) -> Result<(bob::Swap, bob::EventLoop)> {
         let (event_loop, handle) = self.new_eventloop(swap_id).await?;
-        let db = Database::open(&self.db_path)?;
+        let db = Arc::new(Database::open(&self.db_path)?);
 
         let swap = bob::Swap::from_db(
             db,
@@ -407,7 +411,7 @@ impl BobParams {
         let swap_id = Uuid::new_v4();
 
         let (event_loop, handle) = self.new_eventloop(swap_id).await?;
-        let db = Database::open(&self.db_path)?;
+        let db = Arc::new(Database::open(&self.db_path)?);
 
         let swap = bob::Swap::new(
             db,
@@ -418,6 +422,8 @@ impl BobParams {
             handle,
             self.monero_wallet.get_main_address(),
             btc_amount,
+            bitcoin::TxLockFunding::default(),
+            None,
         );
 
         Ok((swap, event_loop))